@@ -0,0 +1,256 @@
+use anyhow::Context;
+use regex::Regex;
+use std::{
+  collections::HashMap,
+  io::{BufRead, BufReader},
+  path::Path,
+};
+use url::Url;
+
+use crate::{
+  endpoint::{EndpointSpec, MAX_TOTAL_WEIGHT},
+  replay::ReplayEntry,
+};
+
+/// The nginx and Apache combined log format, eg:
+/// `127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET /index.html HTTP/1.1" 200 2326 "-" "curl/8.0"`
+pub const COMBINED: &str = r#"^\S+ \S+ \S+ \[(?P<time>[^\]]+)\] "(?P<method>[A-Za-z]+) (?P<path>\S+)(?: [^"]*)?" \d{3} "#;
+
+/// How the requests of an access log are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogMode {
+  /// a weighted mix of the requests, weighted by the number of times each one appears in the log
+  Mix,
+  /// the requests in the order of the log, at their logged times with --replay-speed
+  Replay,
+}
+
+/// The options to import an access log
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+  /// a regex with the named groups method and path, and optionally time and host
+  pub format: Regex,
+  pub only_get: bool,
+  pub strip_query: bool,
+  /// send the requests logged for other hosts to the url instead of skipping them
+  pub rewrite_host: bool,
+}
+
+/// The counts of lines of an access log by what happened to them
+#[derive(Debug, Clone, Default)]
+pub struct LogSummary {
+  pub path: String,
+  pub lines: u64,
+  pub imported: u64,
+  /// lines that didn't match the format
+  pub unparsed: u64,
+  /// lines that were not GET requests with --only-get
+  pub filtered: u64,
+  /// lines for other hosts than the one of the url, without --rewrite-host
+  pub other_hosts: u64,
+}
+
+impl std::fmt::Display for LogSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} of {} lines imported", self.imported, self.lines)?;
+
+    let skipped = [(self.unparsed, "unparsed"), (self.filtered, "filtered"), (self.other_hosts, "for other hosts")]
+      .into_iter()
+      .filter(|(count, _)| *count != 0)
+      .map(|(count, reason)| format!("{count} {reason}"))
+      .collect::<Vec<_>>();
+
+    if !skipped.is_empty() {
+      write!(f, " ({})", skipped.join(", "))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// A request of an access log
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+  pub method: String,
+  /// the path and query of the request
+  pub target: String,
+  /// the logged time in seconds since the unix epoch
+  pub timestamp: Option<f64>,
+}
+
+/// Parses the requests of an access log to `url`, reading it line by line and passing each request to `each` as it's read
+pub fn load(path: &Path, options: &LogOptions, url: &Url, mut each: impl FnMut(LogEntry)) -> Result<LogSummary, anyhow::Error> {
+  let file = std::fs::File::open(path).with_context(|| format!("error reading access log {}", path.display()))?;
+
+  let mut summary = LogSummary {
+    path: path.display().to_string(),
+    ..LogSummary::default()
+  };
+
+  let mut reader = BufReader::new(file);
+  let mut buf = Vec::new();
+  for i in 1.. {
+    buf.clear();
+    let read = reader
+      .read_until(b'\n', &mut buf)
+      .with_context(|| format!("error reading access log {} line {}", path.display(), i))?;
+    if read == 0 {
+      break;
+    }
+
+    // the logged user agents and referers are not always valid utf-8
+    let line = String::from_utf8_lossy(&buf);
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    summary.lines += 1;
+
+    let Some(captures) = options.format.captures(line) else {
+      summary.unparsed += 1;
+      continue;
+    };
+
+    let (Some(method), Some(target)) = (captures.name("method"), captures.name("path")) else {
+      summary.unparsed += 1;
+      continue;
+    };
+
+    let method = method.as_str().to_uppercase();
+    if options.only_get && method != "GET" {
+      summary.filtered += 1;
+      continue;
+    }
+
+    // the target can be an absolute url, like in the logs of a proxy
+    let (host, target) = match Url::parse(target.as_str()) {
+      Ok(absolute) => {
+        let target = match absolute.query() {
+          Some(query) => format!("{}?{query}", absolute.path()),
+          None => absolute.path().to_string(),
+        };
+        (absolute.host_str().map(String::from), target)
+      }
+      Err(_) => (None, target.as_str().to_string()),
+    };
+
+    let host = captures.name("host").map(|host| host.as_str().to_string()).or(host);
+    if let Some(host) = host {
+      // the logged host can include the port
+      let host = host.split(':').next().unwrap_or_default();
+      if !options.rewrite_host && !url.host_str().is_some_and(|url_host| url_host.eq_ignore_ascii_case(host)) {
+        summary.other_hosts += 1;
+        continue;
+      }
+    }
+
+    let target = match options.strip_query {
+      true => target.split('?').next().unwrap_or_default().to_string(),
+      false => target,
+    };
+
+    let timestamp = captures.name("time").and_then(|time| parse_time(time.as_str()));
+
+    summary.imported += 1;
+    each(LogEntry {
+      method,
+      target,
+      timestamp,
+    });
+  }
+
+  if summary.imported == 0 {
+    anyhow::bail!("access log {} has no requests to import, {}", path.display(), summary);
+  }
+
+  Ok(summary)
+}
+
+/// The requests of an access log as a weighted mix, the `top` most frequent requests weighted by their number of appearances
+///
+/// Only the count of each request is kept while reading the log, so a large log doesn't need to fit in memory.
+/// The weights are scaled down proportionally to a total of 10000, keeping a weight of at least 1 for each request
+pub fn mix(path: &Path, options: &LogOptions, url: &Url, top: usize) -> Result<(Vec<EndpointSpec>, LogSummary), anyhow::Error> {
+  let mut counts = HashMap::<(String, String), u64>::new();
+  let summary = load(path, options, url, |entry| {
+    *counts.entry((entry.method, entry.target)).or_default() += 1;
+  })?;
+
+  let mut counts = counts.into_iter().collect::<Vec<_>>();
+  // the ties are sorted by method and path so the mix is the same on each run
+  counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+  counts.truncate(top);
+
+  let total: u64 = counts.iter().map(|(_, count)| count).sum();
+  let scale = match total > MAX_TOTAL_WEIGHT {
+    true => MAX_TOTAL_WEIGHT as f64 / total as f64,
+    false => 1.0,
+  };
+
+  let endpoints = counts
+    .into_iter()
+    .map(|((method, target), count)| EndpointSpec {
+      weight: ((count as f64 * scale).round() as u32).max(1),
+      name: None,
+      method,
+      target,
+      body: None,
+      headers: Vec::new(),
+      template: false,
+    })
+    .collect();
+
+  Ok((endpoints, summary))
+}
+
+/// The requests of an access log as the requests of a replay, in the order of the log
+pub fn replay(path: &Path, options: &LogOptions, url: &Url) -> Result<(Vec<ReplayEntry>, LogSummary), anyhow::Error> {
+  let mut entries = Vec::new();
+  let summary = load(path, options, url, |entry| {
+    entries.push(ReplayEntry {
+      method: entry.method,
+      target: entry.target,
+      headers: Vec::new(),
+      body: None,
+      timestamp: entry.timestamp,
+    })
+  })?;
+
+  Ok((entries, summary))
+}
+
+/// Parses a logged time, either as seconds since the unix epoch (eg: 1700000000.123, like nginx $msec)
+/// or in the common log format (eg: 10/Oct/2000:13:55:36 -0700)
+fn parse_time(s: &str) -> Option<f64> {
+  if let Ok(seconds) = s.parse::<f64>() {
+    return Some(seconds);
+  }
+
+  let re = regex_static::static_regex!(r"^(\d{2})/([A-Za-z]{3})/(\d{4}):(\d{2}):(\d{2}):(\d{2})(?:\.(\d+))? ([+-])(\d{2})(\d{2})$");
+  let captures = re.captures(s.trim())?;
+  let number = |i: usize| captures.get(i).unwrap().as_str().parse::<i64>().unwrap();
+
+  let month = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"]
+    .iter()
+    .position(|month| captures[2].eq_ignore_ascii_case(month))? as i64
+    + 1;
+
+  let days = days_from_civil(number(3), month, number(1));
+  let seconds = days * 86_400 + number(4) * 3600 + number(5) * 60 + number(6);
+  let fraction = captures.get(7).map_or(0.0, |fraction| format!("0.{}", fraction.as_str()).parse::<f64>().unwrap());
+
+  let offset = (number(9) * 3600 + number(10) * 60) * if &captures[8] == "-" { -1 } else { 1 };
+
+  Some((seconds - offset) as f64 + fraction)
+}
+
+/// The number of days from the unix epoch to a date of the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
@@ -1,19 +1,48 @@
 use anyhow::Context;
 use clap::Parser;
 use std::{
-  net::{SocketAddr, ToSocketAddrs}, path::Path, time::Duration
+  collections::HashMap, net::{IpAddr, SocketAddr, ToSocketAddrs}, path::Path, time::Duration
 };
 use url::Url;
 
+use crate::{
+  access_log::{LogMode, LogOptions, LogSummary},
+  check::Check,
+  har::{HarMode, HarSummary},
+  output::Output,
+  data::{Data, DataOrder},
+  distribution::Distribution,
+  endpoint::{Body, Endpoint, EndpointSpec},
+  extract::{Extract, Extractor},
+  profile::Profile,
+  stage::{Stage, Target},
+  template::{Slot, Template},
+  threshold::Threshold,
+};
+
 #[cfg(feature = "h2")]
 use http::Uri;
 
+#[cfg(feature = "openapi")]
+use crate::openapi::{OpenApiSummary, OperationSpec};
+
+#[cfg(feature = "script")]
+use crate::script::{ScriptApi, ScriptSource, WrkRequest};
+
+#[cfg(feature = "plugin")]
+use crate::plugin::PluginSource;
+
+#[cfg(feature = "h1")]
+use crate::template::H1Template;
+#[cfg(feature = "h2")]
+use crate::template::H2Template;
+
 #[cfg(feature = "tls")]
 use rustls::pki_types::ServerName;
 #[cfg(feature = "tls")]
 use std::sync::Arc;
 
-fn parse_duration(s: &str) -> Result<Duration, String> {
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
   let re = regex_static::static_regex!(r"^([0-9]+(?:\.[0-9]+)?)(ns|us|ms|s|m|h|d)$");
   if let Some(captures) = re.captures(s.trim()) {
     let float = captures.get(1).unwrap().as_str().parse::<f64>().unwrap();
@@ -38,6 +67,40 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
   }
 }
 
+/// Parses a size in bytes, either as a number of bytes or with a unit of kb, mb or gb in powers of 1024 (eg: 512kb or 16mb)
+pub fn parse_size(s: &str) -> Result<usize, String> {
+  let re = regex_static::static_regex!(r"^([0-9]+(?:\.[0-9]+)?)\s*(b|kb|mb|gb)?$");
+  let invalid = || format!("invalid size {s}, must be a number of bytes optionally followed by a unit that must be one of b, kb, mb or gb (eg: 16mb)");
+  let captures = re.captures(s.trim()).ok_or_else(invalid)?;
+  let number = captures[1].parse::<f64>().map_err(|_| invalid())?;
+
+  let multiplier = match captures.get(2).map(|unit| unit.as_str()) {
+    None | Some("b") => 1.0,
+    Some("kb") => 1024.0,
+    Some("mb") => 1024.0 * 1024.0,
+    Some("gb") => 1024.0 * 1024.0 * 1024.0,
+    _ => unreachable!(),
+  };
+
+  Ok((number * multiplier) as usize)
+}
+
+/// Parses a ratio either as a percentage (eg: 0.1%) or as a fraction (eg: 0.001)
+pub fn parse_ratio(s: &str) -> Result<f64, String> {
+  let s = s.trim();
+  let ratio = match s.strip_suffix('%') {
+    Some(percent) => percent.trim().parse::<f64>().map(|percent| percent / 100.0),
+    None => s.parse::<f64>(),
+  }
+  .map_err(|_| format!("invalid ratio {s}, must be a percentage (eg: 0.1%) or a fraction (eg: 0.001)"))?;
+
+  if !(0.0..=1.0).contains(&ratio) {
+    return Err(format!("invalid ratio {s}, must be between 0% and 100%"));
+  }
+
+  Ok(ratio)
+}
+
 #[derive(Debug, Parser)]
 #[command(
   disable_version_flag = true,
@@ -46,28 +109,206 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
   arg_required_else_help = true
 )]
 pub struct Args {
-  /// the URL to benchmark
+  /// the URL to benchmark, with --template the path and query, the headers and the body can have variables rendered for each request:
+  /// {{uuid}}, {{seq}}, {{rand_int min max}}, {{now_ms}} and {{thread_id}} (eg: /items/{{seq}})
   #[arg(env = "URL")]
-  pub url: String,
+  pub url: Option<String>,
+
+  /// Read the options from a TOML or YAML file (by its .yaml or .yml extension), the command line options override the ones in the file
+  #[cfg(feature = "config")]
+  #[arg(long, env = "CONFIG")]
+  pub config: Option<String>,
 
   /// Number of connections to keep open
   #[arg(short, long, default_value_t = 10, env = "CONCURRENCY")]
   pub concurrency: usize,
 
-  /// Duration for the test
+  /// Duration for the test (default: 10s, or until all the requests are fulfilled with --requests)
   #[arg(
     short,
     long,
-    default_value = "10s",
     env = "DURATION",
     value_parser = parse_duration
   )]
-  pub duration: Duration,
+  pub duration: Option<Duration>,
+
+  /// Stop after exactly this number of requests across all threads, either fulfilled or failed (a failed connection attempt takes one),
+  /// when used with --duration the test stops at whichever comes first
+  #[arg(short = 'n', long, env = "REQUESTS")]
+  pub requests: Option<u64>,
+
+  /// Warm-up period before the test, requests are sent but excluded from the results
+  #[arg(
+    short = 'w',
+    long,
+    env = "WARMUP",
+    value_parser = parse_duration
+  )]
+  pub warmup: Option<Duration>,
 
   /// Number of threads to use
   #[arg(short, long, default_value_t = 2, env = "THREADS")]
   pub threads: usize,
 
+  /// Target request rate in requests per second across all connections, enables constant-throughput mode
+  #[arg(short = 'R', long, env = "RATE")]
+  pub rate: Option<f64>,
+
+  /// Follow the request rate of a time series file, a CSV with a t_seconds,rate pair per line or a JSON array of [t_seconds, rate] pairs,
+  /// interpolated linearly between points, when set the duration defaults to the duration of the profile
+  #[arg(long, env = "RATE_PROFILE")]
+  pub rate_profile: Option<String>,
+
+  /// Divide the times of the rate profile by this factor (eg: 144 replays a 24h profile in 10m)
+  #[arg(long, default_value_t = 1.0, env = "TIME_SCALE")]
+  pub time_scale: f64,
+
+  /// Report the requested and achieved rate for each interval of this duration in constant-throughput mode
+  /// (default: a twentieth of the duration with a rate profile)
+  #[arg(long, value_parser = parse_duration, env = "INTERVAL")]
+  pub interval: Option<Duration>,
+
+  /// Distribution of the arrival times of requests in constant-throughput mode, one of constant, poisson,
+  /// uniform[:jitter] (eg: uniform:20%) or bursty:on:off (eg: bursty:1s:4s)
+  #[arg(long, default_value = "constant", value_parser = crate::distribution::parse_distribution, env = "DISTRIBUTION")]
+  pub distribution: Distribution,
+
+  /// Seed for the random arrival times and template variables, runs with the same seed send the same requests at the same times (default: random)
+  #[arg(long, env = "SEED")]
+  pub seed: Option<u64>,
+
+  /// Add a stage to the load profile in the format of duration:connections (eg: 30s:10) or duration:rate/s (eg: 2m:5000/s),
+  /// each stage linearly ramps from the previous target to its own (the first one from zero, or from its own target after a warm-up),
+  /// when set the duration is the sum of the stages
+  #[arg(long, value_parser = crate::stage::parse_stage, value_delimiter = ',', env = "STAGE")]
+  pub stage: Vec<Stage>,
+
+  /// Add an endpoint to the request mix in the format of `weight method target [body=value] [header=key:value]...`
+  /// (eg: `70 GET /items`, `10 POST /orders body=@order.json header=content-type:application/json`),
+  /// the target is a path relative to the url or a full url with the same scheme, host and port,
+  /// when set only the endpoints are requested and the method option is ignored
+  #[arg(short = 'e', long, value_parser = crate::endpoint::parse_endpoint, env = "ENDPOINT")]
+  pub endpoint: Vec<EndpointSpec>,
+
+  /// Render the template variables in the url, the endpoints, the headers and the body (eg: /items/{{seq}}),
+  /// implied by a data file or a scenario, the requests of a replay file, an access log, a HAR file, a curl command or an OpenAPI spec
+  /// are always sent as they are
+  #[arg(long, default_value_t = false, env = "TEMPLATE")]
+  pub template: bool,
+
+  /// Feed the template variables from the rows of a data file, a CSV with the names of the columns in the first line
+  /// or a JSON lines file (by its .jsonl or .ndjson extension) with an object per line, each column is a variable (eg: {{user_id}})
+  #[arg(long, env = "DATA")]
+  pub data: Option<String>,
+
+  /// The order in which the rows of the data file are used, the rows are split between the threads so they don't share them
+  #[arg(long, value_enum, default_value_t = DataOrder::Sequential, env = "DATA_ORDER")]
+  pub data_order: DataOrder,
+
+  /// Replay the requests of a JSON lines file, with an object per line in the format of
+  /// {"method": "POST", "path": "/orders", "headers": {"key": "value"}, "body": "...", "timestamp": 1700000000.5}
+  /// (or the body as base64 in body_base64), the requests are sent in the order of the file and cycled through
+  #[arg(long, env = "REPLAY")]
+  pub replay: Option<String>,
+
+  /// Send the requests of the replay file at their recorded timestamps sped up by this factor (eg: 2 replays them twice as fast),
+  /// when set the duration defaults to the time to replay all of them once
+  #[arg(long, env = "REPLAY_SPEED")]
+  pub replay_speed: Option<f64>,
+
+  /// Import the requests of an nginx or Apache access log in the combined format, as a weighted mix or as a replay
+  #[arg(long, env = "ACCESS_LOG")]
+  pub access_log: Option<String>,
+
+  /// Parse the lines of the access log with this regex instead of the combined format, it must have the named groups method and path,
+  /// and optionally time (in seconds since the unix epoch or in the common log format) and host
+  #[arg(long, env = "ACCESS_LOG_FORMAT")]
+  pub access_log_format: Option<String>,
+
+  /// How the requests of the access log are sent, the replay uses the times of the log with --replay-speed
+  #[arg(long, value_enum, default_value_t = LogMode::Mix, env = "ACCESS_LOG_MODE")]
+  pub access_log_mode: LogMode,
+
+  /// Import only the most frequent requests of the access log as a mix
+  #[arg(long, default_value_t = 100, env = "ACCESS_LOG_TOP")]
+  pub access_log_top: usize,
+
+  /// Import only the GET requests of the access log
+  #[arg(long, default_value_t = false, env = "ONLY_GET")]
+  pub only_get: bool,
+
+  /// Remove the query strings of the requests of the access log
+  #[arg(long, default_value_t = false, env = "STRIP_QUERY")]
+  pub strip_query: bool,
+
+  /// Send the requests of the access log logged for other hosts to the url, instead of skipping them
+  #[arg(long, default_value_t = false, env = "REWRITE_HOST")]
+  pub rewrite_host: bool,
+
+  /// Import the requests of a HAR file captured with the browser devtools, the requests to other hosts than the one of the url are skipped
+  #[arg(long, env = "HAR")]
+  pub har: Option<String>,
+
+  /// How the requests of the HAR file are sent, as a user session per connection or as a weighted mix
+  #[arg(long, value_enum, default_value_t = HarMode::Session, env = "HAR_MODE")]
+  pub har_mode: HarMode,
+
+  /// Generate the request mix from the operations of an OpenAPI 3 spec file, in JSON or YAML (by its .yaml or .yml extension),
+  /// the paths of the spec are relative to the path of the url and each operation is reported by its operationId
+  #[cfg(feature = "openapi")]
+  #[arg(long, env = "OPENAPI")]
+  pub openapi: Option<String>,
+
+  /// Request only these operations of the OpenAPI spec, by operationId with an optional weight (eg: listItems:5,createItem),
+  /// use * for all the other ones (eg: createItem:10,*:1), by default all of them are requested with a weight of 1
+  #[cfg(feature = "openapi")]
+  #[arg(long, value_parser = crate::openapi::parse_operation, value_delimiter = ',', env = "OPERATION")]
+  pub operation: Vec<OperationSpec>,
+
+  /// Send the steps of a scenario file in order on each connection, like a user session, binding values extracted
+  /// from the responses to template variables for the next steps (eg: the token of a login), JSON or YAML
+  #[arg(long, env = "SCENARIO")]
+  pub scenario: Option<String>,
+
+  /// Run a lua script with the hooks request() to build each request, response(status, headers, body) called with each response
+  /// and done(report) called with the results, each thread runs the hooks in its own interpreter
+  #[cfg(feature = "script")]
+  #[arg(short, long, env = "SCRIPT")]
+  pub script: Option<String>,
+
+  /// The api of the script, wrk runs the scripts of wrk unmodified, with the wrk table and the hooks setup(thread), init(args),
+  /// delay(), request(), response(status, headers, body) and done(summary, latency, requests), only with http/1.1
+  #[cfg(feature = "script")]
+  #[arg(long, value_enum, default_value_t = ScriptApi::Rload, env = "SCRIPT_API")]
+  pub script_api: ScriptApi,
+
+  /// The arguments passed to the script after -- (eg: rload --script auth.lua URL -- user password)
+  #[cfg(feature = "script")]
+  #[arg(last = true)]
+  pub script_args: Vec<String>,
+
+  /// Generate the requests with a WebAssembly module (.wasm or .wat) that exports its memory and rload_next(conn) returning
+  /// the raw http/1.1 request in its memory as ptr << 32 | len (0 for the request of the endpoint), optionally rload_init(thread)
+  /// and rload_observe(conn, status, latency_us), each thread runs its own instance
+  #[cfg(feature = "plugin")]
+  #[arg(long, env = "PLUGIN")]
+  pub plugin: Option<String>,
+
+  /// The time each call to the plugin can take, a call over budget is interrupted and fails its request as a plugin-budget error
+  #[cfg(feature = "plugin")]
+  #[arg(long, default_value = "1ms", env = "PLUGIN_BUDGET", value_parser = parse_duration)]
+  pub plugin_budget: Duration,
+
+  /// Take the url, the method, the headers and the body from a curl command line
+  /// (eg: --curl 'curl -X POST -H "content-type: application/json" -d @order.json https://example.com/orders'),
+  /// the curl options that can't be honored are rejected, also available as `rload from-curl '<curl command>'`,
+  /// with --compressed the responses are not decompressed, the response hook of a script gets them compressed
+  #[arg(long, env = "CURL")]
+  pub curl: Option<String>,
+
+  /// Connect to this address instead of resolving the host of the url, in the format of host:port:address (eg: example.com:443:127.0.0.1)
+  #[arg(long, env = "RESOLVE")]
+  pub resolve: Option<String>,
 
   /// The method to use (default: GET)
   #[arg(short, long, default_value = "GET", env = "METHOD")]
@@ -81,6 +322,39 @@ pub struct Args {
   #[arg(short = 'H', long, value_parser, value_delimiter = ',', env = "HEADER")]
   pub header: Vec<String>,
 
+  /// Fail the requests whose response has a status other than one of this ones, a list of statuses or classes of statuses
+  /// (eg: 200,201 or 2xx,304), a failed check is counted as an assertion error
+  #[arg(long, value_parser = crate::check::parse_status, env = "EXPECT_STATUS")]
+  pub expect_status: Option<Check>,
+
+  /// Fail the requests whose response body doesn't contain this text, the body is buffered up to --max-body
+  #[arg(long, value_parser = crate::check::parse_body_contains, env = "EXPECT_BODY_CONTAINS")]
+  pub expect_body_contains: Vec<Check>,
+
+  /// Fail the requests whose response body doesn't match this regex, the body is buffered up to --max-body
+  #[arg(long, value_parser = crate::check::parse_body_regex, env = "EXPECT_BODY_REGEX")]
+  pub expect_body_regex: Vec<Check>,
+
+  /// Fail the requests whose response doesn't have this header, in the format of `name: value` (eg: 'content-type: application/json')
+  /// where the value must be contained in the one of the response ignoring case, or only the name to accept any value
+  #[arg(long, value_parser = crate::check::parse_header, env = "EXPECT_HEADER")]
+  pub expect_header: Vec<Check>,
+
+  /// Fail the requests whose response body doesn't have this sha256 digest, in hex, the body is buffered up to --max-body
+  #[arg(long, value_parser = crate::check::parse_body_sha256, env = "EXPECT_BODY_SHA256")]
+  pub expect_body_sha256: Option<Check>,
+
+  /// Maximum size of a response body buffered for the checks, the extractors of a scenario or the script,
+  /// in bytes or with a unit (eg: 512kb or 16mb), a larger body fails its request as a body-too-large error
+  #[arg(long, value_parser = parse_size, default_value = "8mb", env = "MAX_BODY")]
+  pub max_body: usize,
+
+  /// Fail the run with exit code 99 if the results don't meet this threshold, a metric, an operator and a value
+  /// (eg: p99<50ms, rps>10000, errors<0.1%, status_5xx==0), the metrics are pN (eg: p99.9), mean and max of the latencies,
+  /// rps, fulfilled, errors and status_NNN or status_Nxx, the last two as a count or a percentage of the requests
+  #[arg(long, value_parser = crate::threshold::parse_threshold, value_delimiter = ',', env = "THRESHOLD")]
+  pub threshold: Vec<Threshold>,
+
   /// Timeout for each request
   #[cfg(feature = "timeout")]
   #[arg(
@@ -106,6 +380,16 @@ pub struct Args {
   #[arg(short = 'l', long, default_value_t = false, env = "LATENCY")]
   pub latency: bool,
 
+  /// The format of the report, text, wrk, the same summary as wrk printed to stdout, json, the whole report with a versioned schema,
+  /// csv, a row with the summary of the run, or markdown, tables for a PR description or $GITHUB_STEP_SUMMARY, all of them printed to stdout
+  #[arg(long, value_enum, default_value_t = Output::Text, env = "OUTPUT")]
+  pub output: Output,
+
+  /// Write the report to this file in the format of --output instead, then the report is also printed to stderr as text,
+  /// csv and markdown are appended to the file so many runs accumulate in one table or summary
+  #[arg(long, env = "OUTPUT_FILE")]
+  pub output_file: Option<String>,
+
   /// Use http2 protocol
   #[cfg(all(feature = "h1", feature = "h2"))]
   #[arg(short = '2', long, default_value_t = false, env = "H2")]
@@ -141,11 +425,14 @@ pub enum Request<'a> {
   H1 {
     // this is the pre-encoded request to write directly to the socket
     buf: &'a [u8],
+    // the template rendered for each request instead, when the request has variables
+    template: Option<&'a H1Template>,
   },
   #[cfg(feature = "h2")]
   H2 {
     req: &'a http::Request<()>,
     body: Option<&'a bytes::Bytes>,
+    template: Option<&'a H2Template>,
   }
 }
 
@@ -155,25 +442,122 @@ pub struct RunConfig<'a> {
   pub addr: SocketAddr,
   pub threads: usize,
   pub concurrency: usize,
-  pub method: &'a str,
-  pub body_len: usize,
+  /// the target request rate in constant-throughput mode
+  pub rate: Option<&'a Profile>,
+  /// the path of the file the rate profile was loaded from
+  pub rate_profile: Option<&'a str>,
+  /// the length of the intervals of the rate report
+  pub interval: Option<Duration>,
+  /// the distribution of the arrival times in constant-throughput mode
+  pub distribution: Distribution,
+  /// the seed for random arrival times
+  pub seed: u64,
+  /// the number of active connections when ramped by stages
+  pub connections: Option<&'a Profile>,
+  pub stages: &'a [Stage],
   pub disable_keepalive: bool,
   #[cfg(feature = "timeout")]
   pub timeout: Option<Duration>,
   #[cfg(feature = "latency")]
   pub latency: bool,
-  pub request: Request<'a>,
+  /// the format of the report
+  pub output: Output,
+  /// the file the report is written to
+  pub output_file: Option<&'a str>,
+  /// the endpoints of the request mix, all of them use the same protocol
+  pub endpoints: &'a [Endpoint<'a>],
+  /// the order in which the endpoints are requested, as indexes of `endpoints`
+  pub mix: &'a [usize],
+  /// the rows that feed the template variables
+  pub data: Option<&'a Data>,
+  /// the path of the file the data was loaded from
+  pub data_file: Option<&'a str>,
+  pub data_order: DataOrder,
+  /// the path of the replay file the endpoints were loaded from, one endpoint per request
+  pub replay: Option<&'a str>,
+  /// the speed factor of a replay at the recorded timestamps
+  pub replay_speed: Option<f64>,
+  /// the lines imported from an access log
+  pub access_log: Option<&'a LogSummary>,
+  /// the requests imported from a HAR file
+  pub har: Option<&'a HarSummary>,
+  pub har_mode: HarMode,
+  /// the operations of an OpenAPI spec the endpoints were generated from
+  #[cfg(feature = "openapi")]
+  pub openapi: Option<&'a OpenApiSummary>,
+  /// the path of the scenario file the endpoints were loaded from, one endpoint per step
+  pub scenario: Option<&'a str>,
+  /// the script with the hooks of the requests
+  #[cfg(feature = "script")]
+  pub script: Option<&'a ScriptSource>,
+  /// the WebAssembly module that generates the requests
+  #[cfg(feature = "plugin")]
+  pub plugin: Option<&'a PluginSource>,
+  /// the checks of the responses, when there are none the responses are not buffered for them
+  pub checks: &'a [Check],
+  /// the maximum size of a buffered response body
+  pub max_body: usize,
+  /// the thresholds the results are evaluated against at the end of the run
+  pub thresholds: &'a [Threshold],
+  /// each connection sends the endpoints in the order of the mix, like a user session, instead of taking the next one of the thread
+  pub sessions: bool,
   #[cfg(feature = "tls")]
   pub tls: Option<&'a Tls<'a>>,
-  pub duration: Duration,
+  /// the duration of the test, `None` runs until the requests are fulfilled
+  pub duration: Option<Duration>,
+  /// the number of requests to fulfill before stopping
+  pub requests: Option<u64>,
+  /// the warm-up period that runs before the measured duration, the rate and connections profiles already include it
+  pub warmup: Duration,
 }
 
 impl RunConfig<'static> {
   pub fn from_args(args: Args) -> Result<Self, anyhow::Error> {
     let Args {
       url,
+      #[cfg(feature = "config")]
+      config: _,
       threads,
       concurrency,
+      rate,
+      rate_profile,
+      time_scale,
+      interval,
+      distribution,
+      seed,
+      stage,
+      endpoint,
+      template,
+      data,
+      data_order,
+      replay,
+      replay_speed,
+      access_log,
+      access_log_format,
+      access_log_mode,
+      access_log_top,
+      only_get,
+      strip_query,
+      rewrite_host,
+      har,
+      har_mode,
+      #[cfg(feature = "openapi")]
+      openapi,
+      #[cfg(feature = "openapi")]
+      operation,
+      scenario,
+      #[cfg(feature = "script")]
+      script,
+      #[cfg(feature = "script")]
+      script_api,
+      #[cfg(feature = "script")]
+      script_args,
+      #[cfg(feature = "plugin")]
+      plugin,
+      #[cfg(feature = "plugin")]
+      plugin_budget,
+      curl,
+      resolve,
       method,
       body,
       disable_keepalive,
@@ -181,14 +565,67 @@ impl RunConfig<'static> {
       timeout,
       #[cfg(feature = "latency")]
       latency,
+      output,
+      output_file,
       #[cfg(all(feature = "h1", feature = "h2"))]
       h2,
       duration,
+      requests,
+      warmup,
       header,
+      expect_status,
+      expect_body_contains,
+      expect_body_regex,
+      expect_header,
+      expect_body_sha256,
+      max_body,
+      threshold,
       version: _,
       help: _,
     } = args;
 
+    #[cfg(all(feature = "h1", feature = "h2"))]
+    let mut h2 = h2;
+    let mut header = header;
+
+    // the request of a curl command line takes the place of the url, the method and the body, its headers are added to the others
+    let from_curl = curl.is_some();
+    let body = body.map(|body| Body::parse(&body));
+    let (url, method, body, resolve) = match curl {
+      None => (url, method, body, resolve),
+      Some(command) => {
+        let curl = crate::curl::parse(&command).context("error translating curl command")?;
+
+        if url.is_some() {
+          anyhow::bail!("url cannot be used with a curl command, the url is taken from the command");
+        }
+
+        if body.is_some() && curl.body.is_some() {
+          anyhow::bail!("body option cannot be used with a curl command that sends data");
+        }
+
+        if resolve.is_some() && curl.resolve.is_some() {
+          anyhow::bail!("resolve option cannot be used with a curl command with --resolve");
+        }
+
+        if curl.compressed && (!expect_body_contains.is_empty() || !expect_body_regex.is_empty() || expect_body_sha256.is_some() || scenario.is_some()) {
+          anyhow::bail!("curl option --compressed cannot be used with the checks of the body or a scenario, the responses are not decompressed");
+        }
+
+        if curl.http2 {
+          #[cfg(all(feature = "h1", feature = "h2"))]
+          {
+            h2 = true;
+          }
+          #[cfg(not(feature = "h2"))]
+          anyhow::bail!("curl option --http2 requires feature h2 to be enabled at compile time");
+        }
+
+        header.extend(curl.headers);
+        (Some(curl.url), curl.method.unwrap_or(method), body.or(curl.body), resolve.or(curl.resolve))
+      }
+    };
+
     if threads == 0 {
       anyhow::bail!("threads option must be greater than 0");
     }
@@ -197,24 +634,319 @@ impl RunConfig<'static> {
       anyhow::bail!("concurrency option must be greater than 0");
     }
 
-    if duration.as_nanos() == 0 {
-      anyhow::bail!("duration option must be equal or greater than 1ns");
+    if let Some(rate) = rate {
+      if !rate.is_finite() || rate <= 0.0 {
+        anyhow::bail!("rate option must be a number greater than 0");
+      }
+    }
+
+    if requests == Some(0) {
+      anyhow::bail!("requests option must be greater than 0");
     }
 
+    let stages: &'static [Stage] = stage.leak();
+
+    let warmup = warmup.unwrap_or_default();
+
+    let rate_profile: Option<&'static str> = rate_profile.map(|path| &*path.leak());
+
+    let url = url.context("missing url, set it as the first argument or as url in the config file")?;
     let url: &'static _ = Box::leak(Box::new(url.parse::<Url>().context("error parsing url")?));
 
+    let mut replay_file: Option<&'static str> = replay.map(|path| &*path.leak());
+    let mut replay_entries = replay_file.map(|path| crate::replay::load(Path::new(path))).transpose()?;
+
+    let mut endpoint = endpoint;
+    let access_log = match access_log {
+      None => {
+        if access_log_format.is_some() {
+          anyhow::bail!("access-log-format option requires an access log");
+        }
+        None
+      }
+
+      Some(path) => {
+        let format = regex::Regex::new(access_log_format.as_deref().unwrap_or(crate::access_log::COMBINED))
+          .context("invalid access-log-format regex")?;
+
+        if !format.capture_names().any(|name| name == Some("method")) || !format.capture_names().any(|name| name == Some("path")) {
+          anyhow::bail!("access-log-format regex must have the named groups method and path (eg: (?P<method>\\S+))");
+        }
+
+        let options = LogOptions {
+          format,
+          only_get,
+          strip_query,
+          rewrite_host,
+        };
+
+        let summary = match access_log_mode {
+          LogMode::Mix => {
+            if !endpoint.is_empty() {
+              anyhow::bail!("endpoints cannot be used with an access log imported as a mix");
+            }
+
+            if access_log_top == 0 {
+              anyhow::bail!("access-log-top option must be greater than 0");
+            }
+
+            let (endpoints, summary) = crate::access_log::mix(Path::new(&path), &options, url, access_log_top)?;
+            endpoint = endpoints;
+            summary
+          }
+
+          LogMode::Replay => {
+            if replay_file.is_some() {
+              anyhow::bail!("replay option cannot be used with an access log imported as a replay");
+            }
+
+            let (entries, summary) = crate::access_log::replay(Path::new(&path), &options, url)?;
+            replay_file = Some(path.leak());
+            replay_entries = Some(entries);
+            summary
+          }
+        };
+
+        Some(&*Box::leak(Box::new(summary)))
+      }
+    };
+
+    // the weights of the requests of a HAR file imported as a mix, the equal requests are merged
+    let mut har_weights: Option<Vec<u32>> = None;
+    let har = match har {
+      None => None,
+      Some(path) => {
+        if replay_entries.is_some() {
+          anyhow::bail!("har option cannot be used with a replay file or an access log imported as a replay");
+        }
+
+        if !endpoint.is_empty() {
+          anyhow::bail!("har option cannot be used with endpoints or an access log imported as a mix");
+        }
+
+        if body.is_some() {
+          anyhow::bail!("body option cannot be used with a HAR file, the body of each request is in the file");
+        }
+
+        if replay_speed.is_some() {
+          anyhow::bail!("replay-speed option cannot be used with a HAR file, only with a replay file or an access log");
+        }
+
+        let (entries, summary) = crate::har::load(Path::new(&path), url)?;
+
+        replay_entries = Some(match har_mode {
+          HarMode::Session => entries,
+          HarMode::Mix => {
+            let (entries, weights) = crate::har::mix(entries);
+            har_weights = Some(weights);
+            entries
+          }
+        });
+
+        Some(&*Box::leak(Box::new(summary)))
+      }
+    };
+
+
+    #[cfg(feature = "openapi")]
+    let openapi = match openapi {
+      None => {
+        if !operation.is_empty() {
+          anyhow::bail!("operation option requires an OpenAPI spec");
+        }
+        None
+      }
+
+      Some(path) => {
+        if replay_entries.is_some() {
+          anyhow::bail!("openapi option cannot be used with a replay file, an access log imported as a replay or a HAR file");
+        }
+
+        if !endpoint.is_empty() {
+          anyhow::bail!("openapi option cannot be used with endpoints or an access log imported as a mix");
+        }
+
+        if body.is_some() {
+          anyhow::bail!("body option cannot be used with an OpenAPI spec, the bodies are generated from the spec");
+        }
+
+        let (specs, summary) = crate::openapi::load(Path::new(&path), url.path(), &operation)?;
+        endpoint = specs;
+
+        Some(&*Box::leak(Box::new(summary)))
+      }
+    };
+
+    // the extractors of each step of the scenario, by the index of the step
+    let mut step_extracts = None::<Vec<Vec<(String, Extractor)>>>;
+    let scenario: Option<&'static str> = match scenario {
+      None => None,
+      Some(path) => {
+        if replay_entries.is_some() {
+          anyhow::bail!("scenario option cannot be used with a replay file, an access log imported as a replay or a HAR file");
+        }
+
+        if !endpoint.is_empty() {
+          anyhow::bail!("scenario option cannot be used with endpoints, an access log imported as a mix or an OpenAPI spec");
+        }
+
+        if body.is_some() {
+          anyhow::bail!("body option cannot be used with a scenario, the body of each step is in the file");
+        }
+
+        let (specs, extracts) = crate::scenario::load(Path::new(&path))?
+          .into_iter()
+          .map(|step| (step.spec, step.extract))
+          .unzip();
+
+        endpoint = specs;
+        step_extracts = Some(extracts);
+
+        Some(path.leak())
+      }
+    };
+
+    let sessions = (har.is_some() && har_mode == HarMode::Session) || scenario.is_some();
+
+    let profile = match (rate_profile, replay_speed) {
+      (None, None) => None,
+
+      (Some(path), _) => {
+        if rate.is_some() || !stages.is_empty() || replay_speed.is_some() {
+          anyhow::bail!("rate-profile option cannot be used with the rate option, stages or the replay-speed option");
+        }
+
+        if !time_scale.is_finite() || time_scale <= 0.0 {
+          anyhow::bail!("time-scale option must be a number greater than 0");
+        }
+
+        Some(Profile::load(Path::new(path), time_scale)?)
+      }
+
+      (None, Some(speed)) => {
+        let Some(entries) = &replay_entries else {
+          anyhow::bail!("replay-speed option requires a replay file");
+        };
+
+        if rate.is_some() || !stages.is_empty() {
+          anyhow::bail!("replay-speed option cannot be used with the rate option or stages, the requests are sent at their recorded times");
+        }
+
+        if !warmup.is_zero() {
+          anyhow::bail!("replay-speed option cannot be used with a warm-up, the requests are sent at their recorded times");
+        }
+
+        if !speed.is_finite() || speed <= 0.0 {
+          anyhow::bail!("replay-speed option must be a number greater than 0");
+        }
+
+        let timestamps = entries
+          .iter()
+          .map(|entry| entry.timestamp)
+          .collect::<Option<Vec<_>>>()
+          .context("all the requests of the replay file must have a timestamp to be replayed at their recorded times")?;
+
+        if timestamps.len() < 2 {
+          anyhow::bail!("replay file must have at least two requests to be replayed at their recorded times");
+        }
+
+        Some(crate::replay::profile(&timestamps, speed))
+      }
+    };
+
+    // the bodies of the requests of a replay file, by the index of the request
+    let mut replay_bodies = replay_entries
+      .as_mut()
+      .map(|entries| entries.iter_mut().map(|entry| entry.body.take()).collect::<Vec<_>>());
+
+    let duration = match (duration, requests, &profile) {
+      (Some(duration), _, _) => Some(duration),
+      (None, Some(_), _) => None,
+      (None, None, Some(profile)) if !profile.duration().is_zero() => Some(profile.duration()),
+      (None, None, _) => Some(Duration::from_secs(10)),
+    };
+
+    let (concurrency, duration, rate, connections) = match stages.first() {
+      None => {
+        let rate = match profile {
+          Some(profile) => {
+            let initial = profile.value_at(Duration::ZERO);
+            Some(&*Box::leak(Box::new(profile.delayed(warmup, initial))))
+          }
+          None => rate.map(|rate| &*Box::leak(Box::new(Profile::constant(rate)))),
+        };
+        (concurrency, duration, rate, None)
+      }
+
+      Some(first) => {
+        if stages.iter().any(|stage| std::mem::discriminant(&stage.target) != std::mem::discriminant(&first.target)) {
+          anyhow::bail!("all stages must target the same kind of value, either connections (eg: 30s:10) or rates (eg: 30s:5000/s)");
+        }
+
+        if rate.is_some() {
+          anyhow::bail!("rate option cannot be used with stages, use stages with a rate target instead (eg: 30s:5000/s)");
+        }
+
+        // the first stage ramps from zero, but after a warm-up, that holds the load at the target of the first stage, it starts from there
+        let profile = match warmup.is_zero() {
+          true => crate::stage::profile(stages, 0.0),
+          false => crate::stage::profile(stages, first.target.value()).delayed(warmup, first.target.value()),
+        };
+
+        let profile: &'static _ = Box::leak(Box::new(profile));
+        let duration = Some(crate::stage::total_duration(stages));
+
+        match first.target {
+          Target::Rate(_) => (concurrency, duration, Some(profile), None),
+          Target::Connections(_) => {
+            let concurrency = profile.max().ceil() as usize;
+            if concurrency == 0 {
+              anyhow::bail!("at least one of the stages must target more than 0 connections");
+            }
+            (concurrency, duration, None, Some(profile))
+          }
+        }
+      }
+    };
+
+    if distribution != Distribution::Constant && rate.is_none() {
+      anyhow::bail!("distribution option requires a target rate, set with the rate option or stages with a rate target (eg: 30s:5000/s)");
+    }
+
+    let interval = match (interval, rate_profile, duration) {
+      (Some(interval), _, _) => Some(interval),
+      (None, Some(_), Some(duration)) => Some(duration / 20),
+      (None, _, _) => None,
+    };
+
+    if let Some(interval) = interval {
+      if rate.is_none() {
+        anyhow::bail!("interval option requires a target rate, set with the rate option, a rate profile or stages with a rate target (eg: 30s:5000/s)");
+      }
+
+      if interval.is_zero() {
+        anyhow::bail!("interval option must be greater than 0");
+      }
+    }
+
+    let seed = seed.unwrap_or_else(|| {
+      std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+    });
+
+    if duration.is_some_and(|duration| duration.is_zero()) {
+      anyhow::bail!("duration option must be equal or greater than 1ns");
+    }
+
+
     let host: &'static _ = url
       .host_str()
       .context("invalid url, missing host")?
       .to_string()
       .leak();
 
-    let method: &'static _ = method.trim().to_uppercase().leak();
-    let body_len = match &body {
-      None => 0,
-      Some(body) => body.len(),
-    };
-
     #[cfg(feature = "tls")]
     let tls = match url.scheme() {
       "http" => None,
@@ -263,169 +995,540 @@ impl RunConfig<'static> {
 
     let port = url.port_or_known_default().unwrap();
 
-    let addr = format!("{}:{}", host, port)
-      .to_socket_addrs()
-      .with_context(|| format!("error resolving address for {url}"))?
-      .next()
-      .with_context(|| format!("socket addresses for {url} resolved to empty list"))?;
+    let addr = match resolve {
+      Some(resolve) => parse_resolve(&resolve, host, port)?,
+      None => format!("{}:{}", host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("error resolving address for {url}"))?
+        .next()
+        .with_context(|| format!("socket addresses for {url} resolved to empty list"))?,
+    };
 
-    let body = match body {
-      None => None,
-      Some(body) => {
-        if body.starts_with("@") {
-          let path = body.strip_prefix('@').unwrap();
-          let canonical = Path::new(path).canonicalize().with_context(|| format!("error resolving path for {path}"))?;
-          let vec = std::fs::read(path).with_context(|| format!("error reading file from {}", canonical.display()))?;
-          Some(vec)
-        } else {
-          let vec = Vec::from(body);
-          Some(vec)
+    #[cfg(all(feature = "h1", feature = "h2"))]
+    let use_h2 = h2;
+    #[cfg(all(feature = "h1", not(feature = "h2")))]
+    let use_h2 = false;
+    #[cfg(all(not(feature = "h1"), feature = "h2"))]
+    let use_h2 = true;
+
+    // with the api of wrk the script builds the requests from the ones of the command line
+    #[cfg(feature = "script")]
+    let script: Option<&'static ScriptSource> = match script {
+      None => {
+        if script_api != ScriptApi::Rload {
+          anyhow::bail!("script-api option requires a script");
         }
+
+        if !script_args.is_empty() {
+          anyhow::bail!("the arguments after -- are passed to the script, they require a script");
+        }
+
+        None
+      }
+
+      Some(path) => {
+        let wrk = match script_api {
+          ScriptApi::Rload => None,
+          ScriptApi::Wrk => {
+            if use_h2 {
+              anyhow::bail!("script-api wrk cannot be used with http2, the scripts of wrk build http/1.1 requests");
+            }
+
+            if !endpoint.is_empty() || replay_entries.is_some() {
+              anyhow::bail!("script-api wrk cannot be used with endpoints, a replay file, an access log, a HAR file, an OpenAPI spec or a scenario, the script builds the requests");
+            }
+
+            let mut headers = header
+              .iter()
+              .map(|header| {
+                let (name, value) = header.split_once(':').context("invalid header format, must be key:value")?;
+                Ok((name.trim().to_string(), value.trim().to_string()))
+              })
+              .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+            // the requests formatted by the script are sent as they are, with the header in wrk.headers they close the connection too
+            if disable_keepalive {
+              headers.push((String::from("connection"), String::from("close")));
+            }
+
+            Some(WrkRequest {
+              scheme: url.scheme().to_string(),
+              host: host.to_string(),
+              port: url.port(),
+              method: method.trim().to_uppercase(),
+              path: url[url::Position::BeforePath..url::Position::AfterQuery].to_string(),
+              headers,
+              body: body.clone().map(Body::read).transpose()?,
+              addr,
+            })
+          }
+        };
+
+        Some(Box::leak(Box::new(ScriptSource::load(&path, script_args, threads, wrk)?)))
       }
     };
 
-    let content_length = match &body {
-      None => 0,
-      Some(body) => body.len() as u64,
+    #[cfg(feature = "plugin")]
+    let plugin: Option<&'static PluginSource> = match plugin {
+      None => None,
+      Some(path) => {
+        if use_h2 {
+          anyhow::bail!("plugin option cannot be used with http2, the plugin generates http/1.1 requests");
+        }
+
+        #[cfg(feature = "script")]
+        if script.is_some() {
+          anyhow::bail!("plugin option cannot be used with a script, both of them generate the requests");
+        }
+
+        Some(Box::leak(Box::new(PluginSource::load(&path, plugin_budget)?)))
+      }
     };
 
-    #[cfg(feature = "h1")]
-    macro_rules! h1_req {
-      () => {{
-        let buf: &'static _ = {
-          let aux = method.trim().to_uppercase();
-          let aux = http::Method::from_bytes(aux.as_bytes()).with_context(|| format!("invalid method {method}"))?;
-          let method = aux.as_str();
-
-          let mut req_lines = vec![
-            format!(
-              "{} {}{} HTTP/1.1",
-              method,
-              url.path(),
-              match url.query() {
-                Some(query) => format!("?{query}"),
-                None => String::new(),
-              }
-            ),
-            format!("host: {}", host),
-            format!("content-length: {}", content_length),
-          ];
-
-          for h in header {
-            let (k, v) = h
-              .split_once(':')
-              .context("invalid header format, must be key:value")?;
-            let hk = http::header::HeaderName::from_bytes(k.trim().as_bytes())
-              .with_context(|| format!("invalid header name {k}"))?;
-
-            let k = hk.as_str();
-
-            let hv = http::header::HeaderValue::from_str(v.trim())
-              .with_context(|| format!("invalid header value {v}"))?;
-
-            let v = hv
-              .to_str()
-              .with_context(|| format!("invalid header value {v}, only utf-8 is supported"))?;
-
-            req_lines.push(format!("{k}: {v}"));
-          }
+    let checks: &'static [Check] = expect_status
+      .into_iter()
+      .chain(expect_body_contains)
+      .chain(expect_body_regex)
+      .chain(expect_header)
+      .chain(expect_body_sha256)
+      .collect::<Vec<_>>()
+      .leak();
 
-          if disable_keepalive {
-            req_lines.push(String::from("connection: close"));
-          }
+    // the latencies are part of the summary of wrk
+    #[cfg(all(feature = "latency", feature = "script"))]
+    let latency = latency || script_api == ScriptApi::Wrk;
+    #[cfg(feature = "latency")]
+    let latency = latency || output == Output::Wrk || threshold.iter().any(Threshold::is_latency);
 
-          req_lines.push(String::from("\r\n"));
-          
-          let mut buf = Vec::from(req_lines.join("\r\n"));
+    let thresholds: &'static [Threshold] = threshold.leak();
 
-          if let Some(body) = body {
-            buf.extend_from_slice(&body);
-          }
+    #[cfg(not(feature = "serde"))]
+    if output == Output::Json {
+      anyhow::bail!("feature serde must be enabled at compile time to use the json output, not enabled");
+    }
 
-          buf.leak()
-        };
+    let output_file: Option<&'static str> = output_file.map(|path| &*path.leak());
+
+    let data_file: Option<&'static str> = data.map(|path| &*path.leak());
+    let data: Option<&'static Data> = match data_file {
+      None => None,
+      Some(path) => {
+        let data = Data::load(Path::new(path))?;
+        if let Some(column) = data.columns.iter().find(|column| crate::template::BUILTIN.contains(&column.as_str())) {
+          anyhow::bail!("data file {path} column {column} has the same name as a built-in template variable, rename it");
+        }
+        Some(Box::leak(Box::new(data)))
+      }
+    };
+
+    let columns = data.map_or(&[][..], |data| &data.columns[..]);
+
+    // the variables of a data file and the values extracted by a scenario are only used in templates
+    let template = template || data.is_some() || scenario.is_some();
+
+    // the variables bound by the extractors of the scenario, in the order they first appear
+    let mut captures = Vec::<String>::new();
+    for (var, _) in step_extracts.iter().flatten().flatten() {
+      if crate::template::BUILTIN.contains(&var.as_str()) || columns.contains(var) {
+        anyhow::bail!("scenario variable {var} has the same name as a built-in template variable or a column of the data file, rename it");
+      }
 
-        buf
-      }};
+      if !captures.contains(var) {
+        captures.push(var.clone());
+      }
     }
 
-    #[cfg(feature = "h2")]
-    macro_rules! h2_req {
-      () => {{
-        let bytes = {
-          match body {
-            Some(body) => Some(bytes::Bytes::from(body)),
-            None => None
-          } 
-        };
+    // without endpoints the url itself is the only endpoint of the mix, when replaying or importing a HAR file each request of the file is an endpoint
+    let specs = match (endpoint.is_empty(), replay_entries) {
+      (true, None) => vec![EndpointSpec {
+        weight: 1,
+        name: None,
+        method,
+        target: url.to_string(),
+        body,
+        headers: Vec::new(),
+        template: !from_curl,
+      }],
+
+      (false, None) => {
+        if body.is_some() {
+          anyhow::bail!("body option cannot be used with endpoints, set the body of each endpoint with body=value instead");
+        }
 
-        let req: &'static _ = {
-          
-          let mut req = http::Request::new(());
-          
-          let method = http::Method::from_bytes(method.as_bytes()).with_context(|| format!("invalid method {method}"))?;
-          *req.method_mut() = method;
-
-          *req.uri_mut() = Uri::from_static(url.to_string().leak());
-
-          req.headers_mut().insert(http::header::CONTENT_LENGTH, content_length.to_string().parse().unwrap());
-         
-          for h in header {
-            let (k, v) = h
-              .split_once(':')
-              .context("invalid header format, must be key:value")?;
-            let hk = http::header::HeaderName::from_bytes(k.trim().as_bytes())
-              .with_context(|| format!("invalid header name {k}"))?;
-            let hv = http::header::HeaderValue::from_str(v.trim())
-              .with_context(|| format!("invalid header value {v}"))?;
-            req.headers_mut().append(hk, hv);
-          }
+        endpoint
+      }
 
-          Box::leak(Box::new(req))
-        };
+      (true, Some(entries)) => {
+        if body.is_some() {
+          anyhow::bail!("body option cannot be used with a replay file, the body of each request is in the file");
+        }
+
+        entries
+          .into_iter()
+          .enumerate()
+          .map(|(i, entry)| EndpointSpec {
+            weight: har_weights.as_ref().map_or(1, |weights| weights[i]),
+            name: None,
+            method: entry.method,
+            target: entry.target,
+            body: None,
+            headers: entry.headers,
+            template: false,
+          })
+          .collect()
+      }
 
-        let body: &'static _ = Box::leak(Box::new(bytes));
-        let body = body.as_ref();
-        Request::H2 { req, body }
-      }};
+      (false, Some(_)) => anyhow::bail!("endpoints cannot be used with a replay file"),
+    };
+
+    // the endpoints with the same name share a row of the endpoints report
+    let mut reports = HashMap::<String, (usize, &'static str)>::new();
+
+    let mut endpoints = Vec::with_capacity(specs.len());
+    for (i, spec) in specs.into_iter().enumerate() {
+      let target = url
+        .join(&spec.target)
+        .with_context(|| format!("invalid endpoint target {}", spec.target))?;
+
+      if target.origin() != url.origin() {
+        anyhow::bail!("endpoint target {target} must have the same scheme, host and port as the url {url}");
+      }
+
+      let method = http::Method::from_bytes(spec.method.trim().to_uppercase().as_bytes())
+        .with_context(|| format!("invalid method {}", spec.method))?;
+
+      let body = match &mut replay_bodies {
+        Some(bodies) => bodies[i].take(),
+        None => spec.body.map(Body::read).transpose()?,
+      };
+      let body_len = body.as_ref().map_or(0, |body| body.len());
+      let headers = header.iter().chain(spec.headers.iter());
+      let vars = (template && spec.template).then_some((columns, &captures[..]));
+
+      let request = match use_h2 {
+        #[cfg(feature = "h1")]
+        false => h1_request(&method, &target, host, headers, body, disable_keepalive, vars)?,
+        #[cfg(feature = "h2")]
+        true => h2_request(&method, &target, headers, body, vars)?,
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(),
+      };
+
+      // the operations of an OpenAPI spec are reported by their operationId,
+      // and the requests of a replay or HAR file without the query, so the ones to the same path are reported together
+      let path = match vars {
+        Some(_) => crate::template::decode_vars(target.path()),
+        None => std::borrow::Cow::Borrowed(target.path()),
+      };
+      let name = match (spec.name, target.query(), replay_file.is_some() || har.is_some()) {
+        (Some(name), _, _) => name,
+        (None, Some(query), false) => format!("{method} {path}?{query}"),
+        (None, _, _) => format!("{method} {path}"),
+      };
+
+      let next = reports.len();
+      let (report, name) = *reports.entry(name).or_insert_with_key(|name| (next, name.clone().leak()));
+
+      let extract: &'static [Extract] = match &mut step_extracts {
+        None => &[],
+        Some(extracts) => std::mem::take(&mut extracts[i])
+          .into_iter()
+          .map(|(var, extractor)| Extract {
+            var: captures.iter().position(|capture| *capture == var).unwrap(),
+            extractor,
+          })
+          .collect::<Vec<_>>()
+          .leak(),
+      };
+
+      endpoints.push(Endpoint {
+        name,
+        weight: spec.weight,
+        method: method.as_str().to_string().leak(),
+        body_len,
+        request,
+        report,
+        extract,
+      });
     }
 
-    cfg_if::cfg_if! {
-      if #[cfg(all(feature = "h1", feature = "h2"))] {
-        let request = {
-          match h2 {
-            false => Request::H1 { buf: h1_req!() },
-            true => h2_req!(),
-          }
+    if endpoints.iter().all(|endpoint| endpoint.weight == 0) {
+      anyhow::bail!("at least one of the endpoints must have a weight greater than 0");
+    }
+
+    // the values of the data file rendered in headers are checked once here instead of for each request
+    if let (Some(data), Some(path)) = (data, data_file) {
+      for endpoint in &endpoints {
+        let columns = match endpoint.request {
+          #[cfg(feature = "h1")]
+          Request::H1 { template: Some(template), .. } => template.header_columns().collect::<Vec<_>>(),
+          #[cfg(feature = "h2")]
+          Request::H2 { template: Some(template), .. } => template.header_columns().collect::<Vec<_>>(),
+          #[allow(unreachable_patterns)]
+          _ => continue,
         };
-      } else if #[cfg(feature = "h1")] {
-        let request = Request::H1 { buf: h1_req!() };
-      } else if #[cfg(feature = "h2")] {
-        let request = h2_req!();
-      } else {
-        std::compile_error!("at least one of feature=h1 or feature=h2 must be enabled");
+
+        for column in columns {
+          if let Some(row) = data.rows.iter().position(|row| !crate::template::is_header_safe(&row[column])) {
+            anyhow::bail!(
+              "data file {path} row {} column {} has a control character (eg: a line break), it can't be sent in a header",
+              row + 1,
+              data.columns[column]
+            );
+          }
+        }
       }
+    }
+
+    // the requests of a replay file and the sessions of a HAR file are sent in the order of the file
+    let mix: &'static [usize] = match replay_file.is_some() || sessions {
+      true => (0..endpoints.len()).collect::<Vec<_>>().leak(),
+      false => crate::endpoint::mix(&endpoints.iter().map(|endpoint| endpoint.weight).collect::<Vec<_>>()).leak(),
     };
+    let endpoints: &'static [Endpoint] = endpoints.leak();
 
-    
     let config = RunConfig::<'static> {
       url,
       addr,
-      method,
-      body_len, 
       threads,
       concurrency,
+      rate,
+      rate_profile,
+      interval,
+      distribution,
+      seed,
+      connections,
+      stages,
       disable_keepalive,
       #[cfg(feature = "timeout")]
       timeout,
       #[cfg(feature = "latency")]
       latency,
-      request,
+      output,
+      output_file,
+      endpoints,
+      mix,
+      data,
+      data_file,
+      data_order,
+      replay: replay_file,
+      replay_speed,
+      access_log,
+      har,
+      har_mode,
+      #[cfg(feature = "openapi")]
+      openapi,
+      scenario,
+      #[cfg(feature = "script")]
+      script,
+      #[cfg(feature = "plugin")]
+      plugin,
+      checks,
+      max_body,
+      thresholds,
+      sessions,
       #[cfg(feature = "tls")]
       tls,
       duration,
+      requests,
+      warmup,
     };
 
     Ok(config)
   }
 }
+
+/// Parses an address in the format of curl's --resolve (eg: example.com:443:127.0.0.1), it must be for the host and port of the url
+fn parse_resolve(s: &str, host: &str, port: u16) -> Result<SocketAddr, anyhow::Error> {
+  let mut parts = s.splitn(3, ':');
+  let (Some(resolve_host), Some(resolve_port), Some(address)) = (parts.next(), parts.next(), parts.next()) else {
+    anyhow::bail!("invalid resolve option {s}, must be in the format of host:port:address (eg: example.com:443:127.0.0.1)");
+  };
+
+  let resolve_port = resolve_port
+    .parse::<u16>()
+    .with_context(|| format!("invalid port in resolve option {s}"))?;
+
+  if !resolve_host.eq_ignore_ascii_case(host) || resolve_port != port {
+    anyhow::bail!("resolve option {s} must be for the host and port of the url {host}:{port}");
+  }
+
+  // like curl, the address can be a list and the ipv6 ones can be in brackets, the first one is used
+  let address = address.split(',').next().unwrap_or_default();
+  let address = address.trim_start_matches('[').trim_end_matches(']');
+  let ip = address
+    .parse::<IpAddr>()
+    .with_context(|| format!("invalid address in resolve option {s}, must be an ip address"))?;
+
+  Ok(SocketAddr::new(ip, port))
+}
+
+/// The names of the columns of the data file and of the values extracted by the scenario,
+/// for the requests that can have template variables
+type TemplateVars<'a> = Option<(&'a [String], &'a [String])>;
+
+/// Pre-encodes a h1 request to write directly to the socket, or compiles it to a template if it has variables
+#[cfg(feature = "h1")]
+fn h1_request<'h>(
+  method: &http::Method,
+  url: &Url,
+  host: &str,
+  headers: impl Iterator<Item = &'h String>,
+  body: Option<Vec<u8>>,
+  disable_keepalive: bool,
+  vars: TemplateVars,
+) -> Result<Request<'static>, anyhow::Error> {
+  let content_length = body.as_ref().map_or(0, |body| body.len());
+
+  let decode = |s| match vars {
+    Some(_) => crate::template::decode_vars(s),
+    None => std::borrow::Cow::Borrowed(s),
+  };
+
+  let mut req_lines = vec![
+    format!(
+      "{} {}{} HTTP/1.1",
+      method,
+      decode(url.path()),
+      match url.query() {
+        Some(query) => format!("?{}", decode(query)),
+        None => String::new(),
+      }
+    ),
+    format!("host: {}", host),
+  ];
+
+  for h in headers {
+    let (k, v) = h
+      .split_once(':')
+      .context("invalid header format, must be key:value")?;
+    let hk = http::header::HeaderName::from_bytes(k.trim().as_bytes())
+      .with_context(|| format!("invalid header name {k}"))?;
+
+    let k = hk.as_str();
+
+    let hv = http::header::HeaderValue::from_str(v.trim())
+      .with_context(|| format!("invalid header value {v}"))?;
+
+    let v = hv
+      .to_str()
+      .with_context(|| format!("invalid header value {v}, only utf-8 is supported"))?;
+
+    req_lines.push(format!("{k}: {v}"));
+  }
+
+  if disable_keepalive {
+    req_lines.push(String::from("connection: close"));
+  }
+
+  if let Some((columns, captures)) = vars {
+    let line = Template::parse(format!("{}\r\n", req_lines[0]).as_bytes(), Slot::Uri, columns, captures)
+      .map_err(anyhow::Error::msg)
+      .context("invalid template in the request line")?;
+
+    let headers = Template::parse(format!("{}\r\n", req_lines[1..].join("\r\n")).as_bytes(), Slot::Header, columns, captures)
+      .map_err(anyhow::Error::msg)
+      .context("invalid template in the headers")?;
+
+    let body_template = body
+      .as_deref()
+      .map(|body| Template::parse(body, Slot::Body, columns, captures))
+      .transpose()
+      .map_err(anyhow::Error::msg)
+      .context("invalid template in the body")?;
+
+    if !line.is_static() || !headers.is_static() || body_template.as_ref().is_some_and(|body| !body.is_static()) {
+      let template: &'static _ = Box::leak(Box::new(H1Template {
+        line,
+        headers,
+        body: body_template,
+      }));
+
+      return Ok(Request::H1 { buf: &[], template: Some(template) });
+    }
+  }
+
+  req_lines.insert(2, format!("content-length: {}", content_length));
+  req_lines.push(String::from("\r\n"));
+
+  let mut buf = Vec::from(req_lines.join("\r\n"));
+
+  if let Some(body) = body {
+    buf.extend_from_slice(&body);
+  }
+
+  Ok(Request::H1 { buf: buf.leak(), template: None })
+}
+
+/// Builds a h2 request to be cloned for each send, or a template if it has variables
+#[cfg(feature = "h2")]
+fn h2_request<'h>(
+  method: &http::Method,
+  url: &Url,
+  headers: impl Iterator<Item = &'h String>,
+  body: Option<Vec<u8>>,
+  vars: TemplateVars,
+) -> Result<Request<'static>, anyhow::Error> {
+  let content_length = body.as_ref().map_or(0, |body| body.len());
+
+  // without variables every part of the request is a static template
+  let parse = |src: &[u8], slot| match vars {
+    Some((columns, captures)) => Template::parse(src, slot, columns, captures).map_err(anyhow::Error::msg),
+    None => Ok(Template::from_static(src)),
+  };
+
+  let uri = parse(crate::template::decode_vars(url.as_str()).as_bytes(), Slot::Uri).context("invalid template in the url")?;
+
+  let body_template = body
+    .as_deref()
+    .map(|body| parse(body, Slot::Body))
+    .transpose()
+    .context("invalid template in the body")?;
+
+  let mut req = http::Request::new(());
+
+  *req.method_mut() = method.clone();
+
+  if uri.is_static() {
+    *req.uri_mut() = Uri::from_static(url.to_string().leak());
+  }
+
+  req.headers_mut().insert(http::header::CONTENT_LENGTH, content_length.to_string().parse().unwrap());
+
+  let mut header_templates = Vec::new();
+  for h in headers {
+    let (k, v) = h
+      .split_once(':')
+      .context("invalid header format, must be key:value")?;
+    let hk = http::header::HeaderName::from_bytes(k.trim().as_bytes())
+      .with_context(|| format!("invalid header name {k}"))?;
+    let hv = http::header::HeaderValue::from_str(v.trim())
+      .with_context(|| format!("invalid header value {v}"))?;
+
+    let value = parse(hv.as_bytes(), Slot::Header).with_context(|| format!("invalid template in header {k}"))?;
+
+    match value.is_static() {
+      true => {
+        req.headers_mut().append(hk, hv);
+      }
+      false => header_templates.push((hk, value)),
+    }
+  }
+
+  if !uri.is_static() || !header_templates.is_empty() || body_template.as_ref().is_some_and(|body| !body.is_static()) {
+    let template: &'static _ = Box::leak(Box::new(H2Template {
+      req,
+      uri: (!uri.is_static()).then_some(uri),
+      headers: header_templates,
+      body: body_template,
+    }));
+
+    let req: &'static _ = &template.req;
+    return Ok(Request::H2 { req, body: None, template: Some(template) });
+  }
+
+  let req: &'static _ = Box::leak(Box::new(req));
+  let body: &'static _ = Box::leak(Box::new(body.map(bytes::Bytes::from)));
+
+  Ok(Request::H2 { req, body: body.as_ref(), template: None })
+}
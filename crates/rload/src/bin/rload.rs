@@ -13,7 +13,37 @@ static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
 static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc; 
 
 fn main() -> Result<(), anyhow::Error> {
-  let report = rload::cli::run()?;
-  eprintln!("{}", report);
+  match std::env::args().nth(1).as_deref() {
+    Some("find-max") => {
+      let report = rload::find_max::run()?;
+      eprintln!("{}", report);
+    }
+
+    Some("from-curl") => {
+      let report = rload::curl::run()?;
+      report.print()?;
+      exit_on_failed_thresholds(&report);
+    }
+
+    _ => {
+      let report = rload::cli::run()?;
+      report.print()?;
+      exit_on_failed_thresholds(&report);
+    }
+  }
+
   Ok(())
+}
+
+/// Exits with a distinct code when the results don't meet the thresholds, after the report was printed
+fn exit_on_failed_thresholds(report: &rload::report::Report) {
+  let failed = report
+    .failed_thresholds()
+    .map(|threshold| format!("{} (actual {})", threshold.spec, threshold.actual))
+    .collect::<Vec<_>>();
+
+  if !failed.is_empty() {
+    eprintln!("{} of {} thresholds failed: {}", failed.len(), report.thresholds.len(), failed.join(", "));
+    std::process::exit(rload::threshold::EXIT_CODE);
+  }
 }
\ No newline at end of file
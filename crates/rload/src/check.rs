@@ -0,0 +1,139 @@
+use sha2::{Digest, Sha256};
+
+use crate::extract::{Needs, Response};
+
+/// A check of the responses, a response that fails any of them fails its request as an assertion error
+#[derive(Debug, Clone)]
+pub enum Check {
+  /// the status is one of this ranges, a single status is a range of one
+  Status(Vec<(u16, u16)>),
+  /// the body contains this bytes
+  BodyContains(Vec<u8>),
+  /// the body matches this regex
+  BodyRegex(regex::bytes::Regex),
+  /// a header with this name, in lowercase, has a value that contains the given one ignoring case, or is present without a value
+  Header(String, Option<String>),
+  /// the sha256 digest of the body
+  BodySha256([u8; 32]),
+}
+
+impl std::fmt::Display for Check {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Check::Status(ranges) => {
+        let ranges = ranges
+          .iter()
+          .map(|(from, to)| match (from, to) {
+            (from, to) if from == to => from.to_string(),
+            (from, _) => format!("{}xx", from / 100),
+          })
+          .collect::<Vec<_>>();
+        write!(f, "status {}", ranges.join(","))
+      }
+      Check::BodyContains(bytes) => write!(f, "body contains {:?}", String::from_utf8_lossy(bytes)),
+      Check::BodyRegex(regex) => write!(f, "body matches /{}/", regex.as_str()),
+      Check::Header(name, None) => write!(f, "header {name}"),
+      Check::Header(name, Some(value)) => write!(f, "header {name}: {value}"),
+      // the start of the digest is enough to tell it apart in the report
+      Check::BodySha256(digest) => {
+        write!(f, "body sha256 ")?;
+        for byte in &digest[..8] {
+          write!(f, "{byte:02x}")?;
+        }
+        write!(f, "...")
+      }
+    }
+  }
+}
+
+impl Check {
+  /// The parts of the response the check reads, only the checks of the body need it to be buffered
+  pub fn needs(&self) -> Needs {
+    match self {
+      Check::Status(_) => Needs::default(),
+      Check::Header(..) => Needs { headers: true, body: false },
+      Check::BodyContains(_) | Check::BodyRegex(_) | Check::BodySha256(_) => Needs { headers: false, body: true },
+    }
+  }
+
+  pub fn check(&self, res: &Response) -> bool {
+    match self {
+      Check::Status(ranges) => ranges.iter().any(|(from, to)| (*from..=*to).contains(&res.status)),
+      Check::BodyContains(bytes) => bytes.is_empty() || res.body.windows(bytes.len()).any(|window| window == bytes.as_slice()),
+      Check::BodyRegex(regex) => regex.is_match(&res.body),
+      Check::Header(name, value) => res.headers.iter().any(|(key, actual)| {
+        key == name
+          && match value {
+            None => true,
+            Some(value) => String::from_utf8_lossy(actual).to_ascii_lowercase().contains(&value.to_ascii_lowercase()),
+          }
+      }),
+      Check::BodySha256(digest) => Sha256::digest(&res.body)[..] == digest[..],
+    }
+  }
+}
+
+/// Parses the expected statuses, a list of statuses or classes of statuses (eg: 200,201 or 2xx,304)
+pub fn parse_status(s: &str) -> Result<Check, String> {
+  let invalid = |item: &str| format!("invalid status {item}, must be a list of statuses or classes of statuses (eg: 200,201 or 2xx,304)");
+
+  let ranges = s
+    .split(',')
+    .map(|item| {
+      let item = item.trim();
+      match item.strip_suffix("xx").or_else(|| item.strip_suffix("XX")) {
+        Some(class @ ("1" | "2" | "3" | "4" | "5")) => {
+          let class = class.as_bytes()[0] - b'0';
+          Ok((class as u16 * 100, class as u16 * 100 + 99))
+        }
+        Some(_) => Err(invalid(item)),
+        None => match item.parse::<u16>() {
+          Ok(status @ 100..=599) => Ok((status, status)),
+          _ => Err(invalid(item)),
+        },
+      }
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  Ok(Check::Status(ranges))
+}
+
+pub fn parse_body_contains(s: &str) -> Result<Check, String> {
+  Ok(Check::BodyContains(s.as_bytes().to_vec()))
+}
+
+pub fn parse_body_regex(s: &str) -> Result<Check, String> {
+  let regex = regex::bytes::Regex::new(s).map_err(|e| format!("invalid regex {s}: {e}"))?;
+  Ok(Check::BodyRegex(regex))
+}
+
+/// Parses an expected header in the format of `name: value`, or only the name to expect the header with any value
+pub fn parse_header(s: &str) -> Result<Check, String> {
+  let (name, value) = match s.split_once(':') {
+    Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+    None => (s.trim(), None),
+  };
+
+  if name.is_empty() {
+    return Err(format!("invalid header {s}, must be in the format of name: value or only the name"));
+  }
+
+  Ok(Check::Header(name.to_ascii_lowercase(), value))
+}
+
+/// Parses the expected sha256 digest of the body, in hex
+pub fn parse_body_sha256(s: &str) -> Result<Check, String> {
+  let s = s.trim();
+  let invalid = || format!("invalid sha256 digest {s}, must be 64 hex characters");
+
+  if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+    return Err(invalid());
+  }
+
+  let mut digest = [0; 32];
+  for (i, byte) in digest.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+  }
+
+  Ok(Check::BodySha256(digest))
+}
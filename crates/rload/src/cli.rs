@@ -1,14 +1,16 @@
 #[cfg(feature = "latency")]
 use anyhow::Context;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use std::{thread, time::Duration};
 use tokio::sync::watch;
 
 use crate::{
   rt::Instant,
   args::{Args, Request, RunConfig},
+  distribution::Distribution,
   http,
-  report::Report,
+  report::{CheckReport, DataReport, EndpointReport, IntervalReport, Rate, Report, StageReport},
+  run::Requests,
 };
 
 #[cfg(feature = "status-detail")]
@@ -17,8 +19,17 @@ use crate::status::Statuses;
 #[cfg(feature = "error-detail")]
 use crate::error::Errors;
 
+#[cfg(feature = "script")]
+use crate::script::ScriptSource;
+
 pub fn run() -> Result<Report, anyhow::Error> {
-  let args = Args::parse();
+  let matches = Args::command().get_matches();
+  #[allow(unused_mut)]
+  let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+  #[cfg(feature = "config")]
+  crate::config::apply(&mut args, &matches)?;
+
   run_with_args(args)
 }
 
@@ -28,41 +39,115 @@ pub fn run_with_args(args: Args) -> Result<Report, anyhow::Error> {
 }
 
 pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Error> {
-  eprintln!(
-    "Running {} test @ {}",
-    crate::fmt::format_duration(config.duration),
-    config.url
-  );
+  let url = crate::template::decode_vars(config.url.as_str());
+  match (config.duration, config.requests) {
+    (Some(duration), None) => eprintln!("Running {} test @ {}", crate::fmt::format_duration(duration), url),
+    (None, Some(requests)) => eprintln!("Running {} requests test @ {}", requests, url),
+    (Some(duration), Some(requests)) => eprintln!(
+      "Running {} requests test @ {} for at most {}",
+      requests,
+      url,
+      crate::fmt::format_duration(duration)
+    ),
+    (None, None) => unreachable!("a run always has a duration or a number of requests"),
+  }
   eprintln!(
     "  {} threads and {} connections",
     config.threads, config.concurrency
   );
+  if !config.warmup.is_zero() {
+    eprintln!("  {} warm-up", crate::fmt::format_duration(config.warmup));
+  }
+  if !config.stages.is_empty() {
+    for stage in config.stages {
+      eprintln!("  stage {}", stage);
+    }
+  } else if let Some(path) = config.rate_profile {
+    eprintln!("  rate profile {}", path);
+  } else if let (Some(rate), None) = (config.rate, config.replay_speed) {
+    eprintln!("  {} requests/sec", rate.value_at(Duration::ZERO));
+  }
+  if let Some(summary) = config.access_log {
+    eprintln!("  access log {}: {}", summary.path, summary);
+  }
+  if let Some(summary) = config.har {
+    eprintln!("  har {} as a {}: {}", summary.path, config.har_mode, summary);
+  }
+  #[cfg(feature = "openapi")]
+  if let Some(summary) = config.openapi {
+    eprintln!("  openapi {}: {}", summary.path, summary);
+  }
+  if let Some(path) = config.scenario {
+    eprintln!("  scenario {} with {} steps per connection", path, config.endpoints.len());
+  }
+  #[cfg(feature = "script")]
+  if let Some(script) = config.script {
+    match script.wrk {
+      None => eprintln!("  script {}", script.path),
+      Some(_) => eprintln!("  script {} with the api of wrk", script.path),
+    }
+  }
+  #[cfg(feature = "plugin")]
+  if let Some(plugin) = config.plugin {
+    eprintln!("  plugin {} with a budget of {} per call", plugin.path, crate::fmt::format_duration(plugin.budget));
+  }
+  for check in config.checks {
+    eprintln!("  expect {}", check);
+  }
+  if let Some(path) = config.replay {
+    match config.replay_speed {
+      Some(speed) => eprintln!("  replay {} with {} requests at {}x the recorded speed", path, config.endpoints.len(), speed),
+      None => eprintln!("  replay {} with {} requests in order", path, config.endpoints.len()),
+    }
+  }
+  if let (Some(data), Some(file)) = (config.data, config.data_file) {
+    eprintln!("  data {} with {} rows in {} order", file, data.rows.len(), config.data_order);
+  }
+  if config.rate.is_some() && config.distribution != Distribution::Constant {
+    match config.distribution.is_random() {
+      true => eprintln!("  {} arrivals with seed {}", config.distribution, config.seed),
+      false => eprintln!("  {} arrivals", config.distribution),
+    }
+  }
 
   let mut handles = Vec::with_capacity(config.threads);
 
   // with this signaling to start processing we gain a little in precision of the time measuring
-  let (start_send, start_recv) = watch::channel(());
+  // the start instant is shared with the threads so all of them follow the same timeline
+  let (start_send, start_recv) = watch::channel(Instant::now());
 
   let (stop_send, stop_recv) = watch::channel(());
 
-  for _ in 0..config.threads {
+  let requests: Option<&'static Requests> = config.requests.map(|n| &*Box::leak(Box::new(Requests::new(n))));
+
+  for index in 0..config.threads {
     let start = start_recv.clone();
     let stop = stop_recv.clone();
-    let handle = std::thread::spawn(move || crate::run::thread(config, start, stop));
+    let handle = std::thread::spawn(move || crate::run::thread(config, index, start, stop, requests));
     handles.push(handle);
   }
 
   drop(start_recv);
 
   let duration = config.duration;
-  let start = thread::spawn(move || {
+  let warmup = config.warmup;
+  #[cfg(feature = "script")]
+  let script = config.script;
+  let (start, stopped) = thread::spawn(move || {
     // give the threads time to startup
     thread::sleep(Duration::from_millis(25));
     let start = Instant::now();
-    let until = start + duration;
-    start_send.send(()).unwrap();
-    watch_stop(stop_send, until);
-    start
+    let until = duration.map(|duration| start + warmup + duration);
+    start_send.send(start).unwrap();
+    watch_stop(
+      stop_send,
+      until,
+      requests,
+      #[cfg(feature = "script")]
+      script,
+    );
+    // the measurement starts when the warm-up ends
+    (start + warmup, Instant::now())
   })
   .join()
   .unwrap();
@@ -70,6 +155,8 @@ pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Err
   let mut ok = 0;
   let mut read = 0;
   let mut write = 0;
+  let mut sent = 0;
+  let mut late = 0;
 
   #[cfg(feature = "error-detail")]
   let mut err = Errors::new();
@@ -86,16 +173,87 @@ pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Err
   #[cfg(feature = "latency")]
   let mut hdr = hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram");
 
+  #[cfg(feature = "latency")]
+  let mut hdr_corrected = hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram");
+
+  let mut intervals = Vec::<u64>::new();
+  let mut thread_requests = Vec::with_capacity(config.threads);
+
+  // the globals of the script in each thread at the end of the run
+  #[cfg(feature = "script")]
+  let mut script_globals = Vec::with_capacity(config.threads);
+
+  #[cfg(feature = "plugin")]
+  let mut plugin_stats = crate::plugin::PluginStats::default();
+
+  let mut checked = 0;
+  let mut checks = config
+    .checks
+    .iter()
+    .map(|check| CheckReport {
+      name: check.to_string(),
+      failed: 0,
+    })
+    .collect::<Vec<_>>();
+
+  // the endpoints with the same name are reported together, with the sum of their weights
+  let mut endpoints = Vec::<EndpointReport>::with_capacity(crate::endpoint::reports(config.endpoints));
+  for endpoint in config.endpoints {
+    match endpoints.get_mut(endpoint.report) {
+      Some(report) => report.weight += endpoint.weight,
+      None => endpoints.push(EndpointReport {
+        name: endpoint.name.into(),
+        weight: endpoint.weight,
+        ok: 0,
+        err: 0,
+        #[cfg(feature = "status-detail")]
+        statuses: Vec::new(),
+        #[cfg(not(feature = "status-detail"))]
+        not_ok_status: 0,
+        #[cfg(feature = "latency")]
+        hdr: None,
+      }),
+    }
+  }
+
+  #[cfg(feature = "status-detail")]
+  let mut endpoint_statuses = vec![Statuses::new(); endpoints.len()];
+
+  let mut stages = config
+    .stages
+    .iter()
+    .map(|stage| StageReport {
+      stage: *stage,
+      elapsed: Duration::ZERO,
+      ok: 0,
+      err: 0,
+      #[cfg(feature = "latency")]
+      hdr: None,
+    })
+    .collect::<Vec<_>>();
+
   let results = handles
     .into_iter()
     .map(|h| h.join().unwrap())
     .collect::<Vec<_>>();
-  let elapsed = start.elapsed();
+  // with a fixed number of requests the run ends when the last one is completed
+  let finished = results.iter().find_map(|t| t.finished).unwrap_or_else(Instant::now);
+  let elapsed = finished.saturating_duration_since(start);
 
   for t in results {
+    thread_requests.push(t.ok);
     ok += t.ok;
     read += t.read;
     write += t.write;
+    sent += t.sent;
+    late += t.late;
+
+    if intervals.len() < t.intervals.len() {
+      intervals.resize(t.intervals.len(), 0);
+    }
+    for (interval, sent) in intervals.iter_mut().zip(t.intervals) {
+      *interval += sent;
+    }
 
     #[cfg(feature = "error-detail")]
     err.join(t.err);
@@ -119,30 +277,163 @@ pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Err
         hdr
           .add(t.hdr)
           .context("error adding latency histogram to the final result")?;
+        hdr_corrected
+          .add(t.hdr_corrected)
+          .context("error adding corrected latency histogram to the final result")?;
+      }
+    }
+
+    #[cfg(feature = "script")]
+    script_globals.push(t.globals);
+
+    #[cfg(feature = "plugin")]
+    plugin_stats.join(t.plugin);
+
+    checked += t.checked;
+    for (check, failed) in checks.iter_mut().zip(t.checks) {
+      check.failed += failed;
+    }
+
+    for (i, (endpoint, t)) in endpoints.iter_mut().zip(t.endpoints).enumerate() {
+      endpoint.ok += t.ok;
+      endpoint.err += t.err;
+
+      #[cfg(feature = "status-detail")]
+      endpoint_statuses[i].join(t.statuses);
+
+      #[cfg(not(feature = "status-detail"))]
+      {
+        let _ = i;
+        endpoint.not_ok_status += t.not_ok_status;
+      }
+
+      #[cfg(feature = "latency")]
+      {
+        if config.latency {
+          match &mut endpoint.hdr {
+            None => endpoint.hdr = Some(t.hdr),
+            Some(hdr) => hdr
+              .add(t.hdr)
+              .context("error adding endpoint latency histogram to the final result")?,
+          }
+        }
       }
     }
+
+    for (stage, t) in stages.iter_mut().zip(t.stages) {
+      stage.ok += t.ok;
+      stage.err += t.err;
+
+      #[cfg(feature = "latency")]
+      {
+        if config.latency {
+          match &mut stage.hdr {
+            None => stage.hdr = Some(t.hdr),
+            Some(hdr) => hdr
+              .add(t.hdr)
+              .context("error adding stage latency histogram to the final result")?,
+          }
+        }
+      }
+    }
+  }
+
+  #[cfg(feature = "status-detail")]
+  for (endpoint, statuses) in endpoints.iter_mut().zip(endpoint_statuses) {
+    endpoint.statuses = statuses.iter().collect();
   }
 
-  let http_version = match config.request {
+  // the time each stage actually ran, the last one could be cut short by a stop signal
+  let mut stage_start = Duration::ZERO;
+  for stage in stages.iter_mut() {
+    let stage_end = stage_start + stage.stage.duration;
+    stage.elapsed = stage_end.min(elapsed).saturating_sub(stage_start);
+    stage_start = stage_end;
+  }
+
+  let http_version = match config.endpoints[0].request {
     #[cfg(feature = "h1")]
     Request::H1 { .. } => http::Version::Http1,
     #[cfg(feature = "h2")]
     Request::H2 { .. } => http::Version::Http2,
   };
 
+  let rate = config.rate.map(|profile| {
+    // the profile timeline includes the warm-up
+    let running = stopped.saturating_duration_since(start);
+    let until = config.warmup + running;
+    Rate {
+      // for a ramped rate this is the mean of the requested rate
+      target: (profile.integral(until) - profile.integral(config.warmup)) / running.as_secs_f64(),
+      sent,
+      late,
+      // the slots after the stop signal are not accounted, only the ones that were due while measuring
+      dropped: crate::schedule::expected(profile, config.distribution, until)
+        .saturating_sub(crate::schedule::expected(profile, config.distribution, config.warmup))
+        .saturating_sub(sent),
+      distribution: config.distribution,
+      seed: config.distribution.is_random().then_some(config.seed),
+    }
+  });
+
+  let intervals = match (config.rate, config.interval) {
+    (Some(profile), Some(interval)) => {
+      let running = stopped.saturating_duration_since(start);
+      let running = config.duration.map_or(running, |duration| running.min(duration));
+      let count = (running.as_nanos().div_ceil(interval.as_nanos()) as usize).max(intervals.len());
+      (0..count)
+        .map(|i| {
+          let interval_start = interval * i as u32;
+          let interval_end = (interval_start + interval).min(running);
+          let elapsed = interval_end.saturating_sub(interval_start);
+          let requested = match elapsed.as_secs_f64() {
+            0.0 => 0.0,
+            secs => {
+              let integral = |t: Duration| profile.integral(config.distribution.unwarp(config.warmup + t));
+              (integral(interval_end) - integral(interval_start)) / secs
+            }
+          };
+
+          IntervalReport {
+            start: interval_start,
+            elapsed,
+            requested,
+            sent: intervals.get(i).copied().unwrap_or(0),
+          }
+        })
+        .collect()
+    }
+    _ => Vec::new(),
+  };
+
   #[cfg(feature = "latency")]
-  let hdr = match config.latency {
-    true => Some(hdr),
-    false => None,
+  let (hdr, hdr_corrected) = match config.latency {
+    false => (None, None),
+    true => {
+      let hdr_corrected = match config.rate {
+        // in constant-throughput mode the latencies were already measured from the intended send times
+        Some(_) => hdr_corrected,
+        // in the closed loop each connection is expected to send a request every elapsed / (requests per connection)
+        // so the missing samples are backfilled from that interval, the same way wrk does
+        None => {
+          match (elapsed.as_nanos() as u64).checked_div(ok / config.concurrency as u64) {
+            Some(interval) => hdr.clone_correct(interval),
+            None => hdr.clone(),
+          }
+        }
+      };
+
+      (Some(hdr), Some(hdr_corrected))
+    }
   };
 
-  let report = Report {
+  let mut report = Report {
     url: config.url.clone(),
     address: config.addr,
     http_version,
     keepalive: !config.disable_keepalive,
-    method: config.method.into(),
-    body_len: config.body_len,
+    method: config.endpoints[0].method.into(),
+    body_len: config.endpoints[0].body_len,
     ok,
     read,
     write,
@@ -161,6 +452,37 @@ pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Err
     threads: config.threads,
     concurrency: config.concurrency,
     duration: config.duration,
+    requests: config.requests,
+    warmup: config.warmup,
+    rate,
+    rate_profile: config.rate_profile.map(String::from),
+    replay: config.replay.map(String::from),
+    replay_speed: config.replay_speed,
+    access_log: config.access_log.map(|summary| format!("{}, {}", summary.path, summary)),
+    #[cfg(feature = "openapi")]
+    openapi: config.openapi.map(|summary| format!("{}, {}", summary.path, summary)),
+    har: config.har.map(|summary| format!("{} as a {}, {}", summary.path, config.har_mode, summary)),
+    scenario: config.scenario.map(|path| format!("{}, {} steps", path, config.endpoints.len())),
+    #[cfg(feature = "script")]
+    script: config.script.map(|script| script.path.clone()),
+    #[cfg(feature = "plugin")]
+    plugin: config.plugin.map(|plugin| format!("{}, budget {}", plugin.path, crate::fmt::format_duration(plugin.budget))),
+    #[cfg(feature = "plugin")]
+    plugin_stats,
+    checked,
+    checks,
+    thresholds: Vec::new(),
+    data: config.data.zip(config.data_file).map(|(data, file)| DataReport {
+      file: file.to_string(),
+      rows: data.rows.len(),
+      order: config.data_order,
+    }),
+    intervals,
+    stages,
+    endpoints,
+    thread_requests,
+    output: config.output,
+    output_file: config.output_file.map(String::from),
 
     #[cfg(feature = "timeout")]
     timeout: config.timeout,
@@ -169,27 +491,96 @@ pub fn run_with_config(config: RunConfig<'static>) -> Result<Report, anyhow::Err
 
     #[cfg(feature = "latency")]
     hdr,
+    #[cfg(feature = "latency")]
+    hdr_corrected,
   };
 
+  report.thresholds = config.thresholds.iter().map(|threshold| threshold.evaluate(&report)).collect();
+
+  // an error in the done hook doesn't discard the results of the run
+  #[cfg(feature = "script")]
+  if let Some(script) = config.script {
+    if let Err(e) = crate::script::done(script, &report, script_globals) {
+      eprintln!("{e:#}");
+    }
+  }
+
   Ok(report)
 }
 
 #[cfg(feature = "monoio")]
 #[monoio::main(driver = "legacy", timer = true)]
-pub async fn watch_stop(stop: watch::Sender<()>, until: Instant) {
-  watch_stop_inner(stop, until).await
+pub async fn watch_stop(
+  stop: watch::Sender<()>,
+  until: Option<Instant>,
+  requests: Option<&'static Requests>,
+  #[cfg(feature = "script")] script: Option<&'static ScriptSource>,
+) {
+  watch_stop_inner(
+    stop,
+    until,
+    requests,
+    #[cfg(feature = "script")]
+    script,
+  )
+  .await
 }
 
 #[cfg(not(feature = "monoio"))]
 #[tokio::main(flavor = "current_thread")]
-pub async fn watch_stop(stop: watch::Sender<()>, until: Instant) {
-  watch_stop_inner(stop, until).await
+pub async fn watch_stop(
+  stop: watch::Sender<()>,
+  until: Option<Instant>,
+  requests: Option<&'static Requests>,
+  #[cfg(feature = "script")] script: Option<&'static ScriptSource>,
+) {
+  watch_stop_inner(
+    stop,
+    until,
+    requests,
+    #[cfg(feature = "script")]
+    script,
+  )
+  .await
 }
 
-async fn watch_stop_inner(stop: watch::Sender<()>, until: Instant) {
+/// Sends the stop signal on ctrl-c, at `until`, when all the `requests` are completed or when the script stopped all the threads,
+/// whichever comes first
+async fn watch_stop_inner(
+  stop: watch::Sender<()>,
+  until: Option<Instant>,
+  requests: Option<&'static Requests>,
+  #[cfg(feature = "script")] script: Option<&'static ScriptSource>,
+) {
+  let timer = async {
+    match until {
+      Some(until) => crate::rt::sleep_until(until).await,
+      None => std::future::pending().await,
+    }
+  };
+
+  let done = async {
+    match requests {
+      Some(requests) => requests.done.notified().await,
+      None => std::future::pending().await,
+    }
+  };
+
+  let stopped = async {
+    #[cfg(feature = "script")]
+    if let Some(script) = script {
+      script.stops.all.notified().await;
+      return;
+    }
+
+    std::future::pending::<()>().await
+  };
+
   crate::rt::select! {
     _ = crate::rt::ctrl_c() => {}
-    _ = crate::rt::sleep_until(until) => {}
+    _ = timer => {}
+    _ = done => {}
+    _ = stopped => {}
   };
   let _ = stop.send(());
 }
\ No newline at end of file
@@ -0,0 +1,374 @@
+use anyhow::Context;
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use serde::Deserialize;
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  access_log::LogMode,
+  args::{parse_duration, Args},
+  data::DataOrder,
+  har::HarMode,
+  output::Output,
+  endpoint::{Body, EndpointSpec},
+  stage::{Stage, Target},
+};
+
+#[cfg(feature = "script")]
+use crate::script::ScriptApi;
+
+/// The options of a config file, with the same names as the long command line options
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FileConfig {
+  pub url: Option<String>,
+  pub concurrency: Option<usize>,
+  pub duration: Option<String>,
+  pub requests: Option<u64>,
+  pub warmup: Option<String>,
+  pub threads: Option<usize>,
+  pub rate: Option<f64>,
+  pub rate_profile: Option<String>,
+  pub time_scale: Option<f64>,
+  pub interval: Option<String>,
+  pub distribution: Option<String>,
+  pub seed: Option<u64>,
+  pub stage: Option<Vec<StageItem>>,
+  pub endpoint: Option<Vec<EndpointItem>>,
+  pub template: Option<bool>,
+  pub data: Option<String>,
+  pub data_order: Option<String>,
+  pub replay: Option<String>,
+  pub replay_speed: Option<f64>,
+  pub access_log: Option<String>,
+  pub access_log_format: Option<String>,
+  pub access_log_mode: Option<String>,
+  pub access_log_top: Option<usize>,
+  pub only_get: Option<bool>,
+  pub strip_query: Option<bool>,
+  pub rewrite_host: Option<bool>,
+  pub har: Option<String>,
+  pub har_mode: Option<String>,
+  pub openapi: Option<String>,
+  pub operation: Option<Vec<String>>,
+  pub scenario: Option<String>,
+  pub script: Option<String>,
+  pub script_api: Option<String>,
+  pub plugin: Option<String>,
+  pub plugin_budget: Option<String>,
+  pub curl: Option<String>,
+  pub resolve: Option<String>,
+  pub method: Option<String>,
+  pub body: Option<String>,
+  pub header: Option<Headers>,
+  pub expect_status: Option<String>,
+  pub expect_body_contains: Option<Vec<String>>,
+  pub expect_body_regex: Option<Vec<String>>,
+  pub expect_header: Option<Headers>,
+  pub expect_body_sha256: Option<String>,
+  pub max_body: Option<String>,
+  pub threshold: Option<Vec<String>>,
+  pub timeout: Option<String>,
+  pub disable_keepalive: Option<bool>,
+  pub latency: Option<bool>,
+  pub output: Option<String>,
+  pub output_file: Option<String>,
+  pub h2: Option<bool>,
+}
+
+/// A stage as in the command line (eg: "30s:10") or as a table with a duration and either connections or a rate
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StageItem {
+  Spec(String),
+  Table {
+    duration: String,
+    connections: Option<usize>,
+    rate: Option<f64>,
+  },
+}
+
+/// An endpoint as in the command line (eg: "70 GET /items") or as a table
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EndpointItem {
+  Spec(String),
+  Table(EndpointTable),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointTable {
+  #[serde(default = "default_weight")]
+  pub weight: u32,
+  #[serde(default = "default_method")]
+  pub method: String,
+  pub target: String,
+  pub body: Option<String>,
+  #[serde(default)]
+  pub header: Headers,
+}
+
+fn default_weight() -> u32 {
+  1
+}
+
+fn default_method() -> String {
+  String::from("GET")
+}
+
+/// Headers as a list of "key: value" strings like in the command line or as a table of key = value
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Headers {
+  List(Vec<String>),
+  Table(BTreeMap<String, String>),
+}
+
+impl Default for Headers {
+  fn default() -> Self {
+    Headers::List(Vec::new())
+  }
+}
+
+impl Headers {
+  fn into_list(self) -> Vec<String> {
+    match self {
+      Headers::List(list) => list,
+      Headers::Table(table) => table.into_iter().map(|(k, v)| format!("{k}:{v}")).collect(),
+    }
+  }
+}
+
+impl FileConfig {
+  /// Loads a config file, parsed as YAML for the .yaml and .yml extensions and as TOML otherwise
+  pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("error reading config file {}", path.display()))?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("yaml" | "yml") => serde_yaml::from_str(&content).with_context(|| format!("invalid config file {}", path.display())),
+      _ => toml::from_str(&content).with_context(|| format!("invalid config file {}", path.display())),
+    }
+  }
+
+  /// Sets the options of `args` that were not given in the command line or in an environment variable to the ones of the file
+  ///
+  /// The relative paths of the file (the rate profile, the data, replay, access log, HAR and scenario files, the script, the plugin, the OpenAPI spec and the bodies read with @filename) are resolved from the directory of the file
+  pub fn apply(self, args: &mut Args, matches: &ArgMatches, dir: &Path) -> Result<(), anyhow::Error> {
+    let given = |id: &str| matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable));
+
+    macro_rules! set {
+      // an option with a default value
+      ($field:ident, $value:expr) => {
+        if let Some(value) = self.$field {
+          if !given(stringify!($field)) {
+            args.$field = $value(value)?;
+          }
+        }
+      };
+
+      // an optional option
+      (Some $field:ident, $value:expr) => {
+        if let Some(value) = self.$field {
+          if !given(stringify!($field)) {
+            args.$field = Some($value(value)?);
+          }
+        }
+      };
+    }
+
+    let key = |key: &'static str| move |e: String| anyhow::anyhow!("invalid config key {key}: {e}");
+
+    fn ok<T>(value: T) -> Result<T, anyhow::Error> {
+      Ok(value)
+    }
+
+    let duration = |key_name: &'static str| move |value: String| parse_duration(&value).map_err(key(key_name));
+    let body = |value: String| ok(resolve_body(dir, value));
+
+    set!(Some url, ok);
+    set!(concurrency, ok);
+    set!(Some duration, duration("duration"));
+    set!(Some requests, ok);
+    set!(Some warmup, duration("warmup"));
+    set!(threads, ok);
+    set!(Some rate, ok);
+    set!(Some rate_profile, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    set!(time_scale, ok);
+    set!(Some interval, duration("interval"));
+    set!(distribution, |value: String| crate::distribution::parse_distribution(&value).map_err(key("distribution")));
+    set!(Some seed, ok);
+    set!(stage, |items: Vec<StageItem>| {
+      items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| stage(item).map_err(|e| anyhow::anyhow!("invalid config key stage[{i}]: {e}")))
+        .collect::<Result<Vec<_>, _>>()
+    });
+    set!(endpoint, |items: Vec<EndpointItem>| {
+      items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| endpoint(item, dir).map_err(|e| anyhow::anyhow!("invalid config key endpoint[{i}]: {e}")))
+        .collect::<Result<Vec<_>, _>>()
+    });
+    set!(template, ok);
+    set!(Some data, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    set!(data_order, |value: String| DataOrder::from_str(&value, true).map_err(key("data-order")));
+    set!(Some replay, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    set!(Some replay_speed, ok);
+    set!(Some access_log, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    set!(Some access_log_format, ok);
+    set!(access_log_mode, |value: String| LogMode::from_str(&value, true).map_err(key("access-log-mode")));
+    set!(access_log_top, ok);
+    set!(only_get, ok);
+    set!(strip_query, ok);
+    set!(rewrite_host, ok);
+    set!(Some har, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    set!(har_mode, |value: String| HarMode::from_str(&value, true).map_err(key("har-mode")));
+    #[cfg(feature = "openapi")]
+    set!(Some openapi, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    #[cfg(feature = "openapi")]
+    set!(operation, |items: Vec<String>| {
+      items
+        .iter()
+        .map(|item| crate::openapi::parse_operation(item).map_err(|e| anyhow::anyhow!("invalid config key operation: {e}")))
+        .collect::<Result<Vec<_>, _>>()
+    });
+    set!(Some scenario, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    #[cfg(feature = "script")]
+    set!(Some script, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    #[cfg(feature = "script")]
+    set!(script_api, |value: String| ScriptApi::from_str(&value, true).map_err(key("script-api")));
+    #[cfg(feature = "plugin")]
+    set!(Some plugin, |path: String| ok(dir.join(path).to_string_lossy().into_owned()));
+    #[cfg(feature = "plugin")]
+    set!(plugin_budget, duration("plugin-budget"));
+    set!(Some curl, ok);
+    set!(Some resolve, ok);
+    set!(method, ok);
+    set!(Some body, body);
+    set!(header, |headers: Headers| ok(headers.into_list()));
+    set!(Some expect_status, |value: String| crate::check::parse_status(&value).map_err(key("expect-status")));
+    set!(expect_body_contains, |items: Vec<String>| {
+      items.iter().map(|item| crate::check::parse_body_contains(item).map_err(key("expect-body-contains"))).collect::<Result<Vec<_>, _>>()
+    });
+    set!(expect_body_regex, |items: Vec<String>| {
+      items.iter().map(|item| crate::check::parse_body_regex(item).map_err(key("expect-body-regex"))).collect::<Result<Vec<_>, _>>()
+    });
+    set!(expect_header, |headers: Headers| {
+      headers.into_list().iter().map(|item| crate::check::parse_header(item).map_err(key("expect-header"))).collect::<Result<Vec<_>, _>>()
+    });
+    set!(Some expect_body_sha256, |value: String| crate::check::parse_body_sha256(&value).map_err(key("expect-body-sha256")));
+    set!(max_body, |value: String| crate::args::parse_size(&value).map_err(key("max-body")));
+    set!(threshold, |items: Vec<String>| {
+      items.iter().map(|item| crate::threshold::parse_threshold(item).map_err(key("threshold"))).collect::<Result<Vec<_>, _>>()
+    });
+    #[cfg(feature = "timeout")]
+    set!(Some timeout, duration("timeout"));
+    set!(disable_keepalive, ok);
+    #[cfg(feature = "latency")]
+    set!(latency, ok);
+    set!(output, |value: String| Output::from_str(&value, true).map_err(key("output")));
+    set!(Some output_file, ok);
+    #[cfg(all(feature = "h1", feature = "h2"))]
+    set!(h2, ok);
+
+    #[cfg(not(feature = "timeout"))]
+    if self.timeout.is_some() {
+      anyhow::bail!("invalid config key timeout: feature timeout must be enabled at compile time to use timeouts");
+    }
+
+    #[cfg(not(feature = "openapi"))]
+    if self.openapi.is_some() || self.operation.is_some() {
+      anyhow::bail!("invalid config key openapi: feature openapi must be enabled at compile time to use OpenAPI specs");
+    }
+
+    #[cfg(not(feature = "script"))]
+    if self.script.is_some() || self.script_api.is_some() {
+      anyhow::bail!("invalid config key script: feature script must be enabled at compile time to use scripts");
+    }
+
+    #[cfg(not(feature = "plugin"))]
+    if self.plugin.is_some() || self.plugin_budget.is_some() {
+      anyhow::bail!("invalid config key plugin: feature plugin must be enabled at compile time to use plugins");
+    }
+
+    #[cfg(not(feature = "latency"))]
+    if self.latency.is_some() {
+      anyhow::bail!("invalid config key latency: feature latency must be enabled at compile time to measure latencies");
+    }
+
+    #[cfg(not(all(feature = "h1", feature = "h2")))]
+    if self.h2.is_some() {
+      anyhow::bail!("invalid config key h2: features h1 and h2 must be enabled at compile time to choose the protocol");
+    }
+
+    Ok(())
+  }
+}
+
+fn stage(item: StageItem) -> Result<Stage, String> {
+  match item {
+    StageItem::Spec(spec) => crate::stage::parse_stage(&spec),
+    StageItem::Table {
+      duration,
+      connections,
+      rate,
+    } => {
+      let duration = parse_duration(&duration)?;
+      let target = match (connections, rate) {
+        (Some(connections), None) => Target::Connections(connections),
+        (None, Some(rate)) if rate.is_finite() && rate >= 0.0 => Target::Rate(rate),
+        (None, Some(rate)) => return Err(format!("invalid stage rate {rate}, must be equal or greater than 0")),
+        _ => return Err(String::from("a stage must have either connections or a rate")),
+      };
+
+      Ok(Stage { duration, target })
+    }
+  }
+}
+
+fn endpoint(item: EndpointItem, dir: &Path) -> Result<EndpointSpec, String> {
+  let spec = match item {
+    EndpointItem::Spec(spec) => crate::endpoint::parse_endpoint(&spec)?,
+    EndpointItem::Table(table) => EndpointSpec {
+      weight: table.weight,
+      name: None,
+      method: table.method,
+      target: table.target,
+      body: table.body.as_deref().map(Body::parse),
+      headers: table.header.into_list(),
+      template: true,
+    },
+  };
+
+  Ok(EndpointSpec {
+    body: spec.body.map(|body| match body {
+      Body::File(path) => Body::File(dir.join(path).to_string_lossy().into_owned()),
+      body => body,
+    }),
+    ..spec
+  })
+}
+
+/// Resolves the path of a body read from a file relative to `dir`
+fn resolve_body(dir: &Path, body: String) -> String {
+  match body.strip_prefix('@') {
+    Some(path) => format!("@{}", dir.join(path).to_string_lossy()),
+    None => body,
+  }
+}
+
+/// Fills the options not given in the command line with the ones of the --config file, if any
+pub fn apply(args: &mut Args, matches: &ArgMatches) -> Result<(), anyhow::Error> {
+  let Some(path) = args.config.clone() else {
+    return Ok(());
+  };
+
+  let path = PathBuf::from(path);
+  let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+  FileConfig::load(&path)?.apply(args, matches, &dir)
+}
@@ -0,0 +1,340 @@
+use anyhow::Context;
+use base64::Engine;
+use clap::{CommandFactory, FromArgMatches};
+
+use crate::{args::Args, endpoint::Body, report::Report};
+
+/// The flags that only change what curl prints, they have no effect on the requests
+const OUTPUT_FLAGS: &[&str] = &["-s", "--silent", "-S", "--show-error", "-v", "--verbose", "-i", "--include"];
+
+/// The request of a curl command line, in the format of the options of rload
+#[derive(Debug, Clone, Default)]
+pub struct CurlCommand {
+  pub url: String,
+  pub method: Option<String>,
+  /// the headers as "key: value"
+  pub headers: Vec<String>,
+  /// the body, the files of --data-binary are read when the request is built
+  pub body: Option<Body>,
+  pub http2: bool,
+  /// the responses are asked to be compressed with --compressed, rload doesn't decompress them
+  pub compressed: bool,
+  /// the address to connect to, as in curl's --resolve (eg: example.com:443:127.0.0.1)
+  pub resolve: Option<String>,
+}
+
+/// Runs the `from-curl` subcommand, `rload from-curl '<curl command>' [options]` is the same as `rload --curl '<curl command>' [options]`
+pub fn run() -> Result<Report, anyhow::Error> {
+  let argv = std::env::args_os()
+    .enumerate()
+    .map(|(i, arg)| if i == 1 { "--curl".into() } else { arg });
+
+  let matches = Args::command().get_matches_from(argv);
+  #[allow(unused_mut)]
+  let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+  #[cfg(feature = "config")]
+  crate::config::apply(&mut args, &matches)?;
+
+  crate::cli::run_with_args(args)
+}
+
+/// Translates a curl command line, the options that can't be honored are rejected instead of ignored
+///
+/// The supported ones are -X, -H, -d (and --data, --data-raw, --data-ascii), --data-binary, -u, -k, --http1.1, --http2,
+/// --resolve, --compressed and --url, -s, -S, -v, -i and -o are accepted as they only change what curl prints
+///
+/// --compressed only adds the accept-encoding header, unlike curl the responses are not decompressed,
+/// so it can't be used with the checks of the body or the extractors of a scenario
+pub fn parse(command: &str) -> Result<CurlCommand, anyhow::Error> {
+  let words = shell_words::split(command).context("invalid curl command, unbalanced quotes")?;
+
+  let mut words = words.into_iter().peekable();
+  if words.peek().is_some_and(|word| word == "curl") {
+    words.next();
+  }
+
+  let mut curl = CurlCommand::default();
+  let mut urls = Vec::new();
+  let mut data = Vec::<String>::new();
+  let mut binary = None::<Body>;
+  let mut user = None::<String>;
+
+  // the short flags can be grouped (eg: -sSk) and their value can follow them directly (eg: -XPOST)
+  let mut flags = Vec::<(String, Option<String>)>::new();
+  while let Some(word) = words.next() {
+    if word == "--" {
+      urls.extend(words.by_ref());
+      break;
+    }
+
+    if let Some(long) = word.strip_prefix("--") {
+      let (name, value) = match long.split_once('=') {
+        Some((name, value)) => (format!("--{name}"), Some(value.to_string())),
+        None => (word.clone(), None),
+      };
+
+      let value = match (takes_value(&name), value) {
+        (true, Some(value)) => Some(value),
+        (true, None) => Some(words.next().with_context(|| format!("curl option {name} requires a value"))?),
+        (false, Some(_)) => anyhow::bail!("curl option {name} doesn't take a value"),
+        (false, None) => None,
+      };
+
+      flags.push((name, value));
+      continue;
+    }
+
+    let Some(short) = word.strip_prefix('-').filter(|short| !short.is_empty()) else {
+      urls.push(word);
+      continue;
+    };
+
+    for (i, c) in short.char_indices() {
+      let name = format!("-{c}");
+      if takes_value(&name) {
+        let rest = &short[i + c.len_utf8()..];
+        let value = match rest.is_empty() {
+          true => words.next().with_context(|| format!("curl option {name} requires a value"))?,
+          false => rest.to_string(),
+        };
+        flags.push((name, Some(value)));
+        break;
+      }
+
+      flags.push((name, None));
+    }
+  }
+
+  for (name, value) in flags {
+    let value = value.unwrap_or_default();
+    match name.as_str() {
+      "-X" | "--request" => curl.method = Some(value),
+
+      "-H" | "--header" => {
+        if value.starts_with('@') {
+          anyhow::bail!("unsupported curl option {name} {value}, the headers must be given one by one instead of read from a file");
+        }
+        curl.headers.push(value);
+      }
+
+      "-d" | "--data" | "--data-ascii" => match value.strip_prefix('@') {
+        // like curl, the carriage returns and newlines of the file are removed
+        Some(path) => {
+          let content = std::fs::read_to_string(path).with_context(|| format!("error reading curl data file {path}, use --data-binary to send a binary file"))?;
+          data.push(content.replace(['\r', '\n'], ""));
+        }
+        None => data.push(value),
+      },
+
+      "--data-raw" => data.push(value),
+
+      "--data-binary" => {
+        if binary.is_some() {
+          anyhow::bail!("unsupported curl option --data-binary given more than once");
+        }
+        binary = Some(Body::parse(&value));
+      }
+
+      "-u" | "--user" => {
+        if !value.contains(':') {
+          anyhow::bail!("invalid curl option {name} {value}, the password must be given as user:password");
+        }
+        user = Some(value);
+      }
+
+      // rload never verifies the certificates of the server
+      "-k" | "--insecure" => {}
+
+      "--http1.1" => curl.http2 = false,
+      "--http2" => curl.http2 = true,
+
+      "--resolve" => {
+        if curl.resolve.is_some() {
+          anyhow::bail!("unsupported curl option --resolve given more than once, rload connects to a single address");
+        }
+        curl.resolve = Some(value);
+      }
+
+      "--compressed" => curl.compressed = true,
+
+      "--url" => urls.push(value),
+
+      "-o" | "--output" => {}
+      name if OUTPUT_FLAGS.contains(&name) => {}
+
+      name => anyhow::bail!(
+        "unsupported curl option {name}, only -X, -H, -d, --data-binary, -u, -k, --http1.1, --http2, --resolve, --compressed and --url are supported"
+      ),
+    }
+  }
+
+  curl.url = match urls.as_slice() {
+    [url] if url.contains("://") => url.clone(),
+    // curl defaults to http for the urls without a scheme
+    [url] => format!("http://{url}"),
+    [] => anyhow::bail!("invalid curl command, missing url"),
+    _ => anyhow::bail!("invalid curl command with {} urls, rload requests a single url", urls.len()),
+  };
+
+  curl.body = match (data.is_empty(), binary) {
+    (true, None) => None,
+    (false, None) => Some(Body::Bytes(data.join("&").into_bytes())),
+    (true, Some(binary)) => Some(binary),
+    (false, Some(_)) => anyhow::bail!("unsupported curl command with both -d and --data-binary, use only one of them"),
+  };
+
+  // like curl, a body makes the request a form post unless the method and the content type are given
+  if curl.body.is_some() {
+    curl.method.get_or_insert_with(|| String::from("POST"));
+    if !has_header(&curl.headers, "content-type") {
+      curl.headers.push(String::from("content-type: application/x-www-form-urlencoded"));
+    }
+  }
+
+  if let Some(user) = user {
+    if !has_header(&curl.headers, "authorization") {
+      let credentials = base64::engine::general_purpose::STANDARD.encode(user);
+      curl.headers.push(format!("authorization: Basic {credentials}"));
+    }
+  }
+
+  if curl.compressed && !has_header(&curl.headers, "accept-encoding") {
+    curl.headers.push(String::from("accept-encoding: deflate, gzip, br, zstd"));
+  }
+
+  Ok(curl)
+}
+
+fn takes_value(name: &str) -> bool {
+  matches!(
+    name,
+    "-X"
+      | "--request"
+      | "-H"
+      | "--header"
+      | "-d"
+      | "--data"
+      | "--data-ascii"
+      | "--data-raw"
+      | "--data-binary"
+      | "-u"
+      | "--user"
+      | "--resolve"
+      | "--url"
+      | "-o"
+      | "--output"
+  )
+}
+
+fn has_header(headers: &[String], name: &str) -> bool {
+  headers.iter().any(|header| {
+    header
+      .split_once(':')
+      .is_some_and(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn bytes(body: &str) -> Option<Body> {
+    Some(Body::Bytes(body.as_bytes().to_vec()))
+  }
+
+  #[test]
+  fn parses_a_plain_get() {
+    let curl = parse("curl https://example.com/items").unwrap();
+    assert_eq!(curl.url, "https://example.com/items");
+    assert_eq!(curl.method, None);
+    assert_eq!(curl.body, None);
+    assert!(curl.headers.is_empty());
+    assert!(!curl.http2);
+
+    // curl defaults to http
+    assert_eq!(parse("example.com").unwrap().url, "http://example.com");
+  }
+
+  #[test]
+  fn groups_short_flags() {
+    let curl = parse("curl -sSkXPUT -Hx-a:1 https://example.com").unwrap();
+    assert_eq!(curl.method.as_deref(), Some("PUT"));
+    assert_eq!(curl.headers, vec![String::from("x-a:1")]);
+
+    let curl = parse("curl -sX DELETE https://example.com").unwrap();
+    assert_eq!(curl.method.as_deref(), Some("DELETE"));
+
+    let curl = parse("curl --request=PATCH --url https://example.com").unwrap();
+    assert_eq!(curl.method.as_deref(), Some("PATCH"));
+    assert_eq!(curl.url, "https://example.com");
+  }
+
+  #[test]
+  fn joins_data_and_makes_a_form_post() {
+    let curl = parse("curl -d a=1 --data b=2 --data-raw c=3 https://example.com").unwrap();
+    assert_eq!(curl.method.as_deref(), Some("POST"));
+    assert_eq!(curl.body, bytes("a=1&b=2&c=3"));
+    assert_eq!(curl.headers, vec![String::from("content-type: application/x-www-form-urlencoded")]);
+
+    // the method and the content type given are kept
+    let curl = parse(r#"curl -X PUT -H "Content-Type: application/json" -d '{"a":1}' https://example.com"#).unwrap();
+    assert_eq!(curl.method.as_deref(), Some("PUT"));
+    assert_eq!(curl.body, bytes(r#"{"a":1}"#));
+    assert_eq!(curl.headers, vec![String::from("Content-Type: application/json")]);
+  }
+
+  #[test]
+  fn sends_data_raw_as_it_is() {
+    let curl = parse("curl --data-raw @/etc/hostname https://example.com").unwrap();
+    assert_eq!(curl.body, bytes("@/etc/hostname"));
+  }
+
+  #[test]
+  fn reads_data_files() {
+    let dir = std::env::temp_dir().join(format!("rload-curl-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("data.txt");
+    // the content of a file read with -d is never read again as a file, even when it starts with @
+    std::fs::write(&path, "@a=1\r\n&b=2\n").unwrap();
+
+    let curl = parse(&format!("curl -d @{} -d c=3 https://example.com", path.display())).unwrap();
+    assert_eq!(curl.body, bytes("@a=1&b=2&c=3"));
+
+    // the files of --data-binary are sent as they are when the request is built
+    let curl = parse(&format!("curl --data-binary @{} https://example.com", path.display())).unwrap();
+    assert_eq!(curl.body, Some(Body::File(path.display().to_string())));
+    assert_eq!(curl.body.unwrap().read().unwrap(), b"@a=1\r\n&b=2\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn translates_user_and_compressed() {
+    let curl = parse("curl -u user:pass --compressed --http2 https://example.com").unwrap();
+    assert!(curl.http2);
+    assert!(curl.compressed);
+    assert_eq!(
+      curl.headers,
+      vec![String::from("authorization: Basic dXNlcjpwYXNz"), String::from("accept-encoding: deflate, gzip, br, zstd")]
+    );
+  }
+
+  #[test]
+  fn rejects_unsupported_commands() {
+    let err = |command: &str| parse(command).unwrap_err().to_string();
+
+    assert!(err("curl -L https://example.com").contains("unsupported curl option -L"));
+    assert!(err("curl --retry 3 https://example.com").contains("unsupported curl option --retry"));
+    assert!(err("curl -sL https://example.com").contains("unsupported curl option -L"));
+    assert!(err("curl -k=1 https://example.com").contains("unsupported curl option -="));
+    assert!(err("curl --insecure=1 https://example.com").contains("doesn't take a value"));
+    assert!(err("curl -X").contains("requires a value"));
+    assert!(err("curl -H @headers.txt https://example.com").contains("one by one"));
+    assert!(err("curl -d a=1 --data-binary b https://example.com").contains("only one of them"));
+    assert!(err("curl -u user https://example.com").contains("user:password"));
+    assert!(err("curl").contains("missing url"));
+    assert!(err("curl https://a.com https://b.com").contains("single url"));
+    assert!(err("curl 'https://example.com").contains("unbalanced quotes"));
+  }
+}
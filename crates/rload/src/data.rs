@@ -0,0 +1,130 @@
+use anyhow::Context;
+use std::path::Path;
+
+/// The order in which the rows of a data file are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum DataOrder {
+  /// each request takes the next row, starting over after the last one
+  Sequential,
+  /// each request takes a random row
+  Random,
+  /// each connection takes a row and uses it for all of its requests
+  Sticky,
+}
+
+impl std::fmt::Display for DataOrder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DataOrder::Sequential => write!(f, "sequential"),
+      DataOrder::Random => write!(f, "random"),
+      DataOrder::Sticky => write!(f, "sticky"),
+    }
+  }
+}
+
+/// The values of a row, in the order of the columns
+pub type Row = Box<[Box<[u8]>]>;
+
+/// The rows of a data file that feed the template variables, each column is a variable with its name (eg: {{user_id}})
+#[derive(Debug, Clone)]
+pub struct Data {
+  pub columns: Vec<String>,
+  pub rows: Vec<Row>,
+}
+
+impl Data {
+  /// Loads a data file, parsed as JSON lines for the .jsonl and .ndjson extensions, with an object per line,
+  /// and as CSV otherwise, with the names of the columns in the first line
+  pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+    let content = std::fs::read(path).with_context(|| format!("error reading data file {}", path.display()))?;
+
+    let data = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("jsonl" | "ndjson") => parse_jsonl(&content),
+      _ => parse_csv(&content),
+    }
+    .with_context(|| format!("error parsing data file {}", path.display()))?;
+
+    if data.rows.is_empty() {
+      anyhow::bail!("data file {} has no rows", path.display());
+    }
+
+    Ok(data)
+  }
+
+  /// The rows used by a thread, each thread takes its own contiguous slice of the rows so the threads never share them,
+  /// unless there are fewer rows than threads
+  pub fn partition(&self, threads: usize, index: usize) -> &[Row] {
+    let len = self.rows.len();
+    if len < threads {
+      let i = index % len;
+      return &self.rows[i..i + 1];
+    }
+
+    &self.rows[index * len / threads..(index + 1) * len / threads]
+  }
+}
+
+fn parse_csv(content: &[u8]) -> Result<Data, anyhow::Error> {
+  let mut reader = csv::ReaderBuilder::new().has_headers(true).comment(Some(b'#')).from_reader(content);
+
+  let columns = reader
+    .headers()
+    .context("error reading the names of the columns")?
+    .iter()
+    .map(|column| column.trim().to_string())
+    .collect::<Vec<_>>();
+
+  let mut rows = Vec::new();
+  for (i, record) in reader.byte_records().enumerate() {
+    let record = record.with_context(|| format!("invalid row {}", i + 1))?;
+    rows.push(record.iter().map(Box::from).collect());
+  }
+
+  Ok(Data { columns, rows })
+}
+
+fn parse_jsonl(content: &[u8]) -> Result<Data, anyhow::Error> {
+  let content = std::str::from_utf8(content).context("data file is not valid utf-8")?;
+
+  let mut objects = Vec::new();
+  for (i, line) in content.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let object = match serde_json::from_str::<serde_json::Value>(line).with_context(|| format!("invalid json in line {}", i + 1))? {
+      serde_json::Value::Object(object) => object,
+      _ => anyhow::bail!("invalid line {}, each line must be a json object", i + 1),
+    };
+
+    objects.push(object);
+  }
+
+  // the columns are the keys of all the objects, serde_json doesn't keep the order of the keys of an object so each one adds its new keys sorted
+  let mut columns = Vec::<String>::new();
+  for object in &objects {
+    for key in object.keys() {
+      if !columns.contains(key) {
+        columns.push(key.clone());
+      }
+    }
+  }
+
+  // strings are used as they are and the other values as json, missing keys are empty
+  let rows = objects
+    .into_iter()
+    .map(|mut object| {
+      columns
+        .iter()
+        .map(|column| match object.remove(column) {
+          None => Box::from(&b""[..]),
+          Some(serde_json::Value::String(s)) => s.into_bytes().into_boxed_slice(),
+          Some(value) => value.to_string().into_bytes().into_boxed_slice(),
+        })
+        .collect()
+    })
+    .collect();
+
+  Ok(Data { columns, rows })
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+/// The distribution of the arrival times of requests in constant-throughput mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Distribution {
+  /// evenly spaced requests
+  Constant,
+  /// exponentially distributed gaps between requests, as in a Poisson process
+  Poisson,
+  /// gaps uniformly distributed around the mean gap by ± this ratio of it
+  Uniform(f64),
+  /// evenly spaced bursts of requests during `on`, followed by `off` without requests,
+  /// the rate during the bursts is raised so the average rate is still the target one
+  Bursty { on: Duration, off: Duration },
+}
+
+impl Distribution {
+  /// Whether the arrival times depend on the random seed
+  pub fn is_random(self) -> bool {
+    matches!(self, Distribution::Poisson | Distribution::Uniform(_))
+  }
+
+  /// Maps an offset of the evenly spread timeline to the actual arrival time
+  #[inline(always)]
+  pub fn warp(self, offset: Duration) -> Duration {
+    match self {
+      Distribution::Bursty { on, off } => {
+        let period = (on + off).as_secs_f64();
+        let t = offset.as_secs_f64();
+        let k = (t / period).floor();
+        Duration::from_secs_f64(k * period + (t - k * period) * on.as_secs_f64() / period)
+      }
+      _ => offset,
+    }
+  }
+
+  /// The inverse of [`Distribution::warp`], the offsets during the off period of a burst map to the end of it
+  pub fn unwarp(self, offset: Duration) -> Duration {
+    match self {
+      Distribution::Bursty { on, off } => {
+        let period = (on + off).as_secs_f64();
+        let t = offset.as_secs_f64();
+        let k = (t / period).floor();
+        let rest = (t - k * period).min(on.as_secs_f64());
+        Duration::from_secs_f64(k * period + rest * period / on.as_secs_f64())
+      }
+      _ => offset,
+    }
+  }
+}
+
+impl std::fmt::Display for Distribution {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Distribution::Constant => write!(f, "constant"),
+      Distribution::Poisson => write!(f, "poisson"),
+      Distribution::Uniform(jitter) => write!(f, "uniform:{}%", jitter * 100.0),
+      Distribution::Bursty { on, off } => write!(
+        f,
+        "bursty:{}:{}",
+        crate::fmt::format_duration(*on),
+        crate::fmt::format_duration(*off)
+      ),
+    }
+  }
+}
+
+/// Parses a distribution in the format of constant, poisson, uniform[:jitter] (eg: uniform:20%) or bursty:on:off (eg: bursty:1s:4s)
+pub fn parse_distribution(s: &str) -> Result<Distribution, String> {
+  let s = s.trim();
+  let (name, params) = match s.split_once(':') {
+    Some((name, params)) => (name, Some(params)),
+    None => (s, None),
+  };
+
+  match (name, params) {
+    ("constant", None) => Ok(Distribution::Constant),
+    ("poisson" | "exponential", None) => Ok(Distribution::Poisson),
+    ("uniform", None) => Ok(Distribution::Uniform(0.5)),
+    ("uniform", Some(jitter)) => Ok(Distribution::Uniform(crate::args::parse_ratio(jitter)?)),
+    ("bursty", Some(params)) => {
+      let (on, off) = params
+        .split_once(':')
+        .ok_or_else(|| String::from("invalid bursty distribution, must be in the format of bursty:on:off (eg: bursty:1s:4s)"))?;
+
+      let on = crate::args::parse_duration(on)?;
+      let off = crate::args::parse_duration(off)?;
+
+      if on.is_zero() {
+        return Err(String::from("invalid bursty distribution, the on period must be greater than 0"));
+      }
+
+      Ok(Distribution::Bursty { on, off })
+    }
+    _ => Err(format!(
+      "invalid distribution {s}, must be one of constant, poisson, uniform[:jitter] (eg: uniform:20%) or bursty:on:off (eg: bursty:1s:4s)"
+    )),
+  }
+}
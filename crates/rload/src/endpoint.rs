@@ -0,0 +1,257 @@
+use anyhow::Context;
+use std::path::Path;
+
+use crate::{args::Request, extract::Extract};
+
+/// The body of a request, sent as it is or read from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+  Bytes(Vec<u8>),
+  /// the path of the file
+  File(String),
+}
+
+impl Body {
+  /// Parses a body given as an option, use @filename to read it from a file
+  pub fn parse(s: &str) -> Self {
+    match s.strip_prefix('@') {
+      Some(path) => Body::File(path.to_string()),
+      None => Body::Bytes(s.as_bytes().to_vec()),
+    }
+  }
+
+  pub fn read(self) -> Result<Vec<u8>, anyhow::Error> {
+    match self {
+      Body::Bytes(bytes) => Ok(bytes),
+      Body::File(path) => {
+        let canonical = Path::new(&path).canonicalize().with_context(|| format!("error resolving path for {path}"))?;
+        std::fs::read(&path).with_context(|| format!("error reading file from {}", canonical.display()))
+      }
+    }
+  }
+}
+
+/// An endpoint of the request mix as given in the command line, before being resolved and pre-encoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointSpec {
+  pub weight: u32,
+  /// the name of the endpoint in the report, by default its method and path
+  pub name: Option<String>,
+  pub method: String,
+  /// a path relative to the url or a full url with the same scheme, host and port
+  pub target: String,
+  pub body: Option<Body>,
+  pub headers: Vec<String>,
+  /// whether the request can have template variables, the requests imported from a file are sent as they are
+  pub template: bool,
+}
+
+/// Parses an endpoint in the format of `weight method target [body=value] [header=key:value]...` (eg: `10 POST /orders body=@order.json header=content-type:application/json`)
+pub fn parse_endpoint(s: &str) -> Result<EndpointSpec, String> {
+  let invalid = || {
+    format!("invalid endpoint {s}, endpoint must be in the format of weight method target [body=value] [header=key:value]... (eg: 70 GET /items)")
+  };
+
+  let mut parts = split_whitespace(s);
+
+  let weight = parts
+    .next()
+    .ok_or_else(invalid)?
+    .parse::<u32>()
+    .map_err(|_| format!("invalid endpoint weight in {s}, must be an integer"))?;
+
+  let method = parts.next().ok_or_else(invalid)?.to_string();
+  let target = parts.next().ok_or_else(invalid)?.to_string();
+
+  let mut body = None;
+  let mut headers = Vec::new();
+  for option in parts {
+    match option.split_once('=') {
+      Some(("body", value)) => body = Some(Body::parse(value)),
+      Some(("header", value)) => headers.push(value.to_string()),
+      _ => return Err(format!("invalid endpoint option {option} in {s}, must be body=value or header=key:value")),
+    }
+  }
+
+  Ok(EndpointSpec {
+    weight,
+    name: None,
+    method,
+    target,
+    body,
+    headers,
+    template: true,
+  })
+}
+
+/// Splits by whitespace, except inside template variables like {{rand_int 1 100}}
+fn split_whitespace(s: &str) -> impl Iterator<Item = &str> {
+  let mut rest = s.trim_start();
+  std::iter::from_fn(move || {
+    if rest.is_empty() {
+      return None;
+    }
+
+    let mut in_var = false;
+    let mut end = rest.len();
+    for (i, c) in rest.char_indices() {
+      if rest[i..].starts_with("{{") {
+        in_var = true;
+      } else if rest[i..].starts_with("}}") {
+        in_var = false;
+      } else if c.is_whitespace() && !in_var {
+        end = i;
+        break;
+      }
+    }
+
+    let part = &rest[..end];
+    rest = rest[end..].trim_start();
+    Some(part)
+  })
+}
+
+/// A pre-encoded endpoint of the request mix
+#[derive(Clone, Copy)]
+pub struct Endpoint<'a> {
+  /// the method and the path of the endpoint (eg: GET /items)
+  pub name: &'a str,
+  pub weight: u32,
+  pub method: &'a str,
+  pub body_len: usize,
+  pub request: Request<'a>,
+  /// the index of the row of the endpoints report the results are recorded in, shared by the endpoints with the same name
+  pub report: usize,
+  /// the values extracted from the responses of the endpoint, only the steps of a scenario extract values
+  pub extract: &'a [Extract],
+}
+
+/// The number of rows of the endpoints report
+pub fn reports(endpoints: &[Endpoint]) -> usize {
+  endpoints.iter().map(|endpoint| endpoint.report + 1).max().unwrap_or(0)
+}
+
+/// The total weight the mix is scaled down to, to keep the sequence short, the endpoints keep a weight of at least 1
+pub const MAX_TOTAL_WEIGHT: u64 = 10_000;
+
+/// The order in which the endpoints are requested, as indexes of the endpoints
+///
+/// Each endpoint appears as many times as its weight (reduced by the common divisor of all the weights
+/// and scaled down to [`MAX_TOTAL_WEIGHT`] in total), spread evenly across the sequence by a smooth weighted round robin
+pub fn mix(weights: &[u32]) -> Vec<usize> {
+  fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+      a
+    } else {
+      gcd(b, a % b)
+    }
+  }
+
+  let divisor = weights.iter().copied().fold(0, gcd).max(1);
+  let weights = weights.iter().map(|weight| (weight / divisor) as u64).collect::<Vec<_>>();
+
+  let total: u64 = weights.iter().sum();
+  let scale = match total > MAX_TOTAL_WEIGHT {
+    true => MAX_TOTAL_WEIGHT as f64 / total as f64,
+    false => 1.0,
+  };
+
+  let weights = weights
+    .into_iter()
+    .map(|weight| match weight {
+      0 => 0,
+      weight => ((weight as f64 * scale).round() as i64).max(1),
+    })
+    .collect::<Vec<_>>();
+  let total: i64 = weights.iter().sum();
+
+  let mut current = vec![0i64; weights.len()];
+  let mut sequence = Vec::with_capacity(total as usize);
+  for _ in 0..total {
+    for (current, weight) in current.iter_mut().zip(&weights) {
+      *current += weight;
+    }
+
+    // the first endpoint with the highest current weight
+    let (i, _) = current
+      .iter()
+      .enumerate()
+      .rev()
+      .max_by_key(|(_, current)| **current)
+      .unwrap();
+
+    current[i] -= total;
+    sequence.push(i);
+  }
+
+  sequence
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn counts(sequence: &[usize], endpoints: usize) -> Vec<u64> {
+    let mut counts = vec![0; endpoints];
+    for i in sequence {
+      counts[*i] += 1;
+    }
+    counts
+  }
+
+  #[test]
+  fn mix_spreads_each_endpoint_by_its_weight() {
+    assert_eq!(mix(&[3, 1]), vec![0, 0, 1, 0]);
+    assert_eq!(mix(&[1, 1, 1]), vec![0, 1, 2]);
+
+    let sequence = mix(&[5, 3, 2]);
+    assert_eq!(counts(&sequence, 3), vec![5, 3, 2]);
+    // the heaviest endpoint is never requested more than twice in a row
+    assert!(!sequence.windows(3).any(|window| window.iter().all(|i| *i == 0)));
+  }
+
+  #[test]
+  fn mix_reduces_by_the_common_divisor() {
+    assert_eq!(mix(&[70, 20, 10]).len(), 10);
+    assert_eq!(counts(&mix(&[70, 20, 10]), 3), vec![7, 2, 1]);
+    assert_eq!(mix(&[1000]), vec![0]);
+  }
+
+  #[test]
+  fn mix_skips_endpoints_without_weight() {
+    assert_eq!(mix(&[0, 2, 0, 1]), vec![1, 3, 1]);
+  }
+
+  #[test]
+  fn mix_scales_down_large_weights() {
+    let sequence = mix(&[4_000_000_000, 3_999_999_999]);
+    assert!(sequence.len() as u64 <= MAX_TOTAL_WEIGHT + 2);
+    assert_eq!(counts(&sequence, 2), vec![5000, 5000]);
+
+    // the light endpoints keep a weight of at least 1
+    let sequence = mix(&[u32::MAX, 1, 7]);
+    assert!(sequence.len() as u64 <= MAX_TOTAL_WEIGHT + 2);
+    assert_eq!(counts(&sequence, 3)[1..], [1, 1]);
+  }
+
+  #[test]
+  fn parses_endpoints() {
+    let spec = parse_endpoint("10 POST /orders body=@order.json header=content-type:application/json").unwrap();
+    assert_eq!(spec.weight, 10);
+    assert_eq!(spec.method, "POST");
+    assert_eq!(spec.target, "/orders");
+    assert_eq!(spec.body, Some(Body::File(String::from("order.json"))));
+    assert_eq!(spec.headers, vec![String::from("content-type:application/json")]);
+
+    let spec = parse_endpoint("1 POST /orders body={}").unwrap();
+    assert_eq!(spec.body, Some(Body::Bytes(b"{}".to_vec())));
+
+    // the spaces inside template variables don't split the endpoint
+    let spec = parse_endpoint("1 GET /items/{{rand_int 1 100}}").unwrap();
+    assert_eq!(spec.target, "/items/{{rand_int 1 100}}");
+
+    assert!(parse_endpoint("GET /items").is_err());
+    assert!(parse_endpoint("1 GET").is_err());
+    assert!(parse_endpoint("1 GET /items query=1").is_err());
+  }
+}
@@ -10,6 +10,7 @@ pub enum ErrorKind {
   TlsHandshake,
   Read,
   ReadBody,
+  BodyTooLarge,
   Write,
   Parse,
   Timeout,
@@ -18,6 +19,12 @@ pub enum ErrorKind {
   H2Send,
   H2Recv,
   H2Body,
+  Extract,
+  Template,
+  Script,
+  Plugin,
+  PluginBudget,
+  Assertion,
 }
 
 impl std::fmt::Display for ErrorKind {
@@ -27,6 +34,7 @@ impl std::fmt::Display for ErrorKind {
       ErrorKind::TlsHandshake => write!(f, "tls-handshake"),
       ErrorKind::Read => write!(f, "read"),
       ErrorKind::ReadBody => write!(f, "read-body"),
+      ErrorKind::BodyTooLarge => write!(f, "body-too-large"),
       ErrorKind::Write => write!(f, "write"),
       ErrorKind::Parse => write!(f, "parse"),
       ErrorKind::Timeout => write!(f, "timeout"),
@@ -35,6 +43,12 @@ impl std::fmt::Display for ErrorKind {
       ErrorKind::H2Send => write!(f, "h2-send"),
       ErrorKind::H2Recv => write!(f, "h2-recv"),
       ErrorKind::H2Body => write!(f, "h2-body"),
+      ErrorKind::Extract => write!(f, "extract"),
+      ErrorKind::Template => write!(f, "template"),
+      ErrorKind::Script => write!(f, "script"),
+      ErrorKind::Plugin => write!(f, "plugin"),
+      ErrorKind::PluginBudget => write!(f, "plugin-budget"),
+      ErrorKind::Assertion => write!(f, "assertion"),
     }
   }
 }
@@ -90,6 +104,19 @@ impl Errors {
   }
 }
 
+/// The count of each kind of error, by the name of the kind
+#[cfg(feature = "serde")]
+impl serde::Serialize for Errors {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut map = serializer.serialize_map(Some(ErrorKind::COUNT))?;
+    for kind in ErrorKind::iter() {
+      map.serialize_entry(&kind.to_string(), &self.get(kind))?;
+    }
+    map.end()
+  }
+}
+
 impl Default for Errors {
   #[inline(always)]
   fn default() -> Self {
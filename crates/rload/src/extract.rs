@@ -0,0 +1,172 @@
+use serde_json::Value;
+
+/// The parts of a response that are buffered, besides the status that always is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Needs {
+  pub headers: bool,
+  pub body: bool,
+}
+
+impl Needs {
+  pub const ALL: Needs = Needs { headers: true, body: true };
+
+  pub fn union(self, other: Needs) -> Needs {
+    Needs {
+      headers: self.headers || other.headers,
+      body: self.body || other.body,
+    }
+  }
+}
+
+/// A response buffered to extract values from it, only the responses of the requests that extract something are buffered
+#[derive(Debug, Clone, Default)]
+pub struct Response {
+  pub status: u16,
+  /// the names of the headers in lowercase
+  pub headers: Vec<(String, Vec<u8>)>,
+  pub body: Vec<u8>,
+  /// the parts of the next response that are buffered, the others are left empty
+  pub needs: Needs,
+  /// the maximum size of the body, a larger one fails its request as a body-too-large error
+  pub max_body: usize,
+}
+
+impl Response {
+  /// Discards the previous response, keeping the allocations
+  #[inline(always)]
+  pub fn clear(&mut self) {
+    self.status = 0;
+    self.headers.clear();
+    self.body.clear();
+  }
+
+  fn header(&self, name: &str) -> impl Iterator<Item = &[u8]> + '_ {
+    let name = name.to_ascii_lowercase();
+    self
+      .headers
+      .iter()
+      .filter(move |(key, _)| *key == name)
+      .map(|(_, value)| value.as_slice())
+  }
+}
+
+/// How a value is extracted from a response
+#[derive(Debug, Clone)]
+pub enum Extractor {
+  /// a value of the JSON body, by a JSONPath like $.data.items[0].id
+  Json(Vec<PathSegment>),
+  /// the first group of a regex on the body, or the whole match if the regex has no groups
+  Regex(regex::bytes::Regex),
+  /// the value of a response header
+  Header(String),
+  /// the value of a cookie set by the response
+  Cookie(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+  Key(String),
+  /// an index of an array, the negative ones count from the end
+  Index(i64),
+}
+
+/// An extractor that binds its value to a template variable, by the index of the variable
+#[derive(Debug, Clone)]
+pub struct Extract {
+  pub var: usize,
+  pub extractor: Extractor,
+}
+
+/// Parses an extractor, `kind` is one of json, regex, header or cookie
+pub fn parse_extractor(kind: &str, expr: &str) -> Result<Extractor, String> {
+  let extractor = match kind {
+    "json" => Extractor::Json(parse_json_path(expr)?),
+    "regex" => Extractor::Regex(regex::bytes::Regex::new(expr).map_err(|e| format!("invalid regex {expr}: {e}"))?),
+    "header" => Extractor::Header(expr.trim().to_string()),
+    "cookie" => Extractor::Cookie(expr.trim().to_string()),
+    other => return Err(format!("invalid extractor {other}, must be one of json, regex, header or cookie")),
+  };
+
+  Ok(extractor)
+}
+
+/// Parses the supported subset of JSONPath, a path of keys and indexes (eg: $.data.items[0].id or $['data']['items'][-1])
+fn parse_json_path(s: &str) -> Result<Vec<PathSegment>, String> {
+  let invalid = || format!("invalid JSONPath {s}, must be a path of keys and indexes (eg: $.data.items[0].id)");
+
+  let mut rest = s.trim().strip_prefix('$').ok_or_else(invalid)?;
+  let mut path = Vec::new();
+  while !rest.is_empty() {
+    if let Some(after) = rest.strip_prefix('.') {
+      let end = after.find(['.', '[']).unwrap_or(after.len());
+      if end == 0 {
+        return Err(invalid());
+      }
+      path.push(PathSegment::Key(after[..end].to_string()));
+      rest = &after[end..];
+    } else if let Some(after) = rest.strip_prefix('[') {
+      let end = after.find(']').ok_or_else(invalid)?;
+      let inner = after[..end].trim();
+      let segment = match inner.strip_prefix(['\'', '"']).and_then(|key| key.strip_suffix(['\'', '"'])) {
+        Some(key) => PathSegment::Key(key.to_string()),
+        None => PathSegment::Index(inner.parse::<i64>().map_err(|_| invalid())?),
+      };
+      path.push(segment);
+      rest = &after[end + 1..];
+    } else {
+      return Err(invalid());
+    }
+  }
+
+  Ok(path)
+}
+
+impl Extractor {
+  /// The parts of the response the extractor reads
+  pub fn needs(&self) -> Needs {
+    match self {
+      Extractor::Json(_) | Extractor::Regex(_) => Needs { headers: false, body: true },
+      Extractor::Header(_) | Extractor::Cookie(_) => Needs { headers: true, body: false },
+    }
+  }
+
+  /// The extracted value, `None` when the response doesn't have it
+  pub fn extract(&self, res: &Response) -> Option<Vec<u8>> {
+    match self {
+      Extractor::Json(path) => {
+        let json = serde_json::from_slice::<Value>(&res.body).ok()?;
+        let mut value = &json;
+        for segment in path {
+          value = match (segment, value) {
+            (PathSegment::Key(key), Value::Object(map)) => map.get(key)?,
+            (PathSegment::Index(i), Value::Array(list)) => {
+              let i = if *i < 0 { list.len().checked_sub(i.unsigned_abs() as usize)? } else { *i as usize };
+              list.get(i)?
+            }
+            _ => return None,
+          };
+        }
+
+        match value {
+          Value::Null => None,
+          Value::String(s) => Some(s.as_bytes().to_vec()),
+          other => Some(other.to_string().into_bytes()),
+        }
+      }
+
+      Extractor::Regex(regex) => {
+        let captures = regex.captures(&res.body)?;
+        let value = captures.get(1).or_else(|| captures.get(0))?;
+        Some(value.as_bytes().to_vec())
+      }
+
+      Extractor::Header(name) => res.header(name).next().map(|value| value.trim_ascii().to_vec()),
+
+      Extractor::Cookie(name) => res.header("set-cookie").find_map(|cookie| {
+        let pair = cookie.split(|c| *c == b';').next()?;
+        let eq = pair.iter().position(|c| *c == b'=')?;
+        (pair[..eq].trim_ascii() == name.as_bytes()).then(|| pair[eq + 1..].trim_ascii().to_vec())
+      }),
+    }
+  }
+}
@@ -0,0 +1,408 @@
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+use crate::{
+  args::{Args, RunConfig},
+  output::Output,
+  profile::Profile,
+};
+
+#[cfg(feature = "latency")]
+use crate::fmt::format_duration;
+#[cfg(feature = "latency")]
+use std::time::Duration;
+
+/// The value that is searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Search {
+  /// the number of connections in closed loop mode
+  Connections,
+  /// the request rate in constant-throughput mode, with the connections set by --concurrency
+  Rate,
+}
+
+impl std::fmt::Display for Search {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Search::Connections => write!(f, "connections"),
+      Search::Rate => write!(f, "rate"),
+    }
+  }
+}
+
+/// Searches the highest throughput that meets a latency and error rate SLO, each trial runs for --duration
+#[derive(Debug, Parser)]
+#[command(
+  name = "rload find-max",
+  disable_version_flag = true,
+  disable_help_flag = true,
+  version = crate::build::CLAP_LONG_VERSION,
+  arg_required_else_help = true
+)]
+pub struct FindMaxArgs {
+  #[command(flatten)]
+  pub run: Args,
+
+  /// Maximum 50th percentile latency (corrected for coordinated omission)
+  #[cfg(feature = "latency")]
+  #[arg(long, value_parser = crate::args::parse_duration)]
+  pub p50: Option<Duration>,
+
+  /// Maximum 90th percentile latency (corrected for coordinated omission)
+  #[cfg(feature = "latency")]
+  #[arg(long, value_parser = crate::args::parse_duration)]
+  pub p90: Option<Duration>,
+
+  /// Maximum 99th percentile latency (corrected for coordinated omission)
+  #[cfg(feature = "latency")]
+  #[arg(long, value_parser = crate::args::parse_duration)]
+  pub p99: Option<Duration>,
+
+  /// Maximum ratio of failed requests as a percentage (eg: 0.1%) or a fraction (eg: 0.001)
+  #[arg(long, value_parser = crate::args::parse_ratio)]
+  pub max_error_rate: Option<f64>,
+
+  /// The value to search
+  #[arg(long, value_enum, default_value_t = Search::Connections)]
+  pub search: Search,
+
+  /// The value of the first trial (default: 1 connection or 100 requests/sec),
+  /// the connections of each trial are a multiple of --threads as each thread opens the same number of them
+  #[arg(long)]
+  pub min: Option<f64>,
+
+  /// The maximum value to try (default: 4096 connections or 10000000 requests/sec)
+  #[arg(long)]
+  pub max: Option<f64>,
+
+  /// The search stops when the gap between the highest passing and the lowest failing trial is below this ratio,
+  /// in rate search trials that achieve less than the target rate minus this ratio also fail
+  #[arg(long, value_parser = crate::args::parse_ratio, default_value = "5%")]
+  pub tolerance: f64,
+
+  /// Maximum number of trials to run
+  #[arg(long, default_value_t = 20)]
+  pub max_trials: usize,
+}
+
+/// The result of a single trial of the search
+#[derive(Debug, Clone)]
+pub struct Trial {
+  pub concurrency: usize,
+  /// the target rate in rate search
+  pub rate: Option<f64>,
+  /// fulfilled requests per second
+  pub throughput: f64,
+  pub error_rate: f64,
+  #[cfg(feature = "latency")]
+  pub p50: Option<Duration>,
+  #[cfg(feature = "latency")]
+  pub p99: Option<Duration>,
+  /// the SLO checks the trial failed, empty if it passed
+  pub failed: Vec<String>,
+}
+
+impl Trial {
+  pub fn passed(&self) -> bool {
+    self.failed.is_empty()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct FindMaxReport {
+  pub search: Search,
+  pub trials: Vec<Trial>,
+  /// the index of the passing trial with the highest throughput
+  pub best: Option<usize>,
+}
+
+struct Slo {
+  #[cfg(feature = "latency")]
+  percentiles: Vec<(&'static str, f64, Duration)>,
+  max_error_rate: Option<f64>,
+  tolerance: f64,
+}
+
+pub fn run() -> Result<FindMaxReport, anyhow::Error> {
+  // skip the find-max subcommand
+  let matches = FindMaxArgs::command().get_matches_from(std::env::args_os().enumerate().filter(|(i, _)| *i != 1).map(|(_, arg)| arg));
+  #[allow(unused_mut)]
+  let mut args = FindMaxArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+  #[cfg(feature = "config")]
+  crate::config::apply(&mut args.run, &matches)?;
+
+  run_with_args(args)
+}
+
+pub fn run_with_args(args: FindMaxArgs) -> Result<FindMaxReport, anyhow::Error> {
+  let FindMaxArgs {
+    run,
+    #[cfg(feature = "latency")]
+    p50,
+    #[cfg(feature = "latency")]
+    p90,
+    #[cfg(feature = "latency")]
+    p99,
+    max_error_rate,
+    search,
+    min,
+    max,
+    tolerance,
+    max_trials,
+  } = args;
+
+  if !run.stage.is_empty() {
+    anyhow::bail!("stages cannot be used with find-max, the search sets the load of each trial");
+  }
+
+  if run.rate.is_some() {
+    anyhow::bail!("rate option cannot be used with find-max, use --search rate instead");
+  }
+
+  if run.output != Output::Text || run.output_file.is_some() {
+    anyhow::bail!("output options cannot be used with find-max, the report of the search is printed as text");
+  }
+
+  if !run.threshold.is_empty() {
+    anyhow::bail!("threshold option cannot be used with find-max, the SLO options (eg: --p99 20ms) set the limits of each trial");
+  }
+
+  #[cfg(feature = "latency")]
+  let percentiles = [("p50", 50.0, p50), ("p90", 90.0, p90), ("p99", 99.0, p99)]
+    .into_iter()
+    .filter_map(|(name, p, max)| max.map(|max| (name, p, max)))
+    .collect::<Vec<_>>();
+
+  #[cfg(feature = "latency")]
+  let has_slo = !percentiles.is_empty() || max_error_rate.is_some();
+  #[cfg(not(feature = "latency"))]
+  let has_slo = max_error_rate.is_some();
+
+  if !has_slo {
+    anyhow::bail!("at least one SLO must be set, eg: --p99 20ms or --max-error-rate 0.1%");
+  }
+
+  let (default_min, default_max) = match search {
+    Search::Connections => (1.0, 4096.0),
+    Search::Rate => (100.0, 10_000_000.0),
+  };
+
+  let min = min.unwrap_or(default_min);
+  let max = max.unwrap_or(default_max);
+
+  if !min.is_finite() || min <= 0.0 {
+    anyhow::bail!("min option must be a number greater than 0");
+  }
+
+  if !max.is_finite() || max < min {
+    anyhow::bail!("max option must be a number equal or greater than min");
+  }
+
+  if max_trials == 0 {
+    anyhow::bail!("max-trials option must be greater than 0");
+  }
+
+  #[allow(unused_mut)]
+  let mut config = RunConfig::from_args(run)?;
+
+  #[cfg(feature = "latency")]
+  if !percentiles.is_empty() {
+    config.latency = true;
+  }
+
+  let slo = Slo {
+    #[cfg(feature = "latency")]
+    percentiles,
+    max_error_rate,
+    tolerance,
+  };
+
+  // the value of a trial, the connections are rounded down to a multiple of the threads, with at least one per thread,
+  // so a trial runs the connections it reports
+  let threads = config.threads as f64;
+  let value = |x: f64| match search {
+    Search::Connections => (x / threads).floor().max(1.0) * threads,
+    Search::Rate => x,
+  };
+
+  let mut trials = Vec::<Trial>::new();
+
+  // the highest passing and the lowest failing values, the search grows exponentially until a trial fails
+  // and then bisects between both until the gap is within the tolerance
+  let mut pass: Option<f64> = None;
+  let mut fail: Option<f64> = None;
+  let mut x = value(min);
+
+  while trials.len() < max_trials {
+    let trial = match search {
+      Search::Connections => trial(&slo, RunConfig { concurrency: x as usize, ..config }, None)?,
+      Search::Rate => {
+        let rate: &'static _ = Box::leak(Box::new(Profile::constant(x)));
+        trial(&slo, RunConfig { rate: Some(rate), ..config }, Some(x))?
+      }
+    };
+
+    match trial.passed() {
+      true => pass = Some(x),
+      false => fail = Some(x),
+    }
+
+    trials.push(trial);
+
+    x = match (pass, fail) {
+      (Some(pass), None) => {
+        let next = value((pass * 2.0).min(max));
+        if pass >= max || next <= pass {
+          break;
+        }
+        next
+      }
+
+      (Some(pass), Some(fail)) => {
+        let next = value((pass + fail) / 2.0);
+        if fail - pass <= pass * tolerance || next <= pass || next >= fail {
+          break;
+        }
+        next
+      }
+
+      // the first trial already failed
+      (None, _) => break,
+    };
+  }
+
+  let best = trials
+    .iter()
+    .enumerate()
+    .filter(|(_, trial)| trial.passed())
+    .max_by(|(_, a), (_, b)| a.throughput.total_cmp(&b.throughput))
+    .map(|(i, _)| i);
+
+  Ok(FindMaxReport { search, trials, best })
+}
+
+fn trial(slo: &Slo, config: RunConfig<'static>, rate: Option<f64>) -> Result<Trial, anyhow::Error> {
+  let report = crate::cli::run_with_config(config)?;
+  let secs = report.elapsed.as_secs_f64();
+  let throughput = match secs {
+    0.0 => 0.0,
+    secs => report.ok as f64 / secs,
+  };
+
+  // in rate search the requests that could not be sent at all also count as failed
+  let dropped = report.rate.map(|rate| rate.dropped).unwrap_or(0);
+  let errors = report.errors() + dropped;
+  let error_rate = match report.ok + errors {
+    0 => 0.0,
+    total => errors as f64 / total as f64,
+  };
+
+  let mut failed = Vec::new();
+
+  if report.ok == 0 {
+    failed.push(String::from("no requests fulfilled"));
+  }
+
+  if let Some(max_error_rate) = slo.max_error_rate {
+    if error_rate > max_error_rate {
+      failed.push(format!("errors {}% > {}%", percent(error_rate), percent(max_error_rate)));
+    }
+  }
+
+  if let Some(rate) = rate {
+    if throughput < rate * (1.0 - slo.tolerance) {
+      failed.push(format!("rate {}/s < {}/s", throughput.round(), rate.round()));
+    }
+  }
+
+  #[cfg(feature = "latency")]
+  let (p50, p99) = match &report.hdr_corrected {
+    Some(hdr) if report.ok != 0 => {
+      let p = |p: f64| Duration::from_nanos(hdr.value_at_percentile(p));
+
+      for (name, percentile, max) in &slo.percentiles {
+        let value = p(*percentile);
+        if value > *max {
+          failed.push(format!("{name} {} > {}", format_duration(value), format_duration(*max)));
+        }
+      }
+
+      (Some(p(50.0)), Some(p(99.0)))
+    }
+    _ => (None, None),
+  };
+
+  Ok(Trial {
+    concurrency: report.concurrency,
+    rate,
+    throughput,
+    error_rate,
+    #[cfg(feature = "latency")]
+    p50,
+    #[cfg(feature = "latency")]
+    p99,
+    failed,
+  })
+}
+
+fn percent(ratio: f64) -> f64 {
+  (ratio * 100_000.0).round() / 1000.0
+}
+
+impl std::fmt::Display for FindMaxReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.best.map(|i| &self.trials[i]) {
+      None => writeln!(f, " no trial met the SLO")?,
+      Some(best) => {
+        write!(f, " max throughput: {} requests/sec", best.throughput.round() as u64)?;
+        match best.rate {
+          None => writeln!(f, " with {} connections", best.concurrency)?,
+          Some(rate) => writeln!(f, " at a target rate of {}/s", (rate * 100.0).round() / 100.0)?,
+        }
+      }
+    }
+
+    writeln!(f)?;
+    writeln!(f, "==========| Trials |=========")?;
+
+    #[cfg(feature = "latency")]
+    let has_latency = self.trials.iter().any(|trial| trial.p99.is_some());
+    #[cfg(not(feature = "latency"))]
+    let has_latency = false;
+
+    write!(f, "{: <12}req/s     errors    ", self.search.to_string())?;
+    if has_latency {
+      write!(f, "p50         p99         ")?;
+    }
+    writeln!(f, "result")?;
+
+    for (i, trial) in self.trials.iter().enumerate() {
+      let value = match trial.rate {
+        None => trial.concurrency.to_string(),
+        Some(rate) => format!("{}/s", (rate * 100.0).round() / 100.0),
+      };
+
+      write!(
+        f,
+        "{: <12}{: <10}{: <10}",
+        value,
+        trial.throughput.round() as u64,
+        format!("{}%", percent(trial.error_rate)),
+      )?;
+
+      #[cfg(feature = "latency")]
+      if has_latency {
+        let t = |d: Option<Duration>| d.map(|d| format_duration(d).to_string()).unwrap_or_else(|| String::from("-"));
+        write!(f, "{: <12}{: <12}", t(trial.p50), t(trial.p99))?;
+      }
+
+      match (trial.passed(), Some(i) == self.best) {
+        (true, true) => writeln!(f, "pass (max)")?,
+        (true, false) => writeln!(f, "pass")?,
+        (false, _) => writeln!(f, "fail: {}", trial.failed.join(", "))?,
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -11,6 +11,8 @@ use crate::error::ErrorKind;
 #[cfg(feature = "status-detail")]
 use crate::status::Statuses;
 
+use crate::extract::Response;
+
 /// The maximum total size of a request head allowed by the h1 parser
 const H1_HTTP_MAX_RESPONSE_HEAD_SIZE: usize = 1024 * 128;
 
@@ -43,6 +45,8 @@ pub async fn send_request<S: Read + Write + Unpin>(
   statuses: &mut Statuses,
   #[cfg(not(feature = "status-detail"))]
   not_ok_status: &mut u64,
+  // the response is only buffered here when something reads it, and only its parts in `needs`, otherwise the body is discarded as it's read
+  mut capture: Option<&mut Response>,
   #[cfg(feature = "timeout")]
   timeout: Option<std::time::Duration>,
 ) -> Result<bool, SendError> {
@@ -154,6 +158,18 @@ pub async fn send_request<S: Read + Write + Unpin>(
         }
       }
 
+      if let Some(capture) = capture.as_deref_mut() {
+        capture.status = res.code.unwrap_or_default();
+        if capture.needs.headers {
+          for h in res.headers.iter() {
+            capture.headers.push((h.name.to_ascii_lowercase(), h.value.to_vec()));
+          }
+        }
+      }
+
+      // from here on the response is only captured when its body is needed
+      let mut capture = capture.filter(|capture| capture.needs.body);
+
       let is_keepalive = 'k: {
         if !keepalive || res.version != Some(1) {
           // if disabled keepalive in arguments or server http version is http/1.0 we are not using keepalive
@@ -198,6 +214,21 @@ pub async fn send_request<S: Read + Write + Unpin>(
         Some(content_length) => {
           let to_read = (head_len as u64 + content_length).saturating_sub(buf.len() as u64);
 
+          if let Some(capture) = capture.as_deref_mut() {
+            if content_length > capture.max_body as u64 {
+              return err!(BodyTooLarge);
+            }
+
+            // Safety: head_len could never overflow the buf len
+            let readed = unsafe { buf.get_unchecked(head_len..) };
+            capture.body.extend_from_slice(&readed[..readed.len().min(content_length as usize)]);
+
+            return match read_exact_into(stream, to_read, &mut capture.body).await {
+              Ok(()) => Ok(is_keepalive),
+              Err(_) => err!(ReadBody),
+            };
+          }
+
           if to_read == 0 {
             return Ok(is_keepalive);
           }
@@ -224,8 +255,10 @@ pub async fn send_request<S: Read + Write + Unpin>(
           if is_chunked {
 
             // Safety: head_len could never overflow the buf len
-            match consume_chunked_body(stream, unsafe { buf.get_unchecked(head_len..) }).await {
+            let max_body = capture.as_deref().map_or(0, |capture| capture.max_body);
+            match consume_chunked_body(stream, unsafe { buf.get_unchecked(head_len..) }, capture.map(|capture| &mut capture.body), max_body).await {
               Ok(()) => return Ok(is_keepalive),
+              Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => return err!(BodyTooLarge),
               Err(_) => return err!(ReadBody)
             }
 
@@ -254,8 +287,10 @@ pub async fn send_request<S: Read + Write + Unpin>(
           // no chunked encoding nor content-length, consume the response until the end
           // and dispose the connection, as curl does
           } else {
-            match read_to_end(stream).await {
+            let max_body = capture.as_deref().map_or(0, |capture| capture.max_body);
+            match read_to_end(stream, capture.map(|capture| &mut capture.body), max_body).await {
               Ok(()) => return Ok(false),
+              Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => return err!(BodyTooLarge),
               Err(_) => return err!(ReadBody)
             }
           }
@@ -311,7 +346,18 @@ const SHARED_BUF_LEN: usize = 512 * 1024;
 // and we never create a shared reference for it, only mutable references
 static mut SHARED_BUF: [u8; SHARED_BUF_LEN] = [0; SHARED_BUF_LEN];
 
-pub async fn read_to_end<R: Read + Unpin>(r: &mut R) -> Result<(), std::io::Error> {
+/// Reads until the end of the stream, into `sink` if given or discarding it otherwise,
+/// fails with [`std::io::ErrorKind::FileTooLarge`] when the sink would hold more than `max_body` bytes
+pub async fn read_to_end<R: Read + Unpin>(r: &mut R, sink: Option<&mut Vec<u8>>, max_body: usize) -> Result<(), std::io::Error> {
+  if let Some(sink) = sink {
+    while read_into(r, sink, 64 * 1024).await? != 0 {
+      if sink.len() > max_body {
+        return Err(std::io::ErrorKind::FileTooLarge.into());
+      }
+    }
+    return Ok(());
+  }
+
   loop {
 
     #[cfg(feature = "monoio")]
@@ -385,8 +431,56 @@ async fn read_exact_and_dispose<R: Read + Unpin>(r: &mut R, mut take: u64) -> Re
   Ok(())
 }
 
+/// Appends up to `max` bytes read with a single read to `sink`, returns the number of bytes read
+async fn read_into<R: Read + Unpin>(r: &mut R, sink: &mut Vec<u8>, max: usize) -> Result<usize, std::io::Error> {
+  let len = sink.len();
+  sink.resize(len + max, 0);
+
+  #[cfg(feature = "monoio")]
+  let result = {
+    let (result, slice) = r.read(std::mem::take(sink).slice_mut(len..len + max)).await;
+    *sink = slice.into_inner();
+    result
+  };
+
+  #[cfg(not(feature = "monoio"))]
+  // Safety: the sink was just resized to len + max
+  let result = r.read(unsafe { sink.get_unchecked_mut(len..) }).await;
+
+  match result {
+    Ok(n) => {
+      sink.truncate(len + n);
+      Ok(n)
+    }
+    Err(e) => {
+      sink.truncate(len);
+      Err(e)
+    }
+  }
+}
+
+/// Reads exactly `take` bytes into `sink`
+async fn read_exact_into<R: Read + Unpin>(r: &mut R, mut take: u64, sink: &mut Vec<u8>) -> Result<(), std::io::Error> {
+  while take != 0 {
+    let n = read_into(r, sink, take.min(SHARED_BUF_LEN as u64) as usize).await?;
+    if n == 0 {
+      return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    take -= n as u64;
+  }
+
+  Ok(())
+}
+
+/// Reads a chunked body, the data of the chunks is appended to `sink` if given or discarded otherwise,
+/// fails with [`std::io::ErrorKind::FileTooLarge`] when the sink would hold more than `max_body` bytes
 #[inline(always)]
-pub async fn consume_chunked_body<R: Read + Unpin>(stream: &mut R, readed: &[u8]) -> Result<(), std::io::Error> {
+pub async fn consume_chunked_body<R: Read + Unpin>(
+  stream: &mut R,
+  readed: &[u8],
+  mut sink: Option<&mut Vec<u8>>,
+  max_body: usize,
+) -> Result<(), std::io::Error> {
   
   // Safety: we only read initialized part of the buf between start..end
   let mut buf: [u8; H1_CHUNKED_BODY_BUF_SIZE] = unsafe { std::mem::transmute([MaybeUninit::<u8>::uninit(); H1_CHUNKED_BODY_BUF_SIZE]) };
@@ -500,10 +594,26 @@ pub async fn consume_chunked_body<R: Read + Unpin>(stream: &mut R, readed: &[u8]
       }
 
       // not last chunk
-      let mut until = consumed as u64 + size + 2; // +2 is for the \r\n at the end of the chunk
+      let total = consumed as u64 + size + 2; // +2 is for the \r\n at the end of the chunk
+      let mut until = total;
 
       'read_chunk: loop { 
 
+        // the data of the chunk is between its head and the \r\n at its end, this copies the part of it that is in the buf
+        if let Some(sink) = sink.as_deref_mut() {
+          let offset = total - until;
+          let from = (consumed as u64).max(offset);
+          let to = (consumed as u64 + size).min(offset + (len!() as u64).min(until));
+          if from < to {
+            if sink.len() + (to - from) as usize > max_body {
+              return Err(std::io::ErrorKind::FileTooLarge.into());
+            }
+
+            let at = start + (from - offset) as usize;
+            sink.extend_from_slice(&buf[at..at + (to - from) as usize]);
+          }
+        }
+
         if len!() as u64 == until {
           start = 0;
           end = 0;
@@ -540,4 +650,235 @@ pub async fn consume_chunked_body<R: Read + Unpin>(stream: &mut R, readed: &[u8]
       }
     }
   }
-}
\ No newline at end of file
+}
+#[cfg(all(test, not(feature = "monoio")))]
+mod tests {
+  use super::*;
+  use crate::extract::Needs;
+  use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  /// A stream that returns its parts one read at a time, a read into a smaller buffer leaves the rest of the part for the next one
+  struct Parts(VecDeque<Vec<u8>>);
+
+  impl Parts {
+    fn new<T: AsRef<[u8]>>(parts: &[T]) -> Self {
+      Parts(parts.iter().map(|part| part.as_ref().to_vec()).collect())
+    }
+
+    /// The data split in reads of `step` bytes
+    fn trickle(data: &[u8], step: usize) -> Self {
+      Parts(data.chunks(step).map(<[u8]>::to_vec).collect())
+    }
+  }
+
+  impl tokio::io::AsyncRead for Parts {
+    fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+      if let Some(part) = self.0.front_mut() {
+        let n = part.len().min(buf.remaining());
+        buf.put_slice(&part[..n]);
+        part.drain(..n);
+        if part.is_empty() {
+          self.0.pop_front();
+        }
+      }
+      Poll::Ready(Ok(()))
+    }
+  }
+
+  impl tokio::io::AsyncWrite for Parts {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+      Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      Poll::Ready(Ok(()))
+    }
+  }
+
+  /// Runs a test on a thread with a stack large enough for the buffers of the requests, like the threads of a run
+  fn run<F: std::future::Future<Output = ()>>(test: impl FnOnce() -> F + Send + 'static) {
+    std::thread::Builder::new()
+      .stack_size(64 * 1024 * 1024)
+      .spawn(|| tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(test()))
+      .unwrap()
+      .join()
+      .unwrap();
+  }
+
+  /// The chunked encoding of the payload, in chunks of the given sizes
+  fn chunked(payload: &[u8], sizes: &[usize]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut rest = payload;
+    for size in sizes.iter().cycle() {
+      if rest.is_empty() {
+        break;
+      }
+      let (chunk, next) = rest.split_at((*size).min(rest.len()));
+      encoded.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+      encoded.extend_from_slice(chunk);
+      encoded.extend_from_slice(b"\r\n");
+      rest = next;
+    }
+    encoded.extend_from_slice(b"0\r\n\r\n");
+    encoded
+  }
+
+  fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| b'a' + (i % 26) as u8).collect()
+  }
+
+  async fn dechunk(readed: &[u8], stream: &mut Parts) -> Result<Vec<u8>, std::io::Error> {
+    let mut sink = Vec::new();
+    consume_chunked_body(stream, readed, Some(&mut sink), usize::MAX).await?;
+    Ok(sink)
+  }
+
+  #[test]
+  fn captures_chunked_bodies_split_in_small_reads() {
+    run(|| async {
+      let payload = payload(1000);
+      let body = chunked(&payload, &[1, 26, 300, 7]);
+
+      // the chunk heads and the line breaks end up split across the reads
+      for step in [1, 2, 3, 5, 64, 1000] {
+        for readed in [0, 1, 4, 40] {
+          let mut stream = Parts::trickle(&body[readed..], step);
+          assert_eq!(dechunk(&body[..readed], &mut stream).await.unwrap(), payload, "step {step} readed {readed}");
+          assert!(stream.0.is_empty());
+        }
+      }
+    });
+  }
+
+  #[test]
+  fn captures_chunks_larger_than_the_buffer() {
+    run(|| async {
+      let payload = payload(H1_CHUNKED_BODY_BUF_SIZE * 2 + 123);
+      let body = chunked(&payload, &[H1_CHUNKED_BODY_BUF_SIZE + 1000, 10]);
+
+      for step in [4096, 100_000, body.len()] {
+        let mut stream = Parts::trickle(&body, step);
+        assert_eq!(dechunk(&[], &mut stream).await.unwrap(), payload, "step {step}");
+      }
+    });
+  }
+
+  #[test]
+  fn limits_captured_chunked_bodies() {
+    run(|| async {
+      let body = chunked(&payload(100), &[30]);
+
+      let mut sink = Vec::new();
+      let err = consume_chunked_body(&mut Parts::trickle(&body, 7), &[], Some(&mut sink), 99).await.unwrap_err();
+      assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+
+      let mut sink = Vec::new();
+      consume_chunked_body(&mut Parts::trickle(&body, 7), &[], Some(&mut sink), 100).await.unwrap();
+      assert_eq!(sink.len(), 100);
+
+      // without a sink the body is only consumed
+      let mut stream = Parts::trickle(&body, 7);
+      consume_chunked_body(&mut stream, &[], None, 0).await.unwrap();
+      assert!(stream.0.is_empty());
+    });
+  }
+
+  #[test]
+  fn captures_bodies_read_to_the_end() {
+    run(|| async {
+      let payload = payload(200_000);
+      let mut sink = Vec::new();
+      read_to_end(&mut Parts::trickle(&payload, 999), Some(&mut sink), usize::MAX).await.unwrap();
+      assert_eq!(sink, payload);
+
+      let mut sink = Vec::new();
+      let err = read_to_end(&mut Parts::trickle(&payload, 999), Some(&mut sink), 100_000).await.unwrap_err();
+      assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    });
+  }
+
+  /// Sends a request to a stream that responds with the given reads, returns the captured response
+  async fn respond<T: AsRef<[u8]>>(reads: &[T]) -> (Result<bool, SendError>, Response) {
+    let mut stream = Parts::new(reads);
+    let mut response = Response {
+      needs: Needs::ALL,
+      max_body: 1024 * 1024,
+      ..Response::default()
+    };
+
+    #[cfg(feature = "status-detail")]
+    let mut statuses = Statuses::new();
+    #[cfg(not(feature = "status-detail"))]
+    let mut not_ok_status = 0;
+
+    let result = send_request(
+      &mut stream,
+      b"GET / HTTP/1.1\r\n\r\n",
+      true,
+      #[cfg(feature = "status-detail")]
+      &mut statuses,
+      #[cfg(not(feature = "status-detail"))]
+      &mut not_ok_status,
+      Some(&mut response),
+      #[cfg(feature = "timeout")]
+      None,
+    )
+    .await;
+
+    assert!(stream.0.is_empty());
+    (result, response)
+  }
+
+  #[test]
+  fn captures_content_length_bodies() {
+    run(|| async {
+      let head = "HTTP/1.1 201 Created\r\ncontent-length: 10\r\nx-id: 7\r\n\r\n";
+
+      // the head and a part of the body in the same read
+      let (result, response) = respond(&[format!("{head}0123"), String::from("45"), String::from("6789")]).await;
+      assert!(result.is_ok_and(|keepalive| keepalive));
+      assert_eq!(response.status, 201);
+      assert_eq!(response.headers, vec![(String::from("content-length"), b"10".to_vec()), (String::from("x-id"), b"7".to_vec())]);
+      assert_eq!(response.body, b"0123456789");
+
+      // the whole response in one read, and the head split in two
+      let (_, response) = respond(&[format!("{head}0123456789")]).await;
+      assert_eq!(response.body, b"0123456789");
+      let (_, response) = respond(&[&head[..20], &head[20..], "0123456789"]).await;
+      assert_eq!(response.body, b"0123456789");
+    });
+  }
+
+  #[test]
+  fn captures_chunked_bodies_after_the_head() {
+    run(|| async {
+      let payload = payload(500);
+      let body = chunked(&payload, &[33]);
+      let mut response = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n".to_vec();
+      response.extend_from_slice(&body[..50]);
+
+      let (result, captured) = respond(&[&response[..], &body[50..]]).await;
+      assert!(result.is_ok());
+      assert_eq!(captured.body, payload);
+    });
+  }
+
+  #[test]
+  fn fails_bodies_over_the_limit() {
+    run(|| async {
+      let (result, _) = respond(&["HTTP/1.1 200 OK\r\ncontent-length: 2000000\r\n\r\n"]).await;
+      #[cfg(feature = "error-detail")]
+      assert!(matches!(result, Err(ErrorKind::BodyTooLarge)));
+      #[cfg(not(feature = "error-detail"))]
+      assert!(result.is_err());
+    });
+  }
+}
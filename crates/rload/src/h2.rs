@@ -6,6 +6,8 @@ use crate::error::ErrorKind;
 #[cfg(feature = "status-detail")]
 use crate::status::Statuses;
 
+use crate::extract::Response;
+
 #[cfg(feature = "error-detail")]
 type SendError = ErrorKind;
 
@@ -23,6 +25,9 @@ pub async fn send_request(
   
   #[cfg(not(feature = "status-detail"))]
   not_ok_status: &mut u64,
+
+  // the response is only buffered here when something reads it, and only its parts in `needs`, otherwise the body is discarded as it's read
+  mut capture: Option<&mut Response>,
   
   #[cfg(feature = "timeout")]
   timeout: Option<std::time::Duration>,
@@ -87,12 +92,31 @@ pub async fn send_request(
       }
     }
 
+    if let Some(capture) = capture.as_deref_mut() {
+      capture.status = res.status().as_u16();
+      if capture.needs.headers {
+        for (name, value) in res.headers() {
+          capture.headers.push((name.as_str().to_string(), value.as_bytes().to_vec()));
+        }
+      }
+    }
+
+    // from here on the response is only captured when its body is needed
+    let mut capture = capture.filter(|capture| capture.needs.body);
+
     let mut body = res.into_body();
 
     while let Some(chunk) = body.data().await {
       match chunk {
         Ok(chunk) => {
           let _ = body.flow_control().release_capacity(chunk.len());
+          if let Some(capture) = capture.as_deref_mut() {
+            if capture.body.len() + chunk.len() > capture.max_body {
+              return err!(BodyTooLarge);
+            }
+
+            capture.body.extend_from_slice(&chunk);
+          }
         }
 
         Err(_) => return err!(H2Body),
@@ -0,0 +1,220 @@
+use anyhow::Context;
+use base64::Engine;
+use std::{collections::HashMap, path::Path};
+use url::Url;
+
+use crate::replay::ReplayEntry;
+
+/// The headers that only apply to a single connection or that are set by rload itself, they are not sent
+const SKIPPED_HEADERS: &[&str] = &[
+  "connection",
+  "keep-alive",
+  "proxy-connection",
+  "proxy-authenticate",
+  "proxy-authorization",
+  "te",
+  "trailer",
+  "transfer-encoding",
+  "upgrade",
+  "host",
+  "content-length",
+];
+
+/// How the requests of a HAR file are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HarMode {
+  /// each connection sends the requests in the order of the file, like a user session, starting over after the last one
+  Session,
+  /// a weighted mix of the requests, weighted by the number of times each one appears in the file
+  Mix,
+}
+
+impl std::fmt::Display for HarMode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      HarMode::Session => write!(f, "session"),
+      HarMode::Mix => write!(f, "mix"),
+    }
+  }
+}
+
+/// The counts of entries of a HAR file by what happened to them
+#[derive(Debug, Clone, Default)]
+pub struct HarSummary {
+  pub path: String,
+  pub entries: u64,
+  pub imported: u64,
+  /// the entries skipped because they were sent to other hosts than the one of the url, by host
+  pub other_hosts: Vec<(String, u64)>,
+}
+
+impl std::fmt::Display for HarSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} of {} requests imported", self.imported, self.entries)?;
+
+    if !self.other_hosts.is_empty() {
+      let hosts = self
+        .other_hosts
+        .iter()
+        .map(|(host, count)| format!("{host} ({count})"))
+        .collect::<Vec<_>>();
+      write!(f, ", skipped for other hosts: {}", hosts.join(", "))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Loads the requests of a HAR file to the origin of `url`, in the order of the file
+///
+/// The requests to other hosts are skipped and counted in the summary, the hop-by-hop headers are filtered out
+pub fn load(path: &Path, url: &Url) -> Result<(Vec<ReplayEntry>, HarSummary), anyhow::Error> {
+  let content = std::fs::read(path).with_context(|| format!("error reading HAR file {}", path.display()))?;
+  let json: serde_json::Value =
+    serde_json::from_slice(&content).with_context(|| format!("error parsing HAR file {}", path.display()))?;
+
+  let entries = json
+    .pointer("/log/entries")
+    .and_then(|entries| entries.as_array())
+    .with_context(|| format!("invalid HAR file {}, missing log.entries", path.display()))?;
+
+  let mut summary = HarSummary {
+    path: path.display().to_string(),
+    entries: entries.len() as u64,
+    ..HarSummary::default()
+  };
+
+  let mut other_hosts = HashMap::<String, u64>::new();
+  let mut requests = Vec::with_capacity(entries.len());
+  for (i, entry) in entries.iter().enumerate() {
+    let request = entry
+      .get("request")
+      .with_context(|| format!("error parsing HAR file {} entry {}, missing request", path.display(), i + 1))?;
+
+    let target = request
+      .get("url")
+      .and_then(|url| url.as_str())
+      .and_then(|url| Url::parse(url).ok())
+      .with_context(|| format!("error parsing HAR file {} entry {}, missing or invalid request.url", path.display(), i + 1))?;
+
+    if target.origin() != url.origin() {
+      let host = match (target.host_str(), target.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => target.scheme().to_string(),
+      };
+      *other_hosts.entry(host).or_default() += 1;
+      continue;
+    }
+
+    let entry = parse_request(request, &target).with_context(|| format!("error parsing HAR file {} entry {}", path.display(), i + 1))?;
+    requests.push(entry);
+  }
+
+  summary.imported = requests.len() as u64;
+  summary.other_hosts = other_hosts.into_iter().collect();
+  summary.other_hosts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+  if requests.is_empty() {
+    anyhow::bail!("HAR file {} has no requests to {} to import, {}", path.display(), url.origin().ascii_serialization(), summary);
+  }
+
+  Ok((requests, summary))
+}
+
+fn parse_request(request: &serde_json::Value, target: &Url) -> Result<ReplayEntry, anyhow::Error> {
+  let method = request
+    .get("method")
+    .and_then(|method| method.as_str())
+    .unwrap_or("GET")
+    .to_string();
+
+  let target = match target.query() {
+    Some(query) => format!("{}?{query}", target.path()),
+    None => target.path().to_string(),
+  };
+
+  let mut headers = Vec::new();
+  if let Some(list) = request.get("headers").and_then(|headers| headers.as_array()) {
+    for header in list {
+      let (Some(name), Some(value)) = (
+        header.get("name").and_then(|name| name.as_str()),
+        header.get("value").and_then(|value| value.as_str()),
+      ) else {
+        anyhow::bail!("request.headers must be a list of objects with a name and a value");
+      };
+
+      // the http/2 captures of the browsers have the pseudo-headers like :authority in the list
+      if name.starts_with(':') || SKIPPED_HEADERS.iter().any(|skipped| name.eq_ignore_ascii_case(skipped)) {
+        continue;
+      }
+
+      headers.push(format!("{name}:{value}"));
+    }
+  }
+
+  let body = match request.get("postData") {
+    None | Some(serde_json::Value::Null) => None,
+    Some(post_data) => {
+      // the content type is also in the post data, in case it's missing in the headers
+      let has_content_type = headers.iter().any(|header| header.to_lowercase().starts_with("content-type:"));
+      if let (false, Some(mime_type)) = (has_content_type, post_data.get("mimeType").and_then(|mime| mime.as_str())) {
+        if !mime_type.is_empty() {
+          headers.push(format!("content-type:{mime_type}"));
+        }
+      }
+
+      Some(parse_post_data(post_data)?)
+    }
+  };
+
+  Ok(ReplayEntry {
+    method,
+    target,
+    headers,
+    body,
+    timestamp: None,
+  })
+}
+
+/// The body of a request, the text of the post data or, for the captures of forms without it, its encoded params
+fn parse_post_data(post_data: &serde_json::Value) -> Result<Vec<u8>, anyhow::Error> {
+  if let Some(text) = post_data.get("text").and_then(|text| text.as_str()) {
+    // some captures encode the binary bodies as base64, like the responses
+    return match post_data.get("encoding").and_then(|encoding| encoding.as_str()) {
+      Some("base64") => base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .context("invalid base64 in request.postData.text"),
+      _ => Ok(text.as_bytes().to_vec()),
+    };
+  }
+
+  let mut form = url::form_urlencoded::Serializer::new(String::new());
+  for param in post_data.get("params").and_then(|params| params.as_array()).into_iter().flatten() {
+    let name = param.get("name").and_then(|name| name.as_str()).unwrap_or_default();
+    let value = param.get("value").and_then(|value| value.as_str()).unwrap_or_default();
+    form.append_pair(name, value);
+  }
+
+  Ok(form.finish().into_bytes())
+}
+
+/// The requests as a weighted mix, the equal requests are merged and weighted by their number of appearances
+pub fn mix(entries: Vec<ReplayEntry>) -> (Vec<ReplayEntry>, Vec<u32>) {
+  let mut indexes = HashMap::<(String, String, Vec<String>, Option<Vec<u8>>), usize>::new();
+  let mut unique = Vec::<ReplayEntry>::new();
+  let mut weights = Vec::<u32>::new();
+  for entry in entries {
+    let key = (entry.method.clone(), entry.target.clone(), entry.headers.clone(), entry.body.clone());
+    match indexes.get(&key) {
+      Some(i) => weights[*i] += 1,
+      None => {
+        indexes.insert(key, unique.len());
+        unique.push(entry);
+        weights.push(1);
+      }
+    }
+  }
+
+  (unique, weights)
+}
@@ -6,12 +6,37 @@ cfg_if::cfg_if! {
 
 pub mod cli;
 pub mod args;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod io;
 pub mod fmt;
 pub mod error;
 pub mod status;
 pub mod run;
+pub mod schedule;
+pub mod distribution;
+pub mod profile;
+pub mod stage;
+pub mod endpoint;
+pub mod template;
+pub mod data;
+pub mod extract;
+pub mod check;
+pub mod scenario;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+pub mod replay;
+pub mod access_log;
+pub mod har;
+pub mod curl;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod find_max;
 pub mod report;
+pub mod threshold;
+pub mod output;
 pub mod http;
 pub mod rt;
 #[cfg(feature = "h1")]
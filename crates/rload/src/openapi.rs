@@ -0,0 +1,502 @@
+use anyhow::Context;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::endpoint::{Body, EndpointSpec};
+
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// The depth at which the synthesized bodies stop following nested and recursive schemas
+const MAX_DEPTH: usize = 8;
+
+/// An operation selected with --operation, `*` selects all the operations not given by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationSpec {
+  pub id: String,
+  pub weight: u32,
+}
+
+/// Parses an operation in the format of `operationId[:weight]` (eg: listItems:5)
+pub fn parse_operation(s: &str) -> Result<OperationSpec, String> {
+  let (id, weight) = match s.rsplit_once(':') {
+    Some((id, weight)) => (
+      id,
+      weight
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("invalid operation weight in {s}, must be an integer"))?,
+    ),
+    None => (s, 1),
+  };
+
+  let id = id.trim();
+  if id.is_empty() {
+    return Err(format!("invalid operation {s}, must be in the format of operationId[:weight] (eg: listItems:5)"));
+  }
+
+  Ok(OperationSpec {
+    id: id.to_string(),
+    weight,
+  })
+}
+
+/// The operations of an OpenAPI spec by what happened to them
+#[derive(Debug, Clone, Default)]
+pub struct OpenApiSummary {
+  pub path: String,
+  /// the title and version of the api
+  pub title: String,
+  pub operations: u64,
+  pub selected: u64,
+  /// the operations that can't be requested, with the reason
+  pub skipped: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for OpenApiSummary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}, {} of {} operations", self.title, self.selected, self.operations)?;
+
+    if !self.skipped.is_empty() {
+      let skipped = self
+        .skipped
+        .iter()
+        .map(|(id, reason)| format!("{id} ({reason})"))
+        .collect::<Vec<_>>();
+      write!(f, ", skipped: {}", skipped.join(", "))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Generates an endpoint per operation of an OpenAPI 3 spec file, in JSON or YAML, each one named by its operationId
+///
+/// The paths of the spec are relative to the path of the url. The path and query parameters take the value of their examples,
+/// and without one a value driven by their schema, with template variables for the integers and uuids so they change
+/// with each request (eg: {{rand_int 1 1000}}). The JSON bodies are taken from the examples or synthesized from the schemas the same way
+pub fn load(path: &Path, base: &str, operations: &[OperationSpec]) -> Result<(Vec<EndpointSpec>, OpenApiSummary), anyhow::Error> {
+  let content = std::fs::read_to_string(path).with_context(|| format!("error reading OpenAPI spec {}", path.display()))?;
+
+  let spec: Value = match path.extension().and_then(|ext| ext.to_str()) {
+    Some("yaml" | "yml") => serde_yaml::from_str(&content).with_context(|| format!("error parsing OpenAPI spec {}", path.display()))?,
+    _ => serde_json::from_str(&content).with_context(|| format!("error parsing OpenAPI spec {}", path.display()))?,
+  };
+
+  match spec.get("openapi").and_then(|version| version.as_str()) {
+    Some(version) if version.starts_with("3.") => {}
+    _ => anyhow::bail!("invalid OpenAPI spec {}, only OpenAPI 3 specs are supported", path.display()),
+  }
+
+  let paths = spec
+    .get("paths")
+    .and_then(|paths| paths.as_object())
+    .with_context(|| format!("invalid OpenAPI spec {}, missing paths", path.display()))?;
+
+  let mut summary = OpenApiSummary {
+    path: path.display().to_string(),
+    title: format!(
+      "{} {}",
+      spec.pointer("/info/title").and_then(|title| title.as_str()).unwrap_or("untitled"),
+      spec.pointer("/info/version").and_then(|version| version.as_str()).unwrap_or_default(),
+    )
+    .trim()
+    .to_string(),
+    ..OpenApiSummary::default()
+  };
+
+  let resolver = Resolver { spec: &spec };
+
+  let mut available = Vec::<(String, Result<EndpointSpec, String>)>::new();
+  for (template, item) in paths {
+    let item = resolver.resolve(item);
+    for method in METHODS {
+      let Some(operation) = item.get(*method) else {
+        continue;
+      };
+
+      let id = operation
+        .get("operationId")
+        .and_then(|id| id.as_str())
+        .map(String::from)
+        .unwrap_or_else(|| format!("{} {template}", method.to_uppercase()));
+
+      let endpoint = resolver.endpoint(base, template, method, item, operation, &id);
+      available.push((id, endpoint));
+    }
+  }
+
+  summary.operations = available.len() as u64;
+
+  for operation in operations {
+    if operation.id != "*" && !available.iter().any(|(id, _)| *id == operation.id) {
+      let ids = available.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+      anyhow::bail!("unknown operation {} in OpenAPI spec {}, must be one of {}", operation.id, path.display(), ids.join(", "));
+    }
+  }
+
+  // without operations all of them are requested with the same weight
+  let others = match operations.is_empty() {
+    true => Some(1),
+    false => operations.iter().find(|operation| operation.id == "*").map(|operation| operation.weight),
+  };
+
+  let mut endpoints = Vec::new();
+  for (id, endpoint) in available {
+    let weight = match operations.iter().find(|operation| operation.id == id) {
+      Some(operation) => operation.weight,
+      None => match others {
+        Some(weight) => weight,
+        None => continue,
+      },
+    };
+
+    match endpoint {
+      Ok(endpoint) => endpoints.push(EndpointSpec { weight, ..endpoint }),
+      // the operations selected by name must be requested
+      Err(reason) if operations.iter().any(|operation| operation.id == id) => {
+        anyhow::bail!("operation {id} of OpenAPI spec {} can't be requested, {reason}", path.display())
+      }
+      Err(reason) => summary.skipped.push((id, reason)),
+    }
+  }
+
+  summary.selected = endpoints.len() as u64;
+
+  if endpoints.is_empty() {
+    anyhow::bail!("OpenAPI spec {} has no operations to request, {}", path.display(), summary);
+  }
+
+  Ok((endpoints, summary))
+}
+
+struct Resolver<'a> {
+  spec: &'a Value,
+}
+
+impl<'a> Resolver<'a> {
+  /// Follows the local references (eg: {"$ref": "#/components/schemas/Item"}), the others are left as they are
+  fn resolve(&self, mut value: &'a Value) -> &'a Value {
+    // a limit in case of references to themselves
+    for _ in 0..32 {
+      let Some(pointer) = value.get("$ref").and_then(|reference| reference.as_str()).and_then(|r| r.strip_prefix('#')) else {
+        break;
+      };
+
+      match self.spec.pointer(pointer) {
+        Some(target) => value = target,
+        None => break,
+      }
+    }
+
+    value
+  }
+
+  fn endpoint(&self, base: &str, template: &str, method: &str, item: &'a Value, operation: &'a Value, id: &str) -> Result<EndpointSpec, String> {
+    // the parameters of the path apply to all of its operations, unless an operation overrides them
+    let mut parameters = Vec::<&Value>::new();
+    for list in [item.get("parameters"), operation.get("parameters")].into_iter().flatten() {
+      for parameter in list.as_array().into_iter().flatten() {
+        let parameter = self.resolve(parameter);
+        let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+        parameters.retain(|other| key(other) != key(parameter));
+        parameters.push(parameter);
+      }
+    }
+
+    let mut target = template.to_string();
+    let mut query = Vec::new();
+    let mut headers = Vec::new();
+    for parameter in parameters {
+      let name = parameter.get("name").and_then(|name| name.as_str()).unwrap_or_default();
+      let required = parameter.get("required").and_then(|required| required.as_bool()).unwrap_or(false);
+      let example = self.parameter_example(parameter);
+
+      match parameter.get("in").and_then(|location| location.as_str()) {
+        Some("path") => {
+          let value = match example {
+            Some(example) => encode(&scalar(&example)),
+            None => self.scalar_value(parameter.get("schema"), name),
+          };
+          target = target.replace(&format!("{{{name}}}"), &value);
+        }
+
+        // the optional query parameters are only sent when they have an example
+        Some("query") if required || example.is_some() => {
+          let value = match example {
+            Some(example) => encode(&scalar(&example)),
+            None => self.scalar_value(parameter.get("schema"), name),
+          };
+          query.push(format!("{}={value}", encode(name)));
+        }
+
+        Some("header") if required => {
+          let value = match example {
+            Some(example) => scalar(&example),
+            None => self.scalar_value(parameter.get("schema"), name),
+          };
+          headers.push(format!("{name}: {value}"));
+        }
+
+        Some("cookie") if required => return Err(format!("unsupported required cookie parameter {name}")),
+
+        _ => {}
+      }
+    }
+
+    let body = match operation.get("requestBody").map(|body| self.resolve(body)) {
+      None => None,
+      Some(request_body) => {
+        let required = request_body.get("required").and_then(|required| required.as_bool()).unwrap_or(false);
+        let content = request_body.get("content").and_then(|content| content.as_object());
+
+        let json = content.and_then(|content| {
+          content
+            .iter()
+            .find(|(mime, _)| *mime == "application/json" || mime.ends_with("+json"))
+        });
+        let form = content.and_then(|content| content.get_key_value("application/x-www-form-urlencoded"));
+
+        match (json, form) {
+          (Some((mime, media)), _) => {
+            let body = match self.media_example(media) {
+              Some(example) => example.to_string(),
+              None => self.json_value(media.get("schema"), "value", 0),
+            };
+            headers.push(format!("content-type: {mime}"));
+            Some(body)
+          }
+
+          (None, Some((mime, media))) => {
+            let schema = media.get("schema").map(|schema| self.resolve(schema));
+            let properties = schema.and_then(|schema| schema.get("properties")).and_then(|p| p.as_object());
+            let body = properties
+              .into_iter()
+              .flatten()
+              .map(|(name, schema)| format!("{}={}", encode(name), self.scalar_value(Some(schema), name)))
+              .collect::<Vec<_>>()
+              .join("&");
+            headers.push(format!("content-type: {mime}"));
+            Some(body)
+          }
+
+          (None, None) if required => {
+            let types = content.map(|content| content.keys().cloned().collect::<Vec<_>>().join(", ")).unwrap_or_default();
+            return Err(format!("unsupported request body content type {types}"));
+          }
+
+          (None, None) => None,
+        }
+      }
+    };
+
+    let mut target = format!("{}{target}", base.trim_end_matches('/'));
+    if !query.is_empty() {
+      target = format!("{target}?{}", query.join("&"));
+    }
+
+    Ok(EndpointSpec {
+      weight: 1,
+      name: Some(id.to_string()),
+      method: method.to_uppercase(),
+      target,
+      body: body.map(|body| Body::Bytes(body.into_bytes())),
+      headers,
+      template: false,
+    })
+  }
+
+  fn parameter_example(&self, parameter: &'a Value) -> Option<Value> {
+    if let Some(example) = parameter.get("example") {
+      return Some(example.clone());
+    }
+
+    if let Some(example) = first_example(parameter.get("examples").map(|examples| self.resolve(examples)), self) {
+      return Some(example);
+    }
+
+    let schema = parameter.get("schema").map(|schema| self.resolve(schema))?;
+    schema_example(schema)
+  }
+
+  fn media_example(&self, media: &'a Value) -> Option<Value> {
+    if let Some(example) = media.get("example") {
+      return Some(example.clone());
+    }
+
+    first_example(media.get("examples").map(|examples| self.resolve(examples)), self)
+  }
+
+  /// A value for a parameter or a form field, rendered as it goes in a url
+  fn scalar_value(&self, schema: Option<&'a Value>, name: &str) -> String {
+    let Some(schema) = schema.map(|schema| self.resolve(schema)) else {
+      return format!("{}-{{{{seq}}}}", encode(name));
+    };
+
+    if let Some(example) = schema_example(schema) {
+      return encode(&scalar(&example));
+    }
+
+    match schema_type(schema) {
+      Some("integer" | "number") => rand_int(schema),
+      Some("boolean") => String::from("true"),
+      Some("array") => self.scalar_value(schema.get("items"), name),
+      _ => string_value(schema, name, true),
+    }
+  }
+
+  /// A JSON value synthesized from a schema
+  fn json_value(&self, schema: Option<&'a Value>, name: &str, depth: usize) -> String {
+    let Some(schema) = schema.map(|schema| self.resolve(schema)) else {
+      return String::from("{}");
+    };
+
+    if let Some(example) = schema_example(schema) {
+      return example.to_string();
+    }
+
+    if depth >= MAX_DEPTH {
+      return String::from("null");
+    }
+
+    // the first of the alternatives is used and all the parts are merged
+    for key in ["oneOf", "anyOf"] {
+      if let Some(first) = schema.get(key).and_then(|list| list.as_array()).and_then(|list| list.first()) {
+        return self.json_value(Some(first), name, depth + 1);
+      }
+    }
+
+    if let Some(parts) = schema.get("allOf").and_then(|list| list.as_array()) {
+      let mut properties = Vec::new();
+      for part in parts {
+        self.collect_properties(self.resolve(part), &mut properties, depth);
+      }
+      return self.json_object(properties, depth);
+    }
+
+    match schema_type(schema) {
+      Some("object") => {
+        let mut properties = Vec::new();
+        self.collect_properties(schema, &mut properties, depth);
+        self.json_object(properties, depth)
+      }
+      Some("array") => format!("[{}]", self.json_value(schema.get("items"), name, depth + 1)),
+      Some("integer" | "number") => rand_int(schema),
+      Some("boolean") => String::from("true"),
+      Some("null") => String::from("null"),
+      _ => Value::String(string_value(schema, name, false)).to_string(),
+    }
+  }
+
+  fn collect_properties(&self, schema: &'a Value, properties: &mut Vec<(&'a String, &'a Value)>, depth: usize) {
+    if depth >= MAX_DEPTH {
+      return;
+    }
+
+    if let Some(parts) = schema.get("allOf").and_then(|list| list.as_array()) {
+      for part in parts {
+        self.collect_properties(self.resolve(part), properties, depth + 1);
+      }
+    }
+
+    for (name, property) in schema.get("properties").and_then(|p| p.as_object()).into_iter().flatten() {
+      properties.retain(|(other, _)| *other != name);
+      properties.push((name, property));
+    }
+  }
+
+  fn json_object(&self, properties: Vec<(&'a String, &'a Value)>, depth: usize) -> String {
+    let fields = properties
+      .into_iter()
+      .map(|(name, schema)| format!("{}:{}", Value::String(name.clone()), self.json_value(Some(schema), name, depth + 1)))
+      .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(","))
+  }
+}
+
+fn first_example<'a>(examples: Option<&'a Value>, resolver: &Resolver<'a>) -> Option<Value> {
+  let (_, example) = examples?.as_object()?.iter().next()?;
+  resolver.resolve(example).get("value").cloned()
+}
+
+fn schema_example(schema: &Value) -> Option<Value> {
+  if let Some(example) = schema.get("example").or_else(|| schema.get("default")) {
+    return Some(example.clone());
+  }
+
+  if let Some(Value::Array(examples)) = schema.get("examples") {
+    if let Some(example) = examples.first() {
+      return Some(example.clone());
+    }
+  }
+
+  schema.get("enum").and_then(|values| values.as_array()).and_then(|values| values.first()).cloned()
+}
+
+/// The type of a schema, in OpenAPI 3.1 it can be a list with null
+fn schema_type(schema: &Value) -> Option<&str> {
+  match schema.get("type") {
+    Some(Value::String(kind)) => Some(kind),
+    Some(Value::Array(kinds)) => kinds.iter().filter_map(|kind| kind.as_str()).find(|kind| *kind != "null"),
+    _ if schema.get("properties").is_some() => Some("object"),
+    _ => None,
+  }
+}
+
+/// A random integer for each request between the bounds of the schema, {{rand_int min max}}
+fn rand_int(schema: &Value) -> String {
+  let bound = |key: &str, exclusive: &str, step: i64| {
+    let value = schema.get(key).and_then(|value| value.as_f64());
+    // in OpenAPI 3.0 the exclusive bounds are booleans and in 3.1 numbers
+    match (value, schema.get(exclusive)) {
+      (_, Some(Value::Number(number))) => number.as_f64().map(|n| n.ceil() as i64 + step),
+      (Some(value), Some(Value::Bool(true))) => Some(value as i64 + step),
+      (Some(value), _) => Some(value as i64),
+      (None, _) => None,
+    }
+  };
+
+  let min = bound("minimum", "exclusiveMinimum", 1);
+  let max = bound("maximum", "exclusiveMaximum", -1);
+  let (min, max) = match (min, max) {
+    (Some(min), Some(max)) => (min, max.max(min)),
+    (Some(min), None) => (min, min.saturating_add(999)),
+    (None, Some(max)) if max >= 1 => (1, max),
+    (None, Some(max)) => (max.saturating_sub(999), max),
+    (None, None) => (1, 1000),
+  };
+
+  format!("{{{{rand_int {min} {max}}}}}")
+}
+
+/// A string for the format of the schema, with the sequence number so each request sends a new one
+fn string_value(schema: &Value, name: &str, encoded: bool) -> String {
+  let encode = |s: &str| match encoded {
+    true => encode(s),
+    false => s.to_string(),
+  };
+
+  match schema.get("format").and_then(|format| format.as_str()) {
+    Some("uuid") => String::from("{{uuid}}"),
+    Some("date") => String::from("2024-01-01"),
+    Some("date-time") => encode("2024-01-01T00:00:00Z"),
+    Some("email") => format!("user{{{{seq}}}}{}", encode("@example.com")),
+    Some("uri" | "url") => format!("{}{{{{seq}}}}", encode("https://example.com/")),
+    _ => format!("{}-{{{{seq}}}}", encode(name)),
+  }
+}
+
+/// An example as it goes in a url or a header, the strings without their quotes
+fn scalar(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    Value::Array(values) => values.iter().map(scalar).collect::<Vec<_>>().join(","),
+    other => other.to_string(),
+  }
+}
+
+/// Percent-encodes a value of a url
+fn encode(s: &str) -> String {
+  url::form_urlencoded::byte_serialize(s.as_bytes())
+    .collect::<String>()
+    .replace('+', "%20")
+}
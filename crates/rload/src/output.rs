@@ -0,0 +1,742 @@
+use std::fmt::{self, Formatter};
+
+use crate::report::Report;
+
+/// The format of the report printed at the end of the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Output {
+  /// the report of rload
+  #[default]
+  Text,
+  /// the same summary as wrk, printed to stdout, for the tools that parse its output
+  Wrk,
+  /// the whole report as JSON with a versioned schema, printed to stdout (needs feature serde)
+  Json,
+  /// a header and a row with the summary of the run, printed to stdout, an output file gets a row appended for each run
+  Csv,
+  /// tables with the config, the results, the latencies, the errors and the statuses, printed to stdout,
+  /// an output file gets the tables of each run appended, like $GITHUB_STEP_SUMMARY
+  Markdown,
+}
+
+impl std::fmt::Display for Output {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Output::Text => write!(f, "text"),
+      Output::Wrk => write!(f, "wrk"),
+      Output::Json => write!(f, "json"),
+      Output::Csv => write!(f, "csv"),
+      Output::Markdown => write!(f, "markdown"),
+    }
+  }
+}
+
+/// The errors grouped like the socket errors of wrk, with the responses with a status other than 2xx or 3xx
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WrkErrors {
+  pub connect: u64,
+  /// without feature error-detail all the errors are counted as read errors
+  pub read: u64,
+  pub write: u64,
+  pub timeout: u64,
+  pub status: u64,
+}
+
+impl WrkErrors {
+  pub fn new(report: &Report) -> Self {
+    #[cfg(feature = "status-detail")]
+    let status = report
+      .statuses
+      .iter()
+      .filter(|(status, _)| *status < 200 || *status > 399)
+      .map(|(_, count)| count)
+      .sum();
+    #[cfg(not(feature = "status-detail"))]
+    let status = report.not_ok_status;
+
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "error-detail")] {
+        use crate::error::ErrorKind;
+        let mut errors = WrkErrors { status, ..WrkErrors::default() };
+        for (kind, count) in report.err.iter() {
+          match kind {
+            ErrorKind::Connect | ErrorKind::TlsHandshake | ErrorKind::H2Handshake => errors.connect += count,
+            ErrorKind::Write | ErrorKind::H2Ready | ErrorKind::H2Send => errors.write += count,
+            ErrorKind::Timeout => errors.timeout += count,
+            _ => errors.read += count,
+          }
+        }
+        errors
+      } else {
+        WrkErrors {
+          read: report.err_count,
+          status,
+          ..WrkErrors::default()
+        }
+      }
+    }
+  }
+
+  pub fn is_socket_error(&self) -> bool {
+    self.connect != 0 || self.read != 0 || self.write != 0 || self.timeout != 0
+  }
+}
+
+/// The distribution of a list of samples, like the requests per second of each thread
+#[derive(Debug, Clone)]
+pub struct Stats {
+  /// the samples in ascending order
+  samples: Vec<f64>,
+  pub min: f64,
+  pub max: f64,
+  pub mean: f64,
+  pub stdev: f64,
+}
+
+impl Stats {
+  pub fn new(mut samples: Vec<f64>) -> Self {
+    samples.sort_by(f64::total_cmp);
+
+    let n = samples.len() as f64;
+    let mean = match samples.is_empty() {
+      true => 0.0,
+      false => samples.iter().sum::<f64>() / n,
+    };
+    let stdev = match samples.len() {
+      0 | 1 => 0.0,
+      _ => (samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt(),
+    };
+
+    Stats {
+      min: samples.first().copied().unwrap_or_default(),
+      max: samples.last().copied().unwrap_or_default(),
+      mean,
+      stdev,
+      samples,
+    }
+  }
+
+  /// The nearest-rank percentile, `p` from 0 to 100
+  pub fn percentile(&self, p: f64) -> f64 {
+    if self.samples.is_empty() {
+      return 0.0;
+    }
+
+    let rank = ((p / 100.0) * self.samples.len() as f64).ceil() as usize;
+    self.samples[rank.clamp(1, self.samples.len()) - 1]
+  }
+
+  /// The percentage of the samples within one standard deviation of the mean
+  pub fn within_stdev(&self) -> f64 {
+    if self.samples.is_empty() {
+      return 0.0;
+    }
+
+    let (lower, upper) = (self.mean - self.stdev, self.mean + self.stdev);
+    let within = self.samples.iter().filter(|x| (lower..=upper).contains(*x)).count();
+    within as f64 / self.samples.len() as f64 * 100.0
+  }
+}
+
+/// The requests per second of each thread, wrk samples them during the run, here each thread contributes its mean rate
+pub fn thread_rates(report: &Report) -> Stats {
+  let secs = report.elapsed.as_secs_f64();
+  Stats::new(report.thread_requests.iter().map(|ok| *ok as f64 / secs).collect())
+}
+
+/// The percentage of the latencies within one standard deviation of the mean
+#[cfg(feature = "latency")]
+fn hdr_within_stdev(hdr: &hdrhistogram::Histogram<u64>) -> f64 {
+  if hdr.is_empty() {
+    return 0.0;
+  }
+
+  let (mean, stdev) = (hdr.mean(), hdr.stdev());
+  let (lower, upper) = (mean - stdev, mean + stdev);
+  let within: u64 = hdr
+    .iter_recorded()
+    .filter(|v| (lower..=upper).contains(&(v.value_iterated_to() as f64)))
+    .map(|v| v.count_at_value())
+    .sum();
+
+  within as f64 / hdr.len() as f64 * 100.0
+}
+
+/// Formats an amount in the largest unit that keeps it over 0.85, like the units.c of wrk
+fn format_units(n: f64, scale: f64, base: &str, units: &[&str]) -> String {
+  let mut amount = n;
+  let mut unit = base;
+  let mut i = 0;
+  while i + 1 < units.len() && amount >= scale * 0.85 {
+    amount /= scale;
+    unit = units[i];
+    i += 1;
+  }
+
+  format!("{amount:.2}{unit}")
+}
+
+fn format_binary(n: f64) -> String {
+  format_units(n, 1024.0, "", &["K", "M", "G", "T", "P"])
+}
+
+fn format_metric(n: f64) -> String {
+  format_units(n, 1000.0, "", &["k", "M", "G", "T", "P"])
+}
+
+fn format_time_us(n: f64) -> String {
+  match n >= 1_000_000.0 {
+    true => format_units(n / 1_000_000.0, 60.0, "s", &["m", "h"]),
+    false => format_units(n, 1000.0, "us", &["ms", "s"]),
+  }
+}
+
+/// Writes a value right aligned in a column, the space after it is taken by the unit, like the print_units of wrk
+fn write_units(f: &mut Formatter<'_>, value: String, width: usize) -> fmt::Result {
+  let bytes = value.as_bytes();
+  let mut pad = 2;
+  if bytes.last().is_some_and(u8::is_ascii_alphabetic) {
+    pad -= 1;
+  }
+  if bytes.len() >= 2 && bytes[bytes.len() - 2].is_ascii_alphabetic() {
+    pad -= 1;
+  }
+
+  let width = width - pad;
+  write!(f, "{:>width$.width$}{}", value, &"  "[..pad])
+}
+
+fn write_stats(f: &mut Formatter<'_>, name: &str, mean: f64, stdev: f64, max: f64, within: f64, format: fn(f64) -> String) -> fmt::Result {
+  write!(f, "    {name:<10}")?;
+  write_units(f, format(mean), 8)?;
+  write_units(f, format(stdev), 10)?;
+  // wrk keeps the max as an integer
+  write_units(f, format(max.trunc()), 9)?;
+  writeln!(f, "{within:>8.2}%")
+}
+
+/// Writes the report in the format of wrk, with the raw latencies and the mean rate of each thread as the requests per second
+pub fn write_wrk(f: &mut Formatter<'_>, report: &Report) -> fmt::Result {
+  let secs = report.elapsed.as_secs_f64();
+
+  writeln!(f, "  Thread Stats{:>6}{:>11}{:>8}{:>12}", "Avg", "Stdev", "Max", "+/- Stdev")?;
+
+  #[cfg(feature = "latency")]
+  if let Some(hdr) = &report.hdr {
+    let us = |nanos: f64| nanos / 1000.0;
+    write_stats(f, "Latency", us(hdr.mean()), us(hdr.stdev()), us(hdr.max() as f64), hdr_within_stdev(hdr), format_time_us)?;
+  }
+
+  let rates = thread_rates(report);
+  write_stats(f, "Req/Sec", rates.mean, rates.stdev, rates.max, rates.within_stdev(), format_metric)?;
+
+  #[cfg(feature = "latency")]
+  if let Some(hdr) = &report.hdr {
+    writeln!(f, "  Latency Distribution")?;
+    for p in [50.0, 75.0, 90.0, 99.0] {
+      write!(f, "{p:>7.0}%")?;
+      write_units(f, format_time_us(hdr.value_at_percentile(p) as f64 / 1000.0), 10)?;
+      writeln!(f)?;
+    }
+  }
+
+  writeln!(
+    f,
+    "  {} requests in {}, {}B read",
+    report.ok,
+    format_time_us(report.elapsed.as_micros() as f64),
+    format_binary(report.read as f64),
+  )?;
+
+  let errors = WrkErrors::new(report);
+  if errors.is_socket_error() {
+    writeln!(
+      f,
+      "  Socket errors: connect {}, read {}, write {}, timeout {}",
+      errors.connect, errors.read, errors.write, errors.timeout,
+    )?;
+  }
+
+  if errors.status != 0 {
+    writeln!(f, "  Non-2xx or 3xx responses: {}", errors.status)?;
+  }
+
+  writeln!(f, "Requests/sec: {:>9.2}", report.ok as f64 / secs)?;
+  write!(f, "Transfer/sec: {:>10}B", format_binary(report.read as f64 / secs))
+}
+
+/// The version of the schema of the JSON report, it only changes when a field is removed or changes its meaning, new fields can be added to a version
+#[cfg(feature = "serde")]
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The report as JSON, the durations are in seconds and the latencies in nanoseconds, by the suffix of their names,
+/// the fields of the features not enabled at compile time are null
+#[cfg(feature = "serde")]
+pub fn json(report: &Report) -> serde_json::Value {
+  use serde_json::{json, Map, Value};
+
+  let secs = report.elapsed.as_secs_f64();
+  let per_sec = |n: u64| match secs {
+    0.0 => 0.0,
+    secs => n as f64 / secs,
+  };
+
+  #[cfg(feature = "timeout")]
+  let timeout = report.timeout.map(|timeout| timeout.as_secs_f64());
+  #[cfg(not(feature = "timeout"))]
+  let timeout = None::<f64>;
+
+  #[cfg(feature = "openapi")]
+  let openapi = report.openapi.clone();
+  #[cfg(not(feature = "openapi"))]
+  let openapi = None::<String>;
+
+  #[cfg(feature = "script")]
+  let script = report.script.clone();
+  #[cfg(not(feature = "script"))]
+  let script = None::<String>;
+
+  #[cfg(feature = "plugin")]
+  let plugin = report.plugin.as_ref().map(|path| {
+    json!({
+      "plugin": path,
+      "calls": report.plugin_stats.calls,
+      "over_budget": report.plugin_stats.over_budget,
+      "time_secs": report.plugin_stats.time.as_secs_f64(),
+    })
+  });
+  #[cfg(not(feature = "plugin"))]
+  let plugin = None::<Value>;
+
+  // all the kinds are always present, so the tools don't depend on which errors happened
+  let mut errors = Map::new();
+  errors.insert(String::from("total"), json!(report.errors()));
+  #[cfg(feature = "error-detail")]
+  {
+    use strum::IntoEnumIterator;
+    for kind in crate::error::ErrorKind::iter() {
+      errors.insert(kind.to_string(), json!(report.err.get(kind)));
+    }
+  }
+
+  #[cfg(feature = "status-detail")]
+  let statuses = statuses_json(&report.statuses);
+  #[cfg(not(feature = "status-detail"))]
+  let statuses = Value::Null;
+
+  #[cfg(feature = "latency")]
+  let latency = match (&report.hdr, &report.hdr_corrected) {
+    (Some(hdr), Some(corrected)) => json!({
+      "raw": hdr_json(hdr, true),
+      "corrected": hdr_json(corrected, true),
+    }),
+    _ => Value::Null,
+  };
+  #[cfg(not(feature = "latency"))]
+  let latency = Value::Null;
+
+  let endpoints = report
+    .endpoints
+    .iter()
+    .map(|endpoint| {
+      #[cfg(feature = "status-detail")]
+      let (statuses, non_2xx_3xx) = (
+        statuses_json(&endpoint.statuses),
+        endpoint.statuses.iter().filter(|(status, _)| *status < 200 || *status > 399).map(|(_, count)| count).sum::<u64>(),
+      );
+      #[cfg(not(feature = "status-detail"))]
+      let (statuses, non_2xx_3xx) = (Value::Null, endpoint.not_ok_status);
+
+      #[cfg(feature = "latency")]
+      let latency = endpoint.hdr.as_ref().map(|hdr| hdr_json(hdr, false));
+      #[cfg(not(feature = "latency"))]
+      let latency = None::<Value>;
+
+      json!({
+        "name": endpoint.name,
+        "weight": endpoint.weight,
+        "fulfilled": endpoint.ok,
+        "errors": endpoint.err,
+        "statuses": statuses,
+        "non_2xx_3xx": non_2xx_3xx,
+        "latency": latency,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let stages = report
+    .stages
+    .iter()
+    .map(|stage| {
+      #[cfg(feature = "latency")]
+      let latency = stage.hdr.as_ref().map(|hdr| hdr_json(hdr, false));
+      #[cfg(not(feature = "latency"))]
+      let latency = None::<Value>;
+
+      json!({
+        "stage": stage.stage.to_string(),
+        "elapsed_secs": stage.elapsed.as_secs_f64(),
+        "fulfilled": stage.ok,
+        "errors": stage.err,
+        "latency": latency,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let intervals = report
+    .intervals
+    .iter()
+    .map(|interval| {
+      json!({
+        "start_secs": interval.start.as_secs_f64(),
+        "elapsed_secs": interval.elapsed.as_secs_f64(),
+        "requested_rate": interval.requested,
+        "sent": interval.sent,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  json!({
+    "schema_version": JSON_SCHEMA_VERSION,
+    "rload_version": crate::build::PKG_VERSION,
+    "config": {
+      "url": crate::template::decode_vars(report.url.as_str()),
+      "address": report.address.to_string(),
+      "http_version": report.http_version.to_string(),
+      "method": report.method,
+      "body_bytes": report.body_len,
+      "keepalive": report.keepalive,
+      "threads": report.threads,
+      "concurrency": report.concurrency,
+      "duration_secs": report.duration.map(|duration| duration.as_secs_f64()),
+      "requests": report.requests,
+      "warmup_secs": report.warmup.as_secs_f64(),
+      "timeout_secs": timeout,
+      "rate": report.rate.map(|rate| rate.target),
+      "rate_profile": report.rate_profile,
+      "distribution": report.rate.map(|rate| rate.distribution.to_string()),
+      "seed": report.rate.and_then(|rate| rate.seed),
+      "replay": report.replay,
+      "replay_speed": report.replay_speed,
+      "access_log": report.access_log,
+      "har": report.har,
+      "openapi": openapi,
+      "scenario": report.scenario,
+      "script": script,
+      "data": report.data.as_ref().map(|data| json!({
+        "file": data.file,
+        "rows": data.rows,
+        "order": data.order.to_string(),
+      })),
+      "runtime": crate::rt::NAME,
+    },
+    "elapsed_secs": secs,
+    "fulfilled": report.ok,
+    "requests_per_sec": per_sec(report.ok),
+    "read_bytes": report.read,
+    "read_bytes_per_sec": per_sec(report.read),
+    "write_bytes": report.write,
+    "write_bytes_per_sec": per_sec(report.write),
+    "errors": errors,
+    "statuses": statuses,
+    "non_2xx_3xx": WrkErrors::new(report).status,
+    "latency": latency,
+    "rate": report.rate.map(|rate| json!({
+      "target": rate.target,
+      "achieved": per_sec(rate.sent),
+      "sent": rate.sent,
+      "late": rate.late,
+      "dropped": rate.dropped,
+    })),
+    "plugin": plugin,
+    "checked": report.checked,
+    "checks": report.checks.iter().map(|check| json!({ "check": check.name, "failed": check.failed })).collect::<Vec<_>>(),
+    "thresholds": report
+      .thresholds
+      .iter()
+      .map(|threshold| json!({ "threshold": threshold.spec, "actual": threshold.actual, "passed": threshold.passed }))
+      .collect::<Vec<_>>(),
+    "thread_fulfilled": report.thread_requests,
+    "intervals": intervals,
+    "stages": stages,
+    "endpoints": endpoints,
+  })
+}
+
+/// The count of each status, by the status as a string
+#[cfg(all(feature = "serde", feature = "status-detail"))]
+fn statuses_json(statuses: &[(u16, u64)]) -> serde_json::Value {
+  statuses.iter().map(|(status, count)| (status.to_string(), serde_json::json!(count))).collect::<serde_json::Map<_, _>>().into()
+}
+
+/// A latency histogram encoded in the compressed V2 format of HdrHistogram in base64, the same format of the histogram logs,
+/// that can be decoded by the HdrHistogram libraries to merge the results of many runs
+#[cfg(all(feature = "serde", feature = "latency"))]
+fn encode_hdr(hdr: &hdrhistogram::Histogram<u64>) -> Option<String> {
+  use base64::Engine;
+  use hdrhistogram::serialization::{Serializer, V2DeflateSerializer};
+
+  let mut encoded = Vec::new();
+  V2DeflateSerializer::new().serialize(hdr, &mut encoded).ok()?;
+  Some(base64::engine::general_purpose::STANDARD.encode(&encoded))
+}
+
+/// Serializes the latency histograms of the report encoded like [`hdr_json`] does
+#[cfg(all(feature = "serde", feature = "latency"))]
+pub fn serialize_hdr<S: serde::Serializer>(hdr: &Option<hdrhistogram::Histogram<u64>>, serializer: S) -> Result<S::Ok, S::Error> {
+  serde::Serialize::serialize(&hdr.as_ref().and_then(encode_hdr), serializer)
+}
+
+/// The summary of a latency histogram, with the histogram encoded by [`encode_hdr`] when `encode` is set
+#[cfg(all(feature = "serde", feature = "latency"))]
+fn hdr_json(hdr: &hdrhistogram::Histogram<u64>, encode: bool) -> serde_json::Value {
+  let percentiles = [50.0, 75.0, 90.0, 99.0, 99.9, 99.99, 99.999]
+    .into_iter()
+    .map(|p| (p.to_string(), serde_json::json!(hdr.value_at_percentile(p))))
+    .collect::<serde_json::Map<_, _>>();
+
+  let histogram = encode.then(|| encode_hdr(hdr)).flatten();
+
+  serde_json::json!({
+    "count": hdr.len(),
+    "min_ns": hdr.min(),
+    "max_ns": hdr.max(),
+    "mean_ns": hdr.mean(),
+    "stdev_ns": hdr.stdev(),
+    "percentiles_ns": percentiles,
+    "histogram": histogram,
+  })
+}
+
+/// The columns of the CSV output, new columns are only added at the end so the rows of older runs keep their meaning,
+/// the latencies are the ones corrected for coordinated omission, in milliseconds
+pub const CSV_COLUMNS: &[&str] = &[
+  "timestamp",
+  "url",
+  "method",
+  "http_version",
+  "threads",
+  "concurrency",
+  "rate",
+  "duration_secs",
+  "elapsed_secs",
+  "fulfilled",
+  "errors",
+  "requests_per_sec",
+  "read_bytes",
+  "write_bytes",
+  "non_2xx_3xx",
+  "status_2xx",
+  "status_3xx",
+  "status_4xx",
+  "status_5xx",
+  "latency_min_ms",
+  "latency_mean_ms",
+  "latency_p50_ms",
+  "latency_p75_ms",
+  "latency_p90_ms",
+  "latency_p99_ms",
+  "latency_p99_9_ms",
+  "latency_max_ms",
+];
+
+/// The responses with a status of each class, from 1xx to 5xx
+#[cfg(feature = "status-detail")]
+fn status_classes(statuses: &[(u16, u64)]) -> [u64; 5] {
+  let mut classes = [0; 5];
+  for (status, count) in statuses {
+    if let Some(class) = classes.get_mut((*status / 100).wrapping_sub(1) as usize) {
+      *class += count;
+    }
+  }
+  classes
+}
+
+/// The summary of the run as a CSV row of the first `columns` of [`CSV_COLUMNS`], preceded by the header with `header`,
+/// the values not measured are empty
+pub fn write_csv(out: impl std::io::Write, report: &Report, header: bool, columns: usize) -> Result<(), csv::Error> {
+  let mut writer = csv::Writer::from_writer(out);
+  if header {
+    writer.write_record(&CSV_COLUMNS[..columns])?;
+  }
+
+  let secs = report.elapsed.as_secs_f64();
+  let timestamp = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+
+  let mut row = vec![
+    timestamp.to_string(),
+    crate::template::decode_vars(report.url.as_str()).into_owned(),
+    report.method.clone(),
+    report.http_version.to_string(),
+    report.threads.to_string(),
+    report.concurrency.to_string(),
+    report.rate.map(|rate| rate.target.to_string()).unwrap_or_default(),
+    report.duration.map(|duration| duration.as_secs_f64().to_string()).unwrap_or_default(),
+    secs.to_string(),
+    report.ok.to_string(),
+    report.errors().to_string(),
+    format!("{:.2}", if secs == 0.0 { 0.0 } else { report.ok as f64 / secs }),
+    report.read.to_string(),
+    report.write.to_string(),
+    WrkErrors::new(report).status.to_string(),
+  ];
+
+  #[cfg(feature = "status-detail")]
+  row.extend(status_classes(&report.statuses)[1..].iter().map(u64::to_string));
+  #[cfg(not(feature = "status-detail"))]
+  row.extend(std::iter::repeat_n(String::new(), 4));
+
+  #[cfg(feature = "latency")]
+  let latencies = report.hdr_corrected.as_ref().filter(|hdr| !hdr.is_empty()).map(|hdr| {
+    let ms = |nanos: f64| format!("{:.3}", nanos / 1_000_000.0);
+    let p = |p: f64| ms(hdr.value_at_percentile(p) as f64);
+    vec![ms(hdr.min() as f64), ms(hdr.mean()), p(50.0), p(75.0), p(90.0), p(99.0), p(99.9), ms(hdr.max() as f64)]
+  });
+  #[cfg(not(feature = "latency"))]
+  let latencies = None::<Vec<String>>;
+
+  row.extend(latencies.unwrap_or(vec![String::new(); 8]));
+
+  writer.write_record(&row[..columns])?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Escapes the pipes of a markdown table cell
+fn cell(value: impl std::fmt::Display) -> String {
+  value.to_string().replace('|', "\\|")
+}
+
+fn write_table(f: &mut Formatter<'_>, title: &str, columns: &[&str], rows: &[Vec<String>]) -> fmt::Result {
+  writeln!(f)?;
+  writeln!(f, "### {title}")?;
+  writeln!(f)?;
+  writeln!(f, "| {} |", columns.join(" | "))?;
+  writeln!(f, "|{}", " --- |".repeat(columns.len()))?;
+  for row in rows {
+    writeln!(f, "| {} |", row.join(" | "))?;
+  }
+  Ok(())
+}
+
+/// Writes the report as markdown tables, a heading with the url and a table for the config, the results,
+/// the latencies, the errors, the statuses, the checks and the thresholds
+pub fn write_markdown(f: &mut Formatter<'_>, report: &Report) -> fmt::Result {
+  use crate::fmt::format_duration;
+  use human_bytes::human_bytes;
+
+  let secs = report.elapsed.as_secs_f64();
+  let per_sec = |n: u64| match secs {
+    0.0 => 0.0,
+    secs => n as f64 / secs,
+  };
+
+  writeln!(f, "## rload {} {}", cell(&report.method), cell(crate::template::decode_vars(report.url.as_str())))?;
+
+  let mut config = vec![
+    vec![String::from("address"), cell(report.address)],
+    vec![String::from("http-version"), cell(report.http_version)],
+    vec![String::from("threads"), report.threads.to_string()],
+    vec![String::from("concurrency"), report.concurrency.to_string()],
+  ];
+  if report.endpoints.len() > 1 {
+    config.push(vec![String::from("endpoints"), report.endpoints.len().to_string()]);
+  }
+  if let Some(rate) = &report.rate {
+    config.push(vec![String::from("rate"), format!("{}/s", (rate.target * 100.0).round() / 100.0)]);
+  }
+  if let Some(duration) = report.duration {
+    config.push(vec![String::from("duration"), format_duration(duration).to_string()]);
+  }
+  if let Some(requests) = report.requests {
+    config.push(vec![String::from("requests"), requests.to_string()]);
+  }
+  if !report.warmup.is_zero() {
+    config.push(vec![String::from("warmup"), format_duration(report.warmup).to_string()]);
+  }
+  config.push(vec![String::from("runtime"), String::from(crate::rt::NAME)]);
+  write_table(f, "Config", &["option", "value"], &config)?;
+
+  let result = vec![
+    vec![String::from("elapsed"), format_duration(report.elapsed).to_string()],
+    vec![String::from("fulfilled"), report.ok.to_string()],
+    vec![String::from("errors"), report.errors().to_string()],
+    vec![String::from("requests/sec"), format!("{:.2}", per_sec(report.ok))],
+    vec![String::from("read"), format!("{} - {}/s", human_bytes(report.read as f64), human_bytes(per_sec(report.read)))],
+    vec![String::from("write"), format!("{} - {}/s", human_bytes(report.write as f64), human_bytes(per_sec(report.write)))],
+  ];
+  write_table(f, "Result", &["metric", "value"], &result)?;
+
+  #[cfg(feature = "latency")]
+  if let (Some(hdr), Some(corrected)) = (&report.hdr, &report.hdr_corrected) {
+    let t = |nanos: u64| format_duration(std::time::Duration::from_nanos(nanos)).to_string();
+    let tf = |nanos: f64| t(nanos.round() as u64);
+
+    let mut rows = vec![
+      vec![String::from("min"), t(corrected.min()), t(hdr.min())],
+      vec![String::from("mean"), tf(corrected.mean()), tf(hdr.mean())],
+      vec![String::from("stdev"), tf(corrected.stdev()), tf(hdr.stdev())],
+    ];
+    for p in [50.0, 75.0, 90.0, 99.0, 99.9, 99.99] {
+      rows.push(vec![format!("{p}%"), t(corrected.value_at_percentile(p)), t(hdr.value_at_percentile(p))]);
+    }
+    rows.push(vec![String::from("max"), t(corrected.max()), t(hdr.max())]);
+    write_table(f, "Latency", &["", "corrected", "raw"], &rows)?;
+  }
+
+  #[cfg(feature = "error-detail")]
+  if report.errors() != 0 {
+    let rows = report.err.iter().map(|(kind, count)| vec![kind.to_string(), count.to_string()]).collect::<Vec<_>>();
+    write_table(f, "Errors", &["error", "count"], &rows)?;
+  }
+
+  #[cfg(feature = "status-detail")]
+  if !report.statuses.is_empty() {
+    let rows = report.statuses.iter().map(|(status, count)| vec![status.to_string(), count.to_string()]).collect::<Vec<_>>();
+    write_table(f, "Statuses", &["status", "count"], &rows)?;
+  }
+
+  #[cfg(not(feature = "status-detail"))]
+  if report.not_ok_status != 0 {
+    write_table(f, "Statuses", &["status", "count"], &[vec![String::from("not 2xx/3xx"), report.not_ok_status.to_string()]])?;
+  }
+
+  if report.endpoints.len() > 1 {
+    let rows = report
+      .endpoints
+      .iter()
+      .map(|endpoint| vec![cell(&endpoint.name), endpoint.weight.to_string(), endpoint.ok.to_string(), endpoint.err.to_string()])
+      .collect::<Vec<_>>();
+    write_table(f, "Endpoints", &["endpoint", "weight", "fulfilled", "errors"], &rows)?;
+  }
+
+  if !report.checks.is_empty() {
+    let rows = report
+      .checks
+      .iter()
+      .map(|check| vec![cell(&check.name), format!("{} of {}", check.failed, report.checked)])
+      .collect::<Vec<_>>();
+    write_table(f, "Checks", &["check", "failed"], &rows)?;
+  }
+
+  if !report.thresholds.is_empty() {
+    let rows = report
+      .thresholds
+      .iter()
+      .map(|threshold| {
+        let result = match threshold.passed {
+          true => "passed",
+          false => "**failed**",
+        };
+        vec![cell(format!("`{}`", threshold.spec)), cell(&threshold.actual), String::from(result)]
+      })
+      .collect::<Vec<_>>();
+    write_table(f, "Thresholds", &["threshold", "actual", "result"], &rows)?;
+  }
+
+  Ok(())
+}
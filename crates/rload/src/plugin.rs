@@ -0,0 +1,214 @@
+use anyhow::Context;
+use std::time::{Duration, Instant};
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, Trap, TypedFunc};
+
+use crate::error::ErrorKind;
+
+/// A WebAssembly module compiled once, each thread instantiates its own plugin from it
+///
+/// The module exports its `memory` and this functions, it can't have imports:
+/// - `rload_next(conn: i32) -> i64` returns the next request to send on a connection, as raw http/1.1 bytes in its memory
+///   packed as `ptr << 32 | len`, 0 to send the request of the endpoint instead, or a negative value to fail the request
+/// - `rload_observe(conn: i32, status: i32, latency_us: i64)`, optional, is called with the status and the latency of each response
+/// - `rload_init(thread: i32)`, optional, is called once by each thread after instantiating the module
+///
+/// Each call is interrupted when it runs over the budget, failing its request
+pub struct PluginSource {
+  pub path: String,
+  /// the time each call can take
+  pub budget: Duration,
+  engine: Engine,
+  module: Module,
+  /// the epoch ticks of the budget
+  ticks: u64,
+}
+
+/// The calls to the plugin of a thread, including the ones of the warm-up
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PluginStats {
+  pub calls: u64,
+  /// calls interrupted for running over the budget
+  pub over_budget: u64,
+  /// the time spent in the calls
+  pub time: Duration,
+}
+
+impl PluginStats {
+  pub fn join(&mut self, other: Self) {
+    self.calls += other.calls;
+    self.over_budget += other.over_budget;
+    self.time += other.time;
+  }
+}
+
+impl PluginSource {
+  /// Compiles a module, in the binary or the text format, and checks its exports before the test starts
+  pub fn load(path: &str, budget: Duration) -> Result<Self, anyhow::Error> {
+    if budget.is_zero() {
+      anyhow::bail!("plugin-budget option must be greater than 0");
+    }
+
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::from_file(&engine, path).with_context(|| format!("error loading plugin {path}"))?;
+
+    // the budget is measured in ticks of the epoch of the engine, so a call is interrupted at most a tenth of the budget late
+    let tick = (budget / 10).max(Duration::from_micros(10));
+    let ticks = budget.as_nanos().div_ceil(tick.as_nanos()) as u64 + 1;
+
+    let source = PluginSource {
+      path: path.to_string(),
+      budget,
+      engine,
+      module,
+      ticks,
+    };
+
+    Plugin::instantiate(&source)?;
+
+    // the ticker runs until the process exits, the engine outlives the run
+    let engine = source.engine.clone();
+    std::thread::spawn(move || loop {
+      std::thread::sleep(tick);
+      engine.increment_epoch();
+    });
+
+    Ok(source)
+  }
+}
+
+/// A plugin instantiated by a thread, only used by the tasks of the thread
+pub struct Plugin {
+  store: Store<()>,
+  init: Option<TypedFunc<i32, ()>>,
+  memory: Memory,
+  next: TypedFunc<i32, i64>,
+  observe: Option<TypedFunc<(i32, i32, i64), ()>>,
+  ticks: u64,
+  path: String,
+  failed: bool,
+  pub stats: PluginStats,
+}
+
+impl Plugin {
+  fn instantiate(source: &PluginSource) -> Result<Self, anyhow::Error> {
+    let invalid = |what: &str| format!("invalid plugin {}, {what}", source.path);
+
+    let mut store = Store::new(&source.engine, ());
+    store.set_epoch_deadline(source.ticks);
+
+    let instance = Instance::new(&mut store, &source.module, &[]).with_context(|| invalid("the module can't have imports"))?;
+
+    let memory = instance
+      .get_memory(&mut store, "memory")
+      .with_context(|| invalid("it must export its memory"))?;
+
+    let next = instance
+      .get_typed_func::<i32, i64>(&mut store, "rload_next")
+      .with_context(|| invalid("it must export rload_next(conn: i32) -> i64"))?;
+
+    let init = match instance.get_func(&mut store, "rload_init") {
+      None => None,
+      Some(func) => Some(
+        func
+          .typed::<i32, ()>(&store)
+          .with_context(|| invalid("rload_init must be (thread: i32)"))?,
+      ),
+    };
+
+    let observe = match instance.get_func(&mut store, "rload_observe") {
+      None => None,
+      Some(func) => Some(
+        func
+          .typed::<(i32, i32, i64), ()>(&store)
+          .with_context(|| invalid("rload_observe must be (conn: i32, status: i32, latency_us: i64)"))?,
+      ),
+    };
+
+    Ok(Plugin {
+      store,
+      init,
+      memory,
+      next,
+      observe,
+      ticks: source.ticks,
+      path: source.path.clone(),
+      failed: false,
+      stats: PluginStats::default(),
+    })
+  }
+
+  /// Instantiates the module for a thread and calls its rload_init
+  pub fn new(source: &PluginSource, thread: usize) -> Result<Self, anyhow::Error> {
+    let mut plugin = Plugin::instantiate(source)?;
+
+    if let Some(init) = plugin.init.take() {
+      plugin.store.set_epoch_deadline(plugin.ticks);
+      init
+        .call(&mut plugin.store, thread as i32)
+        .with_context(|| format!("error in rload_init of plugin {}", plugin.path))?;
+    }
+
+    Ok(plugin)
+  }
+
+  pub fn has_observe(&self) -> bool {
+    self.observe.is_some()
+  }
+
+  /// Calls a function of the module within the budget, the first error of each thread is printed, the next ones are only counted
+  fn call<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(&mut self, name: &str, func: TypedFunc<P, R>, params: P) -> Result<R, ErrorKind> {
+    self.store.set_epoch_deadline(self.ticks);
+    let start = Instant::now();
+    let result = func.call(&mut self.store, params);
+    self.stats.calls += 1;
+    self.stats.time += start.elapsed();
+
+    result.map_err(|e| match e.downcast_ref::<Trap>() {
+      Some(Trap::Interrupt) => {
+        self.stats.over_budget += 1;
+        ErrorKind::PluginBudget
+      }
+      _ => {
+        if !std::mem::replace(&mut self.failed, true) {
+          eprintln!("error in {name} of plugin {}: {e:#}", self.path);
+        }
+        ErrorKind::Plugin
+      }
+    })
+  }
+
+  /// Writes the next request of a connection to `buf`, `false` when the plugin leaves it to the endpoint
+  pub fn next(&mut self, conn: usize, buf: &mut Vec<u8>) -> Result<bool, ErrorKind> {
+    let packed = self.call("rload_next", self.next.clone(), conn as i32)?;
+    if packed == 0 {
+      return Ok(false);
+    }
+
+    if packed < 0 {
+      return Err(ErrorKind::Plugin);
+    }
+
+    let (ptr, len) = ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize);
+    let Some(request) = self.memory.data(&self.store).get(ptr..ptr + len) else {
+      if !std::mem::replace(&mut self.failed, true) {
+        eprintln!("error in rload_next of plugin {}: the request at {ptr} with a length of {len} is out of the bounds of its memory", self.path);
+      }
+      return Err(ErrorKind::Plugin);
+    };
+
+    buf.clear();
+    buf.extend_from_slice(request);
+    Ok(true)
+  }
+
+  /// Calls rload_observe with a response, if the module exports it
+  pub fn observe(&mut self, conn: usize, status: u16, latency: Duration) -> Result<(), ErrorKind> {
+    match self.observe.clone() {
+      None => Ok(()),
+      Some(observe) => self.call("rload_observe", observe, (conn as i32, status as i32, latency.as_micros() as i64)),
+    }
+  }
+}
@@ -0,0 +1,250 @@
+use anyhow::Context;
+use std::{path::Path, time::Duration};
+
+/// A piecewise linear function of the time elapsed since the start of the run
+///
+/// Used for the number of active connections and for the target request rate,
+/// after the last point the value is held forever
+#[derive(Debug, Clone)]
+pub struct Profile {
+  /// (offset in seconds, value) sorted by offset, the first one is always at offset zero
+  points: Vec<(f64, f64)>,
+  /// the integral of the profile from the start up to each point
+  integrals: Vec<f64>,
+}
+
+impl Profile {
+  /// A profile that holds the same value forever
+  pub fn constant(value: f64) -> Self {
+    Self::from_points(vec![(Duration::ZERO, value)])
+  }
+
+  /// Creates a profile from (offset, value) points
+  ///
+  /// # Panics
+  /// if the points are empty, the first one is not at offset zero or they are not sorted by offset
+  pub fn from_points(points: Vec<(Duration, f64)>) -> Self {
+    assert!(!points.is_empty(), "profile points must not be empty");
+    assert!(points[0].0.is_zero(), "the first profile point must be at offset zero");
+
+    let points = points
+      .into_iter()
+      .map(|(offset, value)| (offset.as_secs_f64(), value))
+      .collect::<Vec<_>>();
+
+    let mut integrals = Vec::with_capacity(points.len());
+    let mut integral = 0.0;
+    integrals.push(integral);
+    for pair in points.windows(2) {
+      let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+      assert!(t1 >= t0, "profile points must be sorted by offset");
+      integral += (v0 + v1) / 2.0 * (t1 - t0);
+      integrals.push(integral);
+    }
+
+    Self { points, integrals }
+  }
+
+  /// Loads a profile from a time series file of (t_seconds, value) points, interpolated linearly between them
+  ///
+  /// The file is parsed as JSON if its extension is .json, either as an array of [t_seconds, value] pairs
+  /// or of {"t_seconds": t, "rate": value} objects, otherwise it's parsed as CSV with a t_seconds,rate pair per line,
+  /// an optional header and lines starting with # as comments
+  ///
+  /// The times are shifted so the first point is at offset zero and divided by `time_scale`,
+  /// so a time scale of 144 replays a 24h curve in 10 minutes
+  pub fn load(path: &Path, time_scale: f64) -> Result<Self, anyhow::Error> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("error reading rate profile from {}", path.display()))?;
+
+    let points = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("json") => parse_json_points(&content),
+      _ => parse_csv_points(&content),
+    }
+    .with_context(|| format!("error parsing rate profile {}", path.display()))?;
+
+    let Some((first, _)) = points.first().copied() else {
+      anyhow::bail!("rate profile {} has no points", path.display());
+    };
+
+    let mut last = first;
+    for (i, (t, value)) in points.iter().copied().enumerate() {
+      if !t.is_finite() || t < last {
+        anyhow::bail!("rate profile {} point {} has an invalid time {t}, times must be sorted", path.display(), i + 1);
+      }
+      if !value.is_finite() || value < 0.0 {
+        anyhow::bail!("rate profile {} point {} has an invalid rate {value}, must be equal or greater than 0", path.display(), i + 1);
+      }
+      last = t;
+    }
+
+    let points = points
+      .into_iter()
+      .map(|(t, value)| (Duration::from_secs_f64((t - first) / time_scale), value))
+      .collect();
+
+    Ok(Self::from_points(points))
+  }
+
+  /// A copy of the profile that starts after `delay`, holding `value` until then
+  pub fn delayed(&self, delay: Duration, value: f64) -> Self {
+    if delay.is_zero() {
+      return self.clone();
+    }
+
+    let mut points = vec![(Duration::ZERO, value), (delay, value)];
+    for (offset, v) in &self.points {
+      points.push((delay + Duration::from_secs_f64(*offset), *v));
+    }
+
+    Self::from_points(points)
+  }
+
+  /// The offset of the last point of the profile
+  pub fn duration(&self) -> Duration {
+    Duration::from_secs_f64(self.points[self.points.len() - 1].0)
+  }
+
+  /// The maximum value of the profile
+  pub fn max(&self) -> f64 {
+    self.points.iter().map(|(_, v)| *v).fold(0.0, f64::max)
+  }
+
+  /// The value of the profile at `t` since the start
+  pub fn value_at(&self, t: Duration) -> f64 {
+    let t = t.as_secs_f64();
+    let i = self.segment(t);
+    self.value_in(i, t)
+  }
+
+  /// The integral of the profile from the start to `t`, for a rate this is the number of requests due until `t`
+  pub fn integral(&self, t: Duration) -> f64 {
+    let t = t.as_secs_f64();
+    let i = self.segment(t);
+    let (t0, v0) = self.points[i];
+    self.integrals[i] + (v0 + self.value_in(i, t)) / 2.0 * (t - t0)
+  }
+
+  /// The first time at which the integral of the profile reaches `n`, for a rate this is the time at which the n-th request is due
+  ///
+  /// `cursor` is the segment to start searching from, it's updated so successive calls with increasing `n` don't scan the whole profile
+  ///
+  /// Returns `None` if the integral never reaches `n`
+  pub fn time_of(&self, n: f64, cursor: &mut usize) -> Option<Duration> {
+    while *cursor + 1 < self.points.len() && self.integrals[*cursor + 1] < n {
+      *cursor += 1;
+    }
+
+    let i = *cursor;
+    let (t0, v0) = self.points[i];
+    let rest = n - self.integrals[i];
+
+    if rest <= 0.0 {
+      return Some(Duration::from_secs_f64(t0));
+    }
+
+    // the slope of the segment, after the last point the value is held
+    let slope = match self.points.get(i + 1) {
+      Some((t1, v1)) if *t1 > t0 => (v1 - v0) / (t1 - t0),
+      _ => 0.0,
+    };
+
+    // solves v0 * dt + slope / 2 * dt^2 = rest for dt in the numerically stable form
+    let disc = v0 * v0 + 2.0 * slope * rest;
+    let div = v0 + disc.max(0.0).sqrt();
+    if div <= 0.0 {
+      return None;
+    }
+
+    Some(Duration::from_secs_f64(t0 + 2.0 * rest / div))
+  }
+
+  /// The first time at or after `from` at which the value of the profile is greater than `threshold`
+  ///
+  /// Returns `None` if the value never goes above `threshold`
+  pub fn time_above(&self, threshold: f64, from: Duration) -> Option<Duration> {
+    let from = from.as_secs_f64();
+    let start = self.segment(from);
+
+    if self.value_in(start, from) > threshold {
+      return Some(Duration::from_secs_f64(from));
+    }
+
+    for i in start..self.points.len() - 1 {
+      let (t0, v0) = self.points[i];
+      let (t1, v1) = self.points[i + 1];
+      if v1 > threshold && t1 > t0 {
+        // the crossing point of the segment, nudged a little forward so the value is strictly above the threshold
+        let t = t0 + (threshold - v0) / (v1 - v0) * (t1 - t0);
+        return Some(Duration::from_secs_f64(t.max(from).min(t1)) + Duration::from_micros(1));
+      }
+    }
+
+    None
+  }
+
+  /// The index of the segment that contains `t`
+  fn segment(&self, t: f64) -> usize {
+    let mut i = 0;
+    while i + 1 < self.points.len() && self.points[i + 1].0 <= t {
+      i += 1;
+    }
+    i
+  }
+
+  /// The value at `t` inside the segment `i`
+  fn value_in(&self, i: usize, t: f64) -> f64 {
+    let (t0, v0) = self.points[i];
+    match self.points.get(i + 1) {
+      Some((t1, v1)) if *t1 > t0 => v0 + (v1 - v0) * ((t - t0) / (t1 - t0)).clamp(0.0, 1.0),
+      _ => v0,
+    }
+  }
+}
+
+fn parse_csv_points(content: &str) -> Result<Vec<(f64, f64)>, anyhow::Error> {
+  let mut points = Vec::new();
+  for (i, line) in content.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let (t, value) = line
+      .split_once(',')
+      .with_context(|| format!("line {}: invalid point {line}, must be in the format of t_seconds,rate", i + 1))?;
+
+    match (t.trim().parse::<f64>(), value.trim().parse::<f64>()) {
+      (Ok(t), Ok(value)) => points.push((t, value)),
+      // the header
+      _ if points.is_empty() && i == 0 => continue,
+      _ => anyhow::bail!("line {}: invalid point {line}, time and rate must be numbers", i + 1),
+    }
+  }
+
+  Ok(points)
+}
+
+fn parse_json_points(content: &str) -> Result<Vec<(f64, f64)>, anyhow::Error> {
+  let json: serde_json::Value = serde_json::from_str(content)?;
+  let items = json.as_array().context("rate profile must be an array of points")?;
+
+  items
+    .iter()
+    .enumerate()
+    .map(|(i, item)| {
+      let pair = match item {
+        serde_json::Value::Array(pair) if pair.len() == 2 => (pair[0].as_f64(), pair[1].as_f64()),
+        serde_json::Value::Object(map) => (
+          map.get("t_seconds").and_then(|v| v.as_f64()),
+          map.get("rate").and_then(|v| v.as_f64()),
+        ),
+        _ => (None, None),
+      };
+
+      match pair {
+        (Some(t), Some(value)) => Ok((t, value)),
+        _ => anyhow::bail!("point {}: invalid point {item}, must be a [t_seconds, rate] pair or an object with t_seconds and rate numbers", i + 1),
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,141 @@
+use anyhow::Context;
+use base64::Engine;
+use std::{path::Path, time::Duration};
+
+use crate::profile::Profile;
+
+/// A request of a replay file
+#[derive(Debug, Clone)]
+pub struct ReplayEntry {
+  pub method: String,
+  /// a path relative to the url or a full url with the same scheme, host and port
+  pub target: String,
+  pub headers: Vec<String>,
+  pub body: Option<Vec<u8>>,
+  /// the time the request was recorded at, in seconds
+  pub timestamp: Option<f64>,
+}
+
+/// Loads a replay file with a JSON object per line, in the format of
+/// `{"method": "POST", "path": "/orders", "headers": {"content-type": "application/json"}, "body": "{}", "timestamp": 1700000000.5}`
+///
+/// Only the path is required, the method defaults to GET, the headers can also be a list of "key: value" strings,
+/// the body can be given as base64 with `body_base64` instead and the timestamp is in seconds
+pub fn load(path: &Path) -> Result<Vec<ReplayEntry>, anyhow::Error> {
+  let content = std::fs::read_to_string(path).with_context(|| format!("error reading replay file {}", path.display()))?;
+
+  let mut entries = Vec::new();
+  for (i, line) in content.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let entry = parse_entry(line).with_context(|| format!("error parsing replay file {} line {}", path.display(), i + 1))?;
+    entries.push(entry);
+  }
+
+  if entries.is_empty() {
+    anyhow::bail!("replay file {} has no requests", path.display());
+  }
+
+  Ok(entries)
+}
+
+fn parse_entry(line: &str) -> Result<ReplayEntry, anyhow::Error> {
+  let json: serde_json::Value = serde_json::from_str(line)?;
+  let object = json.as_object().context("each line must be a json object")?;
+
+  let string = |key: &str| -> Result<Option<String>, anyhow::Error> {
+    match object.get(key) {
+      None | Some(serde_json::Value::Null) => Ok(None),
+      Some(serde_json::Value::String(s)) => Ok(Some(s.clone())),
+      Some(_) => anyhow::bail!("{key} must be a string"),
+    }
+  };
+
+  let method = string("method")?.unwrap_or_else(|| String::from("GET"));
+  let target = string("path")?.context("missing path")?;
+
+  let headers = match object.get("headers") {
+    None | Some(serde_json::Value::Null) => Vec::new(),
+    Some(serde_json::Value::Object(map)) => map
+      .iter()
+      .map(|(k, v)| match v {
+        serde_json::Value::String(v) => Ok(format!("{k}:{v}")),
+        _ => anyhow::bail!("header {k} must be a string"),
+      })
+      .collect::<Result<_, _>>()?,
+    Some(serde_json::Value::Array(list)) => list
+      .iter()
+      .map(|h| h.as_str().map(String::from).context("headers must be \"key: value\" strings"))
+      .collect::<Result<_, _>>()?,
+    Some(_) => anyhow::bail!("headers must be an object or a list of \"key: value\" strings"),
+  };
+
+  let body = match (string("body")?, string("body_base64")?) {
+    (Some(_), Some(_)) => anyhow::bail!("body and body_base64 cannot be both set"),
+    (Some(body), None) => Some(body.into_bytes()),
+    (None, Some(body)) => Some(
+      base64::engine::general_purpose::STANDARD
+        .decode(body.trim())
+        .context("invalid base64 in body_base64")?,
+    ),
+    (None, None) => None,
+  };
+
+  let timestamp = match object.get("timestamp") {
+    None | Some(serde_json::Value::Null) => None,
+    Some(value) => match value.as_f64() {
+      Some(t) if t.is_finite() => Some(t),
+      _ => anyhow::bail!("timestamp must be a number of seconds"),
+    },
+  };
+
+  Ok(ReplayEntry {
+    method,
+    target,
+    headers,
+    body,
+    timestamp,
+  })
+}
+
+/// The gap enforced between requests recorded at the same time, so each one has its own arrival time
+const MIN_GAP: Duration = Duration::from_micros(1);
+
+/// A rate profile under which the n-th request is due at the recorded time of the n-th entry divided by `speed`
+///
+/// The rate is constant between two entries, after the last one it's held at the average rate of the file so the entries
+/// are cycled through at that pace, the duration of the profile covers all of them once
+///
+/// # Panics
+/// if there are less than two timestamps
+pub fn profile(timestamps: &[f64], speed: f64) -> Profile {
+  assert!(timestamps.len() >= 2, "a replay profile needs at least two timestamps");
+
+  let first = timestamps[0];
+
+  let mut offsets = Vec::<Duration>::with_capacity(timestamps.len());
+  for t in timestamps {
+    let offset = Duration::from_secs_f64(((t - first) / speed).max(0.0));
+    let offset = match offsets.last() {
+      Some(last) => offset.max(*last + MIN_GAP),
+      None => offset,
+    };
+    offsets.push(offset);
+  }
+
+  let span = offsets[offsets.len() - 1];
+  let average = (offsets.len() - 1) as f64 / span.as_secs_f64();
+
+  let mut points = Vec::with_capacity(offsets.len() * 2);
+  for pair in offsets.windows(2) {
+    let rate = 1.0 / (pair[1] - pair[0]).as_secs_f64();
+    points.push((pair[0], rate));
+    points.push((pair[1], rate));
+  }
+  points.push((span, average));
+  points.push((span + Duration::from_secs_f64(1.0 / average), average));
+
+  Profile::from_points(points)
+}
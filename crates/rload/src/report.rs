@@ -1,13 +1,105 @@
+use anyhow::Context;
+use std::io::Write;
 use human_bytes::human_bytes;
 use std::{net::SocketAddr, time::Duration};
 use url::Url;
 
-use crate::fmt::format_duration;
+use crate::{data::DataOrder, distribution::Distribution, fmt::format_duration, output::Output, stage::Stage, threshold::ThresholdReport};
 
 #[cfg(feature = "error-detail")]
 use crate::error::Errors;
 
+/// The maximum number of rows of the endpoints report
+const MAX_ENDPOINTS: usize = 20;
+
+/// The results of a constant-throughput run
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Rate {
+  /// the target rate in requests per second
+  pub target: f64,
+  /// the number of requests actually sent
+  pub sent: u64,
+  /// requests sent later than their intended send time because all connections were busy
+  pub late: u64,
+  /// requests that were due before the end of the run but never sent because all connections were busy
+  pub dropped: u64,
+  /// the distribution of the arrival times
+  pub distribution: Distribution,
+  /// the seed of the arrival times, for random distributions
+  pub seed: Option<u64>,
+}
+
+/// The data file that fed the template variables
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DataReport {
+  pub file: String,
+  pub rows: usize,
+  pub order: DataOrder,
+}
+
+/// The requested and achieved rate during an interval of a constant-throughput run
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IntervalReport {
+  /// the offset of the interval from the start of the measurement
+  pub start: Duration,
+  /// the time the interval actually ran, the last one could be cut short
+  pub elapsed: Duration,
+  /// the mean requested rate during the interval
+  pub requested: f64,
+  /// the requests sent during the interval
+  pub sent: u64,
+}
+
+/// The results of a single endpoint of the request mix
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EndpointReport {
+  /// the method and the path of the endpoint (eg: GET /items)
+  pub name: String,
+  pub weight: u32,
+  pub ok: u64,
+  pub err: u64,
+  #[cfg(feature = "status-detail")]
+  pub statuses: Vec<(u16, u64)>,
+  #[cfg(not(feature = "status-detail"))]
+  pub not_ok_status: u64,
+  /// the raw latencies of the requests fulfilled for the endpoint
+  #[cfg(feature = "latency")]
+  #[cfg_attr(feature = "serde", serde(serialize_with = "crate::output::serialize_hdr"))]
+  pub hdr: Option<hdrhistogram::Histogram<u64>>,
+}
+
+/// The responses that failed a check
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CheckReport {
+  /// the check as given in the command line (eg: status 200,201)
+  pub name: String,
+  pub failed: u64,
+}
+
+/// The results of a single stage of the load profile
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StageReport {
+  pub stage: Stage,
+  /// the time the stage actually ran
+  pub elapsed: Duration,
+  pub ok: u64,
+  pub err: u64,
+  /// the raw latencies of the requests fulfilled during the stage
+  #[cfg(feature = "latency")]
+  #[cfg_attr(feature = "serde", serde(serialize_with = "crate::output::serialize_hdr"))]
+  pub hdr: Option<hdrhistogram::Histogram<u64>>,
+}
+
+/// The results of a run, with feature serde it serializes as its fields,
+/// the JSON of --output json has its own versioned schema built by [`crate::output::json`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Report {
   pub url: Url,
   pub address: SocketAddr,
@@ -18,8 +110,53 @@ pub struct Report {
 
   pub threads: usize,
   pub concurrency: usize,
-  pub duration: Duration,
+  /// `None` when the run was limited only by the number of requests
+  pub duration: Option<Duration>,
+  pub requests: Option<u64>,
+  pub warmup: Duration,
   pub elapsed: Duration,
+  pub rate: Option<Rate>,
+  /// the path of the rate profile file
+  pub rate_profile: Option<String>,
+  /// the path of the replay file
+  pub replay: Option<String>,
+  /// the speed factor of a replay at the recorded timestamps
+  pub replay_speed: Option<f64>,
+  /// the path of the imported access log and how many of its lines were imported
+  pub access_log: Option<String>,
+  /// the path of the imported HAR file, how its requests are sent and how many of them were imported
+  pub har: Option<String>,
+  /// the path of the OpenAPI spec the endpoints were generated from and how many of its operations are requested
+  #[cfg(feature = "openapi")]
+  pub openapi: Option<String>,
+  /// the path of the scenario file and its number of steps
+  pub scenario: Option<String>,
+  /// the path of the script with the hooks of the requests
+  #[cfg(feature = "script")]
+  pub script: Option<String>,
+  /// the path of the plugin that generates the requests and the budget of each call
+  #[cfg(feature = "plugin")]
+  pub plugin: Option<String>,
+  /// the calls to the plugin
+  #[cfg(feature = "plugin")]
+  pub plugin_stats: crate::plugin::PluginStats,
+  /// the responses that went through the checks
+  pub checked: u64,
+  /// the checks of the responses, empty when there are none
+  pub checks: Vec<CheckReport>,
+  /// the thresholds evaluated at the end of the run, empty when there are none
+  pub thresholds: Vec<ThresholdReport>,
+  pub intervals: Vec<IntervalReport>,
+  pub stages: Vec<StageReport>,
+  pub data: Option<DataReport>,
+  /// the endpoints of the request mix, a single one when requesting only the url
+  pub endpoints: Vec<EndpointReport>,
+  /// the requests fulfilled by each thread
+  pub thread_requests: Vec<u64>,
+  /// the format the report is printed in
+  pub output: Output,
+  /// the file the report is written to in its format, then it is printed to stderr as text
+  pub output_file: Option<String>,
 
   #[cfg(feature = "timeout")]
   pub timeout: Option<Duration>,
@@ -40,12 +177,128 @@ pub struct Report {
   #[cfg(not(feature = "status-detail"))]
   pub not_ok_status: u64,
 
+  /// the raw latencies, measured from the moment each request was actually sent
   #[cfg(feature = "latency")]
+  #[cfg_attr(feature = "serde", serde(serialize_with = "crate::output::serialize_hdr"))]
   pub hdr: Option<hdrhistogram::Histogram<u64>>,
+
+  /// the latencies corrected for coordinated omission
+  #[cfg(feature = "latency")]
+  #[cfg_attr(feature = "serde", serde(serialize_with = "crate::output::serialize_hdr"))]
+  pub hdr_corrected: Option<hdrhistogram::Histogram<u64>>,
+}
+
+impl Report {
+  /// Prints the report, to stderr like the progress of the run, or to stdout in the formats meant to be parsed,
+  /// with an output file the report is written to it instead and printed to stderr as text
+  pub fn print(&self) -> Result<(), anyhow::Error> {
+    match (&self.output_file, self.output) {
+      (Some(path), Output::Csv) => self.append_csv(path)?,
+      (Some(path), Output::Markdown) => {
+        let mut file = std::fs::OpenOptions::new()
+          .create(true)
+          .append(true)
+          .open(path)
+          .with_context(|| format!("error opening {path} to append the report"))?;
+        writeln!(file, "{}", self).with_context(|| format!("error writing the report to {path}"))?;
+      }
+      (Some(path), _) => {
+        std::fs::write(path, format!("{}\n", self)).with_context(|| format!("error writing the report to {path}"))?;
+      }
+      (None, Output::Text) => eprintln!("{}", self),
+      (None, Output::Wrk | Output::Json | Output::Csv | Output::Markdown) => println!("{}", self),
+    }
+
+    if self.output_file.is_some() {
+      eprintln!("{}", Text(self));
+    }
+
+    Ok(())
+  }
+
+  /// Appends the row of this run to a CSV file, with the header when the file is new or empty,
+  /// so the runs of a sweep accumulate in one table
+  fn append_csv(&self, path: &str) -> Result<(), anyhow::Error> {
+    let existing = match std::fs::read_to_string(path) {
+      Ok(content) => content,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e).with_context(|| format!("error reading {path} to append the report")),
+    };
+
+    // a file written by an older version has only the first columns, the rows appended to it have the same ones
+    let (header, columns) = match existing.lines().next() {
+      None => (true, crate::output::CSV_COLUMNS.len()),
+      Some(line) => {
+        let names = line.split(',').collect::<Vec<_>>();
+        if !crate::output::CSV_COLUMNS.starts_with(&names) {
+          anyhow::bail!("error appending the report to {path}, its columns are not the ones of the csv output of rload");
+        }
+        (false, names.len())
+      }
+    };
+
+    let file = std::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .with_context(|| format!("error opening {path} to append the report"))?;
+    crate::output::write_csv(file, self, header, columns).with_context(|| format!("error writing the report to {path}"))?;
+
+    Ok(())
+  }
+
+  /// The thresholds the results didn't meet
+  pub fn failed_thresholds(&self) -> impl Iterator<Item = &ThresholdReport> + '_ {
+    self.thresholds.iter().filter(|threshold| !threshold.passed)
+  }
+
+  /// The total number of failed requests and connection attempts
+  pub fn errors(&self) -> u64 {
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "error-detail")] {
+        self.err.total()
+      } else {
+        self.err_count
+      }
+    }
+  }
 }
 
 impl std::fmt::Display for Report {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.output {
+      Output::Text => self.write_text(f),
+      Output::Wrk => crate::output::write_wrk(f, self),
+      #[cfg(feature = "serde")]
+      Output::Json => {
+        let json = serde_json::to_string_pretty(&crate::output::json(self)).map_err(|_| std::fmt::Error)?;
+        f.write_str(&json)
+      }
+      #[cfg(not(feature = "serde"))]
+      Output::Json => unreachable!("the json output is rejected without feature serde"),
+      Output::Csv => {
+        let mut csv = Vec::new();
+        crate::output::write_csv(&mut csv, self, true, crate::output::CSV_COLUMNS.len()).map_err(|_| std::fmt::Error)?;
+        // without the line break of the row, like the other outputs
+        f.write_str(String::from_utf8_lossy(&csv).trim_end())
+      }
+      Output::Markdown => crate::output::write_markdown(f, self),
+    }
+  }
+}
+
+/// The report as text, whatever its output format
+struct Text<'a>(&'a Report);
+
+impl std::fmt::Display for Text<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let Text(report) = self;
+    report.write_text(f)
+  }
+}
+
+impl Report {
+  fn write_text(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let secs = self.elapsed.as_secs_f64();
 
     if self.ok == 0 {
@@ -66,17 +319,21 @@ impl std::fmt::Display for Report {
 
     writeln!(f)?;
     writeln!(f, "==========| Config |=========")?;
-    writeln!(f, "url:          {}", self.url)?;
+    writeln!(f, "url:          {}", crate::template::decode_vars(self.url.as_str()))?;
     writeln!(f, "address:      {}", self.address)?;
     writeln!(f, "http-version: {}", self.http_version)?;
-    writeln!(f, "method:       {}", self.method)?;
-    if self.body_len != 0
-      || !matches!(
-        self.method.as_ref(),
-        "GET" | "HEAD" | "DELETE" | "OPTIONS" | "TRACE"
-      )
-    {
-      writeln!(f, "body:         {}", human_bytes(self.body_len as f64))?;
+    if self.endpoints.len() > 1 {
+      writeln!(f, "endpoints:    {}", self.endpoints.len())?;
+    } else {
+      writeln!(f, "method:       {}", self.method)?;
+      if self.body_len != 0
+        || !matches!(
+          self.method.as_ref(),
+          "GET" | "HEAD" | "DELETE" | "OPTIONS" | "TRACE"
+        )
+      {
+        writeln!(f, "body:         {}", human_bytes(self.body_len as f64))?;
+      }
     }
     writeln!(
       f,
@@ -90,11 +347,61 @@ impl std::fmt::Display for Report {
 
     writeln!(f, "threads:      {}", self.threads)?;
     writeln!(f, "concurrency:  {}", self.concurrency)?;
-    writeln!(
-      f,
-      "duration:     {}",
-      crate::fmt::format_duration(self.duration)
-    )?;
+    if let Some(rate) = &self.rate {
+      writeln!(f, "rate:         {}/s", (rate.target * 100.0).round() / 100.0)?;
+      if let Some(path) = &self.rate_profile {
+        writeln!(f, "rate-profile: {}", path)?;
+      }
+      if rate.distribution != Distribution::Constant {
+        writeln!(f, "arrivals:     {}", rate.distribution)?;
+      }
+      if let Some(seed) = rate.seed {
+        writeln!(f, "seed:         {}", seed)?;
+      }
+    }
+    if let Some(access_log) = &self.access_log {
+      writeln!(f, "access log:   {}", access_log)?;
+    }
+    if let Some(har) = &self.har {
+      writeln!(f, "har:          {}", har)?;
+    }
+    #[cfg(feature = "openapi")]
+    if let Some(openapi) = &self.openapi {
+      writeln!(f, "openapi:      {}", openapi)?;
+    }
+    if let Some(scenario) = &self.scenario {
+      writeln!(f, "scenario:     {}", scenario)?;
+    }
+    #[cfg(feature = "script")]
+    if let Some(script) = &self.script {
+      writeln!(f, "script:       {}", script)?;
+    }
+    #[cfg(feature = "plugin")]
+    if let Some(plugin) = &self.plugin {
+      writeln!(f, "plugin:       {}", plugin)?;
+    }
+    if let Some(replay) = &self.replay {
+      match self.replay_speed {
+        Some(speed) => writeln!(f, "replay:       {} ({}x)", replay, speed)?,
+        None => writeln!(f, "replay:       {}", replay)?,
+      }
+    }
+    if let Some(data) = &self.data {
+      writeln!(f, "data:         {} ({} rows, {})", data.file, data.rows, data.order)?;
+    }
+    if let Some(duration) = self.duration {
+      writeln!(
+        f,
+        "duration:     {}",
+        crate::fmt::format_duration(duration)
+      )?;
+    }
+    if let Some(requests) = self.requests {
+      writeln!(f, "requests:     {}", requests)?;
+    }
+    if !self.warmup.is_zero() {
+      writeln!(f, "warmup:       {}", crate::fmt::format_duration(self.warmup))?;
+    }
     #[cfg(feature = "timeout")]
     if let Some(timeout) = self.timeout {
       writeln!(f, "timeout:      {}", crate::fmt::format_duration(timeout))?;
@@ -112,22 +419,36 @@ impl std::fmt::Display for Report {
         crate::fmt::format_duration(Duration::from_nanos(nanos.round() as u64))
       }
 
-      if let Some(hdr) = &self.hdr {
+      if let (Some(hdr), Some(corrected)) = (&self.hdr, &self.hdr_corrected) {
+        fn row(
+          f: &mut std::fmt::Formatter<'_>,
+          name: &str,
+          corrected: impl std::fmt::Display,
+          raw: impl std::fmt::Display,
+        ) -> std::fmt::Result {
+          writeln!(f, "{: <9}{: <12}{}", name, corrected.to_string(), raw)
+        }
+
         writeln!(f)?;
         writeln!(f, "=========| Latency |=========")?;
-        writeln!(f, "min:     {}", t(hdr.min()))?;
-        writeln!(f, "max:     {}", t(hdr.max()))?;
-        writeln!(f, "mean:    {}", tf(hdr.mean()))?;
-        writeln!(f, "stdev:   {}", tf(hdr.stdev()))?;
+        row(f, "", "corrected", "raw")?;
+        row(f, "min:", t(corrected.min()), t(hdr.min()))?;
+        row(f, "max:", t(corrected.max()), t(hdr.max()))?;
+        row(f, "mean:", tf(corrected.mean()), tf(hdr.mean()))?;
+        row(f, "stdev:", tf(corrected.stdev()), tf(hdr.stdev()))?;
         writeln!(f, "-----------------------------")?;
 
-        writeln!(f, "50%      {}", t(hdr.value_at_percentile(50.0)))?;
-        writeln!(f, "75%      {}", t(hdr.value_at_percentile(75.0)))?;
-        writeln!(f, "90%      {}", t(hdr.value_at_percentile(95.0)))?;
-        writeln!(f, "99%      {}", t(hdr.value_at_percentile(99.0)))?;
-        writeln!(f, "99.9%    {}", t(hdr.value_at_percentile(99.9)))?;
-        writeln!(f, "99.99%   {}", t(hdr.value_at_percentile(99.99)))?;
-        writeln!(f, "99.999%  {}", t(hdr.value_at_percentile(99.999)))?;
+        for (name, p) in [
+          ("50%", 50.0),
+          ("75%", 75.0),
+          ("90%", 90.0),
+          ("99%", 99.0),
+          ("99.9%", 99.9),
+          ("99.99%", 99.99),
+          ("99.999%", 99.999),
+        ] {
+          row(f, name, t(corrected.value_at_percentile(p)), t(hdr.value_at_percentile(p)))?;
+        }
       }
     }
 
@@ -208,6 +529,185 @@ impl std::fmt::Display for Report {
       (self.ok as f64 / secs).round() as u64
     )?;
 
+    #[cfg(feature = "plugin")]
+    if self.plugin.is_some() {
+      let stats = &self.plugin_stats;
+      writeln!(f, "- Plugin")?;
+      writeln!(f, "  · calls:          {}", stats.calls)?;
+      if let Some(mean) = stats.time.checked_div(stats.calls as u32) {
+        writeln!(f, "  · mean time:      {}", format_duration(mean))?;
+      }
+      writeln!(f, "  · over budget:    {}", stats.over_budget)?;
+    }
+
+    if !self.checks.is_empty() {
+      writeln!(f, "- Checks")?;
+      let width = self.checks.iter().map(|check| check.name.len() + 2).max().unwrap_or(0).max(15);
+      for check in &self.checks {
+        let percent = match self.checked {
+          0 => 0.0,
+          checked => check.failed as f64 / checked as f64 * 100.0,
+        };
+        writeln!(f, "  · {: <width$}{} failed of {} ({:.2}%)", format!("{}:", check.name), check.failed, self.checked, percent)?;
+      }
+    }
+
+    if let Some(rate) = &self.rate {
+      writeln!(f, "- Rate")?;
+      writeln!(f, "  · target:         {}/s", (rate.target * 100.0).round() / 100.0)?;
+      writeln!(f, "  · achieved:       {}/s", (rate.sent as f64 / secs).round() as u64)?;
+      writeln!(f, "  · late:           {}", rate.late)?;
+      writeln!(f, "  · dropped:        {}", rate.dropped)?;
+    }
+
+    if !self.intervals.is_empty() {
+      writeln!(f)?;
+      writeln!(f, "=========| Intervals |=======")?;
+      writeln!(f, "interval        requested   achieved    diff")?;
+
+      // the achieved rate of each interval as a bar scaled to the highest requested or achieved rate
+      const BAR: usize = 30;
+      let rates = self
+        .intervals
+        .iter()
+        .map(|interval| match interval.elapsed.as_secs_f64() {
+          0.0 => (interval.requested, 0.0),
+          secs => (interval.requested, interval.sent as f64 / secs),
+        })
+        .collect::<Vec<_>>();
+      let max = rates.iter().map(|(requested, achieved)| requested.max(*achieved)).fold(0.0, f64::max);
+
+      for (interval, (requested, achieved)) in self.intervals.iter().zip(rates) {
+        let diff = match requested {
+          0.0 => String::from("-"),
+          requested => format!("{:+.1}%", (achieved - requested) / requested * 100.0),
+        };
+
+        let bar = match max {
+          0.0 => 0,
+          max => (achieved / max * BAR as f64).round() as usize,
+        };
+
+        writeln!(
+          f,
+          "{: <16}{: <12}{: <12}{: <8}{}",
+          format_duration(interval.start).to_string(),
+          format!("{}/s", requested.round() as u64),
+          format!("{}/s", achieved.round() as u64),
+          diff,
+          "#".repeat(bar),
+        )?;
+      }
+    }
+
+    if self.endpoints.len() > 1 {
+      writeln!(f)?;
+      writeln!(f, "=========| Endpoints |=======")?;
+
+      #[cfg(feature = "latency")]
+      let has_latency = self.endpoints.iter().any(|endpoint| endpoint.hdr.is_some());
+      #[cfg(not(feature = "latency"))]
+      let has_latency = false;
+
+      // with many endpoints, like the paths of a replay file, only the ones with the most requests are shown
+      let mut shown = self.endpoints.iter().collect::<Vec<_>>();
+      if shown.len() > MAX_ENDPOINTS {
+        shown.sort_by_key(|endpoint| std::cmp::Reverse(endpoint.ok + endpoint.err));
+        shown.truncate(MAX_ENDPOINTS);
+      }
+
+      let width = shown.iter().map(|endpoint| endpoint.name.len()).max().unwrap_or(0).max(8) + 2;
+
+      write!(f, "{: <width$}weight  fulfilled   errors    ", "endpoint")?;
+      if has_latency {
+        write!(f, "p50         p99         ")?;
+      }
+      writeln!(f, "statuses")?;
+
+      for endpoint in shown {
+        write!(f, "{: <width$}{: <8}{: <12}{: <10}", endpoint.name, endpoint.weight, endpoint.ok, endpoint.err)?;
+
+        #[cfg(feature = "latency")]
+        if let Some(hdr) = &endpoint.hdr {
+          let t = |nanos| format_duration(Duration::from_nanos(nanos)).to_string();
+          write!(f, "{: <12}{: <12}", t(hdr.value_at_percentile(50.0)), t(hdr.value_at_percentile(99.0)))?;
+        }
+
+        #[cfg(feature = "status-detail")]
+        {
+          let statuses = endpoint
+            .statuses
+            .iter()
+            .map(|(status, count)| format!("{status}: {count}"))
+            .collect::<Vec<_>>();
+          write!(f, "{}", statuses.join(", "))?;
+        }
+
+        #[cfg(not(feature = "status-detail"))]
+        write!(f, "not 2xx/3xx: {}", endpoint.not_ok_status)?;
+
+        writeln!(f)?;
+      }
+
+      if self.endpoints.len() > MAX_ENDPOINTS {
+        writeln!(f, "... and {} more", self.endpoints.len() - MAX_ENDPOINTS)?;
+      }
+    }
+
+    if !self.stages.is_empty() {
+      writeln!(f)?;
+      writeln!(f, "==========| Stages |=========")?;
+
+      #[cfg(feature = "latency")]
+      let has_latency = self.stages.iter().any(|stage| stage.hdr.is_some());
+      #[cfg(not(feature = "latency"))]
+      let has_latency = false;
+
+      write!(f, "stage           fulfilled   errors    req/s     ")?;
+      if has_latency {
+        write!(f, "p50         p99         max")?;
+      }
+      writeln!(f)?;
+
+      for stage in &self.stages {
+        let rps = match stage.elapsed.as_secs_f64() {
+          0.0 => 0,
+          secs => (stage.ok as f64 / secs).round() as u64,
+        };
+
+        write!(f, "{: <16}{: <12}{: <10}{: <10}", stage.stage.to_string(), stage.ok, stage.err, rps)?;
+
+        #[cfg(feature = "latency")]
+        if let Some(hdr) = &stage.hdr {
+          let t = |nanos| format_duration(Duration::from_nanos(nanos)).to_string();
+          write!(
+            f,
+            "{: <12}{: <12}{}",
+            t(hdr.value_at_percentile(50.0)),
+            t(hdr.value_at_percentile(99.0)),
+            t(hdr.max()),
+          )?;
+        }
+
+        writeln!(f)?;
+      }
+    }
+
+    if !self.thresholds.is_empty() {
+      writeln!(f)?;
+      writeln!(f, "========| Thresholds |=======")?;
+
+      let width = self.thresholds.iter().map(|threshold| threshold.spec.len()).max().unwrap_or(0).max(9) + 2;
+      writeln!(f, "{: <width$}{: <12}result", "threshold", "actual")?;
+      for threshold in &self.thresholds {
+        let result = match threshold.passed {
+          true => "passed",
+          false => "FAILED",
+        };
+        writeln!(f, "{: <width$}{: <12}{}", threshold.spec, threshold.actual, result)?;
+      }
+    }
+
     Ok(())
   }
 }
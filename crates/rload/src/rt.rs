@@ -12,6 +12,19 @@ cfg_if::cfg_if! {
     pub use monoio_http::h2;
     #[cfg(feature = "tls")]
     pub use monoio_rustls::TlsConnector;
+
+    /// Yields once to the runtime, so the other tasks of the thread can run
+    pub async fn yield_now() {
+      let mut yielded = false;
+      std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+        true => std::task::Poll::Ready(()),
+        false => {
+          cx.waker().wake_by_ref();
+          std::task::Poll::Pending
+        }
+      })
+      .await
+    }
   } else {
     pub const NAME: &str = "tokio";
     pub use tokio::spawn;
@@ -21,6 +34,7 @@ cfg_if::cfg_if! {
     pub use tokio::io::{AsyncWrite as Write, AsyncWriteExt as WriteExt};
     pub use tokio::net::TcpStream;
     pub use tokio::signal::ctrl_c;
+    pub use tokio::task::yield_now;
     #[cfg(feature = "h2")]
     pub use h2;
     #[cfg(feature = "tls")]
@@ -1,4 +1,11 @@
-use crate::{args::{Request, RunConfig}, io::CounterStream};
+use crate::{
+  args::{Request, RunConfig},
+  extract::{Needs, Response},
+  io::CounterStream,
+  rt::Instant,
+  schedule::{Schedule, LATE_TOLERANCE},
+  template::Vars,
+};
 
 #[cfg(feature = "error-detail")]
 use crate::error::{ErrorKind, Errors};
@@ -7,15 +14,95 @@ use crate::error::{ErrorKind, Errors};
 use crate::status::Statuses;
 
 use near_safe_cell::NearSafeCell;
-use tokio::sync::watch;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{watch, Notify};
+
+/// The requests left in a run with a fixed number of requests, shared by all threads
+#[derive(Debug)]
+pub struct Requests {
+  /// requests not sent yet, a failed connection attempt also takes one
+  pub unsent: AtomicU64,
+  /// requests not completed yet, either unsent or in flight, a request is completed when it's fulfilled or when it fails
+  pub pending: AtomicU64,
+  /// notified once when the last request is completed
+  pub done: Notify,
+}
+
+impl Requests {
+  pub fn new(n: u64) -> Self {
+    Self {
+      unsent: AtomicU64::new(n),
+      pending: AtomicU64::new(n),
+      done: Notify::new(),
+    }
+  }
+}
+
+/// The results of a thread for a single stage of the load profile
+#[derive(Debug, Clone)]
+pub struct StageResult {
+  pub ok: u64,
+  pub err: u64,
+  #[cfg(feature = "latency")]
+  pub hdr: hdrhistogram::Histogram<u64>,
+}
+
+#[cfg_attr(not(feature = "latency"), allow(clippy::derivable_impls))]
+impl Default for StageResult {
+  fn default() -> Self {
+    Self {
+      ok: 0,
+      err: 0,
+      #[cfg(feature = "latency")]
+      hdr: hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram"),
+    }
+  }
+}
+
+/// The results of a thread for a single endpoint of the request mix
+#[derive(Debug, Clone)]
+pub struct EndpointResult {
+  pub ok: u64,
+  /// failed requests, the errors before sending a request like connect errors are not attributed to any endpoint
+  pub err: u64,
+  #[cfg(feature = "status-detail")]
+  pub statuses: Statuses,
+  #[cfg(not(feature = "status-detail"))]
+  pub not_ok_status: u64,
+  #[cfg(feature = "latency")]
+  pub hdr: hdrhistogram::Histogram<u64>,
+}
+
+#[cfg_attr(not(feature = "latency"), allow(clippy::derivable_impls))]
+impl Default for EndpointResult {
+  fn default() -> Self {
+    Self {
+      ok: 0,
+      err: 0,
+      #[cfg(feature = "status-detail")]
+      statuses: Statuses::new(),
+      #[cfg(not(feature = "status-detail"))]
+      not_ok_status: 0,
+      #[cfg(feature = "latency")]
+      hdr: hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram"),
+    }
+  }
+}
 
 #[derive(Debug, Clone)]
 pub struct ThreadResult {
   pub ok: u64,
   pub read: u64,
   pub write: u64,
+  /// requests sent in constant-throughput mode
+  pub sent: u64,
+  /// requests sent later than their intended send time in constant-throughput mode
+  pub late: u64,
   #[cfg(feature = "latency")]
   pub hdr: hdrhistogram::Histogram<u64>,
+  /// latencies measured from the intended send time in constant-throughput mode
+  #[cfg(feature = "latency")]
+  pub hdr_corrected: hdrhistogram::Histogram<u64>,
 
   #[cfg(feature = "error-detail")]
   pub err: Errors,
@@ -26,16 +113,46 @@ pub struct ThreadResult {
   pub statuses: Statuses,
   #[cfg(not(feature = "status-detail"))]
   pub not_ok_status: u64,
+
+  /// the results of each stage of the load profile
+  pub stages: Vec<StageResult>,
+
+  /// the results of each endpoint of the request mix, the statuses are also joined in the totals at the end of the thread
+  pub endpoints: Vec<EndpointResult>,
+
+  /// requests sent in each interval of the rate report
+  pub intervals: Vec<u64>,
+
+  /// with a fixed number of requests, the time the last one was completed if it was in this thread
+  pub finished: Option<Instant>,
+
+  /// the globals of the script at the end of the thread, read with thread:get(name) in the done hook with the api of wrk
+  #[cfg(feature = "script")]
+  pub globals: Vec<(String, crate::script::Global)>,
+
+  /// the calls to the plugin of this thread
+  #[cfg(feature = "plugin")]
+  pub plugin: crate::plugin::PluginStats,
+
+  /// the responses that went through the checks
+  pub checked: u64,
+  /// the responses that failed each check, in the order of the checks
+  pub checks: Vec<u64>,
 }
 
+#[cfg_attr(not(feature = "latency"), allow(clippy::derivable_impls))]
 impl Default for ThreadResult {
   fn default() -> Self {
     Self {
       ok: 0,
       read: 0,
       write: 0,
+      sent: 0,
+      late: 0,
       #[cfg(feature = "latency")]
       hdr: hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram"),
+      #[cfg(feature = "latency")]
+      hdr_corrected: hdrhistogram::Histogram::<u64>::new(5).expect("error creating latency histogram"),
       
       #[cfg(feature = "error-detail")]
       err: Errors::new(),
@@ -47,6 +164,20 @@ impl Default for ThreadResult {
       statuses: Statuses::new(),
       #[cfg(not(feature = "status-detail"))]
       not_ok_status: 0,
+
+      stages: Vec::new(),
+
+      endpoints: Vec::new(),
+
+      intervals: Vec::new(),
+
+      finished: None,
+      #[cfg(feature = "script")]
+      globals: Vec::new(),
+      #[cfg(feature = "plugin")]
+      plugin: crate::plugin::PluginStats::default(),
+      checked: 0,
+      checks: Vec::new(),
     }
   }
 }
@@ -56,26 +187,32 @@ impl Default for ThreadResult {
 #[monoio::main(driver = "legacy", timer = true)]
 pub async fn thread(
   config: RunConfig<'static>,
-  start: watch::Receiver<()>,
+  index: usize,
+  start: watch::Receiver<Instant>,
   stop: watch::Receiver<()>,
+  requests: Option<&'static Requests>,
 ) -> ThreadResult {
-  thread_inner(config, start, stop).await
+  thread_inner(config, index, start, stop, requests).await
 }
 
 #[cfg(not(feature = "monoio"))]
 #[tokio::main(flavor = "current_thread")]
 pub async fn thread(
   config: RunConfig<'static>,
-  start: watch::Receiver<()>,
+  index: usize,
+  start: watch::Receiver<Instant>,
   stop: watch::Receiver<()>,
+  requests: Option<&'static Requests>,
 ) -> ThreadResult {
-  thread_inner(config, start, stop).await
+  thread_inner(config, index, start, stop, requests).await
 }
 
 pub async fn thread_inner(
   config: RunConfig<'static>,
-  start: watch::Receiver<()>,
+  index: usize,
+  start: watch::Receiver<Instant>,
   stop: watch::Receiver<()>,
+  requests: Option<&'static Requests>,
 ) -> ThreadResult {
   macro_rules! leak {
     ($var:ident = $v:expr) => {
@@ -83,23 +220,529 @@ pub async fn thread_inner(
     };
   }
 
-  leak!(result = ThreadResult::default());
+  // the hooks of the script run in an interpreter of this thread, the script already ran once before the test started
+  #[cfg(feature = "script")]
+  let script = match config.script.map(|source| crate::script::Script::new(source, index)).transpose() {
+    Ok(script) => script,
+    Err(e) => {
+      eprintln!("error loading script in thread {index}: {e:#}");
+      return ThreadResult::default();
+    }
+  };
+
+  // the instance of the plugin of this thread, the module was already instantiated once before the test started
+  #[cfg(feature = "plugin")]
+  let plugin = match config.plugin.map(|source| crate::plugin::Plugin::new(source, index)).transpose() {
+    Ok(plugin) => plugin,
+    Err(e) => {
+      eprintln!("error loading plugin in thread {index}: {e:#}");
+      return ThreadResult::default();
+    }
+  };
+
+  // the address this thread connects to, the setup hook of a script with the api of wrk can change it
+  #[cfg(feature = "script")]
+  let addr = config.script.and_then(|script| script.addr(index)).unwrap_or(config.addr);
+  #[cfg(not(feature = "script"))]
+  let addr = config.addr;
+
+  leak!(result = ThreadResult {
+    stages: vec![StageResult::default(); config.stages.len()],
+    endpoints: vec![EndpointResult::default(); crate::endpoint::reports(config.endpoints)],
+    checks: vec![0; config.checks.len()],
+    ..ThreadResult::default()
+  });
+  // the position of this thread in the mix of endpoints, the threads start at different positions so they don't send the same requests in lockstep
+  leak!(position = index % config.mix.len());
+  leak!(schedule = config
+    .rate
+    .map(|rate| Schedule::new(rate, config.distribution, config.seed, config.threads, index)));
+  // requests started during the warm-up are not recorded
+  leak!(measuring = config.warmup.is_zero());
+  // the values of the template variables of the requests sent by this thread
+  leak!(vars = Vars::new(
+    config.seed,
+    config.threads,
+    index,
+    config.data.map_or(&[], |data| data.partition(config.threads, index)),
+    config.data_order,
+  ));
+
+  if !config.warmup.is_zero() {
+    let mut start = start.clone();
+    crate::rt::spawn(async move {
+      start.changed().await.unwrap();
+      let started = *start.borrow();
+      crate::rt::sleep_until(started + config.warmup).await;
+      // Safety: this values are local to this thread, so is not possible to race
+      unsafe {
+        *measuring.get_mut_unsafe() = true;
+        result.get_mut_unsafe().read = 0;
+        result.get_mut_unsafe().write = 0;
+      }
+    });
+  }
 
   let conns = (config.concurrency as f64 / config.threads as f64).ceil() as usize;
+  // the buffers each connection renders its h1 templates into, they must be static to be written to the socket
+  leak!(rendered = (0..conns).map(|_| NearSafeCell::new(Vec::<u8>::new())).collect::<Vec<_>>());
+  // the position of each connection in the mix when each one sends the endpoints as a user session
+  leak!(sessions = (0..conns).map(|_| NearSafeCell::new(0usize)).collect::<Vec<_>>());
+  #[cfg(feature = "script")]
+  leak!(script = script);
+  // the responses are only buffered for the script when it has a response hook
+  #[cfg(feature = "script")]
+  let script_response = script.get().as_ref().is_some_and(|script| script.has_response());
+  #[cfg(not(feature = "script"))]
+  let script_response = false;
+  #[cfg(feature = "plugin")]
+  leak!(plugin = plugin);
+  // the responses are also buffered for the status passed to rload_observe of the plugin
+  #[cfg(feature = "plugin")]
+  let plugin_observe = plugin.get().as_ref().is_some_and(|plugin| plugin.has_observe());
+  #[cfg(not(feature = "plugin"))]
+  let plugin_observe = false;
+  // the parts of the responses read by the checks, the response hook of the script and the plugin, the plugin only reads the status
+  let needs = config
+    .checks
+    .iter()
+    .fold(Needs::default(), |needs, check| needs.union(check.needs()))
+    .union(if script_response { Needs::ALL } else { Needs::default() });
+  // the last response of each connection, only buffered for the steps of a scenario that extract values from it or for the script
+  leak!(responses = (0..conns)
+    .map(|_| NearSafeCell::new(Response { max_body: config.max_body, ..Response::default() }))
+    .collect::<Vec<_>>());
   let mut handles = Vec::with_capacity(conns);
-  for _ in 0..conns {
+  for conn in 0..conns {
+    // connections are interleaved between threads, so ramping them up and down is spread evenly across threads
+    let conn_index = (conn * config.threads + index) as f64;
+    #[cfg_attr(not(feature = "h1"), allow(unused_variables))]
+    let rendered: &'static NearSafeCell<Vec<u8>> = &rendered.get()[conn];
+    let session: &'static NearSafeCell<usize> = &sessions.get()[conn];
+    let response: &'static NearSafeCell<Response> = &responses.get()[conn];
     let mut stop = stop.clone();
     let mut start = start.clone();
     let task = async move {
       let task = async {
         
         start.changed().await.unwrap();
+        let started = *start.borrow();
+
+        // records a failed request or connection attempt, unless it happens during the warm-up
+        macro_rules! record_err {
+          ($err:expr) => {{
+            if *measuring.get() {
+              cfg_if::cfg_if! {
+                if #[cfg(feature = "error-detail")] {
+                  unsafe {
+                    result.get_mut_unsafe().err.record($err);
+                  }
+                } else {
+                  unsafe {
+                    result.get_mut_unsafe().err_count += 1;
+                  }
+                }
+              }
+
+              if !config.stages.is_empty() {
+                let i = crate::stage::index_at(config.stages, started.elapsed().saturating_sub(config.warmup));
+                // Safety: index_at always returns an index in bounds for a non empty list of stages
+                unsafe {
+                  result.get_mut_unsafe().stages.get_unchecked_mut(i).err += 1;
+                }
+              }
+            }
+          }};
+
+          // a failed request is also recorded in the results of its endpoint
+          ($err:expr, $report:ident) => {{
+            record_err!($err);
+            if *measuring.get() {
+              // Safety: the report index of an endpoint is always in bounds of the endpoint results
+              unsafe {
+                result.get_mut_unsafe().endpoints.get_unchecked_mut($report).err += 1;
+              }
+            }
+          }};
+        }
+
+        // with a fixed number of requests marks the request taken by claim! as completed, the last one stops the run
+        macro_rules! complete {
+          ($measured:ident) => {
+            if let (Some(requests), true) = (requests, $measured) {
+              if requests.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                unsafe {
+                  result.get_mut_unsafe().finished = Some(Instant::now());
+                }
+                requests.done.notify_one();
+              }
+            }
+          };
+        }
+
+        // records a fulfilled request if it was sent after the warm-up, with its latency measured from $start
+        // and its corrected latency measured from the intended send time $intended
+        macro_rules! record_ok {
+          ($measured:ident, $start:ident, $intended:ident, $report:ident) => {{
+            if $measured {
+              unsafe {
+                result.get_mut_unsafe().ok += 1;
+                result.get_mut_unsafe().endpoints.get_unchecked_mut($report).ok += 1;
+              }
+
+              complete!($measured);
+
+              #[cfg(feature = "latency")]
+              let elapsed = match $start {
+                None => None,
+                Some(start) => {
+                  let elapsed = start.elapsed().as_nanos() as u64;
+                  unsafe {
+                    // this will not fail, by ignoring the error instead of unwrapping we remove the branching from the code
+                    let _ = result.get_mut_unsafe().hdr.record(elapsed);
+                    let _ = result.get_mut_unsafe().endpoints.get_unchecked_mut($report).hdr.record(elapsed);
+                  }
+
+                  // the corrected latency also accounts the time the request waited for a free connection
+                  if let Some(intended) = $intended {
+                    let corrected = intended.elapsed().as_nanos();
+                    unsafe {
+                      let _ = result.get_mut_unsafe().hdr_corrected.record(corrected as u64);
+                    }
+                  }
+
+                  Some(elapsed)
+                }
+              };
+
+              if !config.stages.is_empty() {
+                let i = crate::stage::index_at(config.stages, started.elapsed().saturating_sub(config.warmup));
+                // Safety: index_at always returns an index in bounds for a non empty list of stages
+                let stage = unsafe { result.get_mut_unsafe().stages.get_unchecked_mut(i) };
+                stage.ok += 1;
+
+                #[cfg(feature = "latency")]
+                if let Some(elapsed) = elapsed {
+                  let _ = stage.hdr.record(elapsed);
+                }
+              }
+            }
+          }};
+        }
+
+        // the next endpoint of the mix to request, when replaying each thread takes every threads-th request of the file
+        // so all the threads together send them in order, with sessions each connection goes through the whole mix
+        macro_rules! next_endpoint {
+          () => {{
+            // Safety: the position is local to this thread and the session to this connection, so is not possible to race
+            let position = match config.sessions {
+              true => unsafe { session.get_mut_unsafe() },
+              false => unsafe { position.get_mut_unsafe() },
+            };
+            // Safety: the position is always in bounds of the mix and the mix only contains indexes of endpoints
+            let endpoint = unsafe { config.endpoints.get_unchecked(*config.mix.get_unchecked(*position)) };
+            *position += match config.replay {
+              None => 1,
+              Some(_) => config.threads,
+            };
+            if *position >= config.mix.len() {
+              *position %= config.mix.len();
+            }
+            endpoint
+          }};
+        }
+
+        // the buffer for the response of the endpoint, only when it extracts values from it, it is checked or the script or the plugin handle it,
+        // with only the parts of the response they read
+        macro_rules! capture {
+          ($endpoint:ident) => {{
+            match $endpoint.extract.is_empty() && config.checks.is_empty() && !script_response && !plugin_observe {
+              true => None,
+              false => {
+                // Safety: the response is local to this connection and the previous one was already extracted
+                let response = unsafe { response.get_mut_unsafe() };
+                response.clear();
+                response.needs = $endpoint.extract.iter().fold(needs, |needs, extract| needs.union(extract.extractor.needs()));
+                Some(response)
+              }
+            }
+          }};
+        }
+
+        // binds the values extracted from the response of the endpoint to the variables of this connection,
+        // when one of them is missing the request fails and the session starts over, as the next steps would use stale values
+        macro_rules! extract {
+          ($endpoint:ident) => {{
+            let mut extracted = true;
+            // Safety: the response, the vars and the session are local to this connection and thread
+            let response = unsafe { response.get_mut_unsafe() };
+            let vars = unsafe { vars.get_mut_unsafe() };
+            for extract in $endpoint.extract {
+              match extract.extractor.extract(response) {
+                Some(value) => vars.set_capture(conn, extract.var, value),
+                None => extracted = false,
+              }
+            }
+
+            if !extracted {
+              unsafe { *session.get_mut_unsafe() = 0 };
+            }
+
+            extracted
+          }};
+        }
+
+        // runs all the checks on the response of the endpoint, counting the ones that failed, whether it passed all of them
+        macro_rules! check {
+          () => {{
+            let mut passed = true;
+            // Safety: the response and the results are local to this connection and thread
+            let response = unsafe { response.get_mut_unsafe() };
+            for (i, check) in config.checks.iter().enumerate() {
+              if !check.check(response) {
+                passed = false;
+                if *measuring.get() {
+                  unsafe { *result.get_mut_unsafe().checks.get_unchecked_mut(i) += 1 };
+                }
+              }
+            }
+
+            if *measuring.get() {
+              unsafe { result.get_mut_unsafe().checked += 1 };
+            }
+
+            passed
+          }};
+        }
+
+        // handles the response of the endpoint, checking it, binding the extracted values and calling the response hook of the script,
+        // the error is the kind of the one that failed the request
+        macro_rules! handle_response {
+          ($endpoint:ident) => {{
+            #[allow(unused_mut)]
+            let mut handled = if !config.checks.is_empty() && !check!() {
+              Err(crate::error::ErrorKind::Assertion)
+            } else if $endpoint.extract.is_empty() || extract!($endpoint) {
+              Ok(())
+            } else {
+              Err(crate::error::ErrorKind::Extract)
+            };
+
+            #[cfg(feature = "script")]
+            if let (Some(script), true, Ok(())) = (script.get(), script_response, &handled) {
+              // Safety: the response is local to this connection and it was just read
+              if script.response(unsafe { response.get_mut_unsafe() }).is_err() {
+                handled = Err(crate::error::ErrorKind::Script);
+              }
+            }
+
+            handled
+          }};
+        }
+
+        // with connection stages, whether this connection is one of the active ones right now
+        macro_rules! is_active {
+          () => {
+            match config.connections {
+              None => true,
+              Some(connections) => connections.value_at(started.elapsed()) > conn_index,
+            }
+          };
+        }
 
         'conn: loop {
+          // with connection stages waits until this connection becomes one of the active ones
+          if let Some(connections) = config.connections {
+            while !is_active!() {
+              match connections.time_above(conn_index, started.elapsed()) {
+                Some(at) => crate::rt::sleep_until(started + at).await,
+                // the connection will never be active again
+                None => std::future::pending().await,
+              }
+            }
+          }
+
+          // in constant-throughput mode waits for the next slot of the timeline before sending a request
+          // and evaluates to the intended send time of the request
+          macro_rules! wait_slot {
+            () => {
+              // Safety: the schedule is local to this thread, so is not possible to race
+              match unsafe { schedule.get_mut_unsafe() } {
+                None => None,
+                Some(schedule) => {
+                  let at = match schedule.next_slot() {
+                    Some(offset) => started + offset,
+                    // the rate dropped to zero for good, there is nothing left to send
+                    None => std::future::pending().await,
+                  };
+
+                  let now = Instant::now();
+                  let (intended, late) = if at > now {
+                    crate::rt::sleep_until(at).await;
+                    // the timers wake up to a millisecond late, that delay is not caused by the server
+                    // so a request that waited for its slot counts from the moment it was actually sent
+                    (Instant::now(), false)
+                  } else {
+                    (at, now - at > LATE_TOLERANCE)
+                  };
+
+                  if *measuring.get() {
+                    unsafe {
+                      result.get_mut_unsafe().sent += 1;
+                      if late {
+                        result.get_mut_unsafe().late += 1;
+                      }
+                    }
+
+                    if let Some(interval) = config.interval {
+                      // the requests sent right before the stop signal arrives count in the last interval
+                      let i = (started.elapsed().saturating_sub(config.warmup).as_nanos() / interval.as_nanos()) as usize;
+                      let i = match config.duration {
+                        Some(duration) => i.min((duration.as_nanos().div_ceil(interval.as_nanos()) as usize).saturating_sub(1)),
+                        None => i,
+                      };
+                      let intervals = unsafe { &mut result.get_mut_unsafe().intervals };
+                      if intervals.len() <= i {
+                        intervals.resize(i + 1, 0);
+                      }
+                      intervals[i] += 1;
+                    }
+                  }
+
+                  Some(intended)
+                }
+              }
+            };
+          }
+
+          // with a fixed number of requests takes one of the remaining ones before sending a request,
+          // when there are none left this connection is done, requests sent during the warm-up are not counted
+          macro_rules! claim {
+            ($measured:ident) => {
+              if let (Some(requests), true) = (requests, $measured) {
+                if requests.unsent.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1)).is_err() {
+                  break 'conn;
+                }
+              }
+            };
+          }
+
+          // records a failed request, that completes the request it took so a failing server doesn't keep the run going
+          macro_rules! fail {
+            ($measured:ident, $err:expr, $report:ident) => {{
+              record_err!($err, $report);
+              complete!($measured);
+            }};
+          }
+
+          // fails a request that was never sent, nothing was awaited for it, so without yielding
+          // a request that keeps failing before being sent would never give the other tasks of the thread a chance to run
+          macro_rules! fail_yield {
+            ($measured:ident, $err:expr, $report:ident) => {{
+              fail!($measured, $err, $report);
+              crate::rt::yield_now().await;
+            }};
+          }
+
+          // records a failed connection attempt, that takes one of the remaining requests as it would have sent it
+          macro_rules! fail_conn {
+            ($err:expr) => {{
+              let measured = *measuring.get();
+              claim!(measured);
+              record_err!($err);
+              complete!(measured);
+            }};
+          }
+
           #[cfg(feature = "h1")]
           macro_rules! send_h1_requests {
-            ($stream:ident, $buf:ident) => {{
+            ($stream:ident) => {{
               'req: loop {
+                if !is_active!() {
+                  continue 'conn;
+                }
+
+                // a thread stopped by its script sends no more requests, and the delay hook of the script waits before each one
+                #[cfg(feature = "script")]
+                if let Some(script) = script.get() {
+                  if script.is_stopped() {
+                    break 'conn;
+                  }
+
+                  if let Some(delay) = script.delay() {
+                    crate::rt::sleep(delay).await;
+                  }
+                }
+
+                #[cfg_attr(not(feature = "latency"), allow(unused_variables))]
+                let intended = wait_slot!();
+                let measured = *measuring.get();
+                claim!(measured);
+
+                let endpoint = next_endpoint!();
+                let report = endpoint.report;
+
+                // the request hook of the script replaces the request of the endpoint, unless it returns nil
+                #[cfg(feature = "script")]
+                let scripted = match script.get().as_ref().map(|script| script.request()).transpose().ok() {
+                  Some(scripted) => scripted.flatten(),
+                  None => {
+                    fail_yield!(measured, ErrorKind::Script, report);
+                    continue 'req;
+                  }
+                };
+
+                #[cfg(feature = "script")]
+                let scripted = scripted.map(|scripted| {
+                  // Safety: the buffer is local to this connection and the previous request was already sent
+                  let buf = unsafe { rendered.get_mut_unsafe() };
+                  scripted.write_h1(config.url, !config.disable_keepalive, buf);
+                  &buf[..]
+                });
+                #[cfg(not(feature = "script"))]
+                let scripted = None;
+
+                // the plugin generates the request instead of the endpoint, unless it returns 0
+                #[cfg(feature = "plugin")]
+                let scripted = {
+                  // Safety: the plugin is local to this thread, the buffer is local to this connection and the previous request was already sent
+                  let generated = match unsafe { plugin.get_mut_unsafe() } {
+                    None => Ok(false),
+                    Some(plugin) => plugin.next(conn, unsafe { rendered.get_mut_unsafe() }),
+                  };
+
+                  match generated {
+                    Ok(true) => Some(&rendered.get()[..]),
+                    Ok(false) => scripted,
+                    #[allow(unused)]
+                    Err(e) => {
+                      fail_yield!(measured, e, report);
+                      continue 'req;
+                    }
+                  }
+                };
+
+                let buf: &'static [u8] = match (scripted, endpoint.request) {
+                  (Some(buf), _) => buf,
+                  (None, Request::H1 { buf, template: None }) => buf,
+                  (None, Request::H1 { template: Some(template), .. }) => {
+                    // Safety: the buffer and the vars are local to this connection and thread, and the previous request was already sent
+                    let buf = unsafe { rendered.get_mut_unsafe() };
+                    #[allow(unused)]
+                    if let Err(e) = template.render(unsafe { vars.get_mut_unsafe() }, conn, buf) {
+                      fail_yield!(measured, e, report);
+                      continue 'req;
+                    }
+                    buf
+                  }
+                  #[cfg(feature = "h2")]
+                  (None, Request::H2 { .. }) => unreachable!("all endpoints use the same protocol"),
+                };
+
+                // the latency passed to rload_observe of the plugin, measured even when the latencies are not reported
+                #[cfg(feature = "plugin")]
+                let sent = std::time::Instant::now();
+
                 #[cfg(feature = "latency")]
                 let start = {
                   if config.latency {
@@ -111,14 +754,16 @@ pub async fn thread_inner(
 
                 match crate::h1::send_request(
                   &mut $stream,
-                  $buf,
+                  buf,
                   !config.disable_keepalive,
 
                   #[cfg(feature = "status-detail")]
-                  unsafe { &mut result.get_mut_unsafe().statuses },
+                  unsafe { &mut result.get_mut_unsafe().endpoints.get_unchecked_mut(report).statuses },
                  
                   #[cfg(not(feature = "status-detail"))]
-                  unsafe { &mut result.get_mut_unsafe().not_ok_status },
+                  unsafe { &mut result.get_mut_unsafe().endpoints.get_unchecked_mut(report).not_ok_status },
+
+                  capture!(endpoint),
 
                   #[cfg(feature = "timeout")]
                   config.timeout,
@@ -126,17 +771,20 @@ pub async fn thread_inner(
                 .await
                 {
                   Ok(is_keepalive) => {
-                    unsafe {
-                      result.get_mut_unsafe().ok += 1;
+                    #[allow(unused_mut)]
+                    let mut handled = handle_response!(endpoint);
+
+                    #[cfg(feature = "plugin")]
+                    if let (true, Some(plugin), Ok(())) = (plugin_observe, unsafe { plugin.get_mut_unsafe() }, &handled) {
+                      // Safety: the plugin is local to this thread, the response is local to this connection and it was just read
+                      handled = plugin.observe(conn, unsafe { response.get_mut_unsafe() }.status, sent.elapsed());
                     }
-                    #[cfg(feature = "latency")]
-                    {
-                      if let Some(start) = start {
-                        let elapsed = start.elapsed().as_nanos();
-                        unsafe {
-                          // this will not fail, by ignoring the error instead of unwrapping we remove the branching from the code 
-                          let _ = result.get_mut_unsafe().hdr.record(elapsed as u64);
-                        }
+
+                    match handled {
+                      Ok(()) => record_ok!(measured, start, intended, report),
+                      #[allow(unused)]
+                      Err(e) => {
+                        fail!(measured, e, report);
                       }
                     }
 
@@ -148,18 +796,7 @@ pub async fn thread_inner(
                   }
                   #[allow(unused)]
                   Err(e) => {
-                    cfg_if::cfg_if! {
-                      if #[cfg(feature = "error-detail")] {
-                        unsafe {
-                          result.get_mut_unsafe().err.record(e);
-                        }
-                      } else {
-                        unsafe {
-                          result.get_mut_unsafe().err_count += 1;
-                        }
-                      }
-                    }
-
+                    fail!(measured, e, report);
                     continue 'conn;
                   }
                 }
@@ -169,29 +806,74 @@ pub async fn thread_inner(
 
           #[cfg(feature = "h2")]
           macro_rules! send_h2_requests {
-            ($stream:ident, $req:ident, $body:ident) => {{
-              let (mut h2, conn) = match crate::rt::h2::client::handshake($stream).await {
+            ($stream:ident) => {{
+              let (mut h2, connection) = match crate::rt::h2::client::handshake($stream).await {
                 Ok(pair) => pair,
                 Err(_) => {
-                  cfg_if::cfg_if! {
-                    if #[cfg(feature = "error-detail")] {
-                      unsafe {
-                        result.get_mut_unsafe().err.record(ErrorKind::H2Handshake);
-                      }
-                    } else {
-                      unsafe {
-                        result.get_mut_unsafe().err_count += 1;
-                      }
-                    }
-                  }
-
+                  fail_conn!(ErrorKind::H2Handshake);
                   continue 'conn;
                 }
               };
 
-              crate::rt::spawn(conn);
+              crate::rt::spawn(connection);
 
               'req: loop {
+                if !is_active!() {
+                  continue 'conn;
+                }
+
+                // a thread stopped by its script sends no more requests, and the delay hook of the script waits before each one
+                #[cfg(feature = "script")]
+                if let Some(script) = script.get() {
+                  if script.is_stopped() {
+                    break 'conn;
+                  }
+
+                  if let Some(delay) = script.delay() {
+                    crate::rt::sleep(delay).await;
+                  }
+                }
+
+                #[cfg_attr(not(feature = "latency"), allow(unused_variables))]
+                let intended = wait_slot!();
+                let measured = *measuring.get();
+                claim!(measured);
+
+                let endpoint = next_endpoint!();
+                let report = endpoint.report;
+                let (req, body, template) = match endpoint.request {
+                  Request::H2 { req, body, template } => (req, body, template),
+                  #[cfg(feature = "h1")]
+                  Request::H1 { .. } => unreachable!("all endpoints use the same protocol"),
+                };
+
+                // the request hook of the script replaces the request of the endpoint, unless it returns nil
+                #[cfg(feature = "script")]
+                let scripted = match script.get().as_ref().map(|script| script.request()).transpose().ok() {
+                  Some(scripted) => scripted.flatten().map(|scripted| scripted.to_h2(config.url)),
+                  None => {
+                    fail_yield!(measured, ErrorKind::Script, report);
+                    continue 'req;
+                  }
+                };
+
+                // the request of the script takes the place of the template
+                #[cfg(feature = "script")]
+                let template = template.filter(|_| scripted.is_none());
+
+                let templated = match template {
+                  None => None,
+                  // Safety: the vars are local to this thread and the reference is dropped before any await
+                  Some(template) => match template.render(unsafe { vars.get_mut_unsafe() }, conn) {
+                    Ok(templated) => Some(templated),
+                    #[allow(unused)]
+                    Err(e) => {
+                      fail_yield!(measured, e, report);
+                      continue 'req;
+                    }
+                  },
+                };
+
                 #[cfg(feature = "latency")]
                 let start = {
                   if config.latency {
@@ -203,13 +885,25 @@ pub async fn thread_inner(
 
                 match crate::h2::send_request(
                   h2,
-                  || ($req.clone(), $body.cloned()),
+                  || {
+                    #[cfg(feature = "script")]
+                    if let Some((req, body)) = &scripted {
+                      return (req.clone(), body.clone());
+                    }
+
+                    match &templated {
+                      None => (req.clone(), body.cloned()),
+                      Some((req, body)) => (req.clone(), body.clone()),
+                    }
+                  },
 
                   #[cfg(feature = "status-detail")]
-                  unsafe { &mut result.get_mut_unsafe().statuses },
+                  unsafe { &mut result.get_mut_unsafe().endpoints.get_unchecked_mut(report).statuses },
 
                   #[cfg(not(feature = "status-detail"))]
-                  unsafe { &mut result.get_mut_unsafe().not_ok_status },
+                  unsafe { &mut result.get_mut_unsafe().endpoints.get_unchecked_mut(report).not_ok_status },
+
+                  capture!(endpoint),
 
                   #[cfg(feature = "timeout")]
                   config.timeout,
@@ -218,18 +912,11 @@ pub async fn thread_inner(
                 {
                   Ok(sender) => {
                     h2 = sender;
-                    unsafe {
-                      result.get_mut_unsafe().ok += 1;
-                    }
-
-                    #[cfg(feature = "latency")]
-                    {
-                      if let Some(start) = start {
-                        let elapsed = start.elapsed().as_nanos();
-                        unsafe {
-                          // this will not fail, by ignoring the error instead of unwrapping we remove the branching from the code
-                          let _ = result.get_mut_unsafe().hdr.record(elapsed as u64);
-                        }
+                    match handle_response!(endpoint) {
+                      Ok(()) => record_ok!(measured, start, intended, report),
+                      #[allow(unused)]
+                      Err(e) => {
+                        fail!(measured, e, report);
                       }
                     }
 
@@ -242,18 +929,7 @@ pub async fn thread_inner(
 
                   #[allow(unused)]
                   Err(e) => {
-                    cfg_if::cfg_if! {
-                      if #[cfg(feature = "error-detail")] {
-                        unsafe {
-                          result.get_mut_unsafe().err.record(e);
-                        }
-                      } else {
-                        unsafe {
-                          result.get_mut_unsafe().err_count += 1;
-                        }
-                      }
-                    }
-
+                    fail!(measured, e, report);
                     continue 'conn;
                   }
                 }
@@ -267,18 +943,7 @@ pub async fn thread_inner(
               match $inner.await {
                 Ok(stream) => stream,
                 Err(_) => {
-                  cfg_if::cfg_if! {
-                    if #[cfg(feature = "error-detail")] {
-                      unsafe {
-                        result.get_mut_unsafe().err.record(ErrorKind::$err);
-                      }
-                    } else {
-                      unsafe {
-                        result.get_mut_unsafe().err_count += 1;
-                      }
-                    }
-                  }
-
+                  fail_conn!(ErrorKind::$err);
                   continue 'conn;
                 }
               }
@@ -288,18 +953,7 @@ pub async fn thread_inner(
                 None => match $inner.await {
                   Ok(stream) => stream,
                   Err(_) => {
-                    cfg_if::cfg_if! {
-                      if #[cfg(feature = "error-detail")] {
-                        unsafe {
-                          result.get_mut_unsafe().err.record(ErrorKind::$err);
-                        }
-                      } else {
-                        unsafe {
-                          result.get_mut_unsafe().err_count += 1;
-                        }
-                      }
-                    }
-
+                    fail_conn!(ErrorKind::$err);
                     continue 'conn;
                   }
                 }
@@ -308,33 +962,11 @@ pub async fn thread_inner(
                   match pingora_timeout::timeout(timeout, $inner).await {
                     Ok(Ok(stream)) => stream,
                     Ok(Err(_)) => {
-                      cfg_if::cfg_if! {
-                        if #[cfg(feature = "error-detail")] {
-                          unsafe {
-                            result.get_mut_unsafe().err.record(ErrorKind::$err);
-                          }
-                        } else {
-                          unsafe {
-                            result.get_mut_unsafe().err_count += 1;
-                          }
-                        }
-                      }
-
+                      fail_conn!(ErrorKind::$err);
                       continue 'conn;
                     }
                     Err(_) => {
-                      cfg_if::cfg_if! {
-                        if #[cfg(feature = "error-detail")] {
-                          unsafe {
-                            result.get_mut_unsafe().err.record(ErrorKind::Timeout);
-                          }
-                        } else {
-                          unsafe {
-                            result.get_mut_unsafe().err_count += 1;
-                          }
-                        }
-                      }
-
+                      fail_conn!(ErrorKind::Timeout);
                       continue 'conn;
                     }
                   }
@@ -343,7 +975,7 @@ pub async fn thread_inner(
             }};
           }
 
-          let stream = timeout!(crate::rt::TcpStream::connect(config.addr), Connect);
+          let stream = timeout!(crate::rt::TcpStream::connect(addr), Connect);
 
           // Safety: this conters are local to this thread, so is not possible to race
           #[allow(unused_mut)]
@@ -360,14 +992,14 @@ pub async fn thread_inner(
           let tls = Option::<std::convert::Infallible>::None;
 
           match tls {
-            None => match config.request {
+            None => match config.endpoints[0].request {
               #[cfg(feature = "h1")]
-              Request::H1 { buf } => {
-                send_h1_requests!(stream, buf);
+              Request::H1 { .. } => {
+                send_h1_requests!(stream);
               }
               #[cfg(feature = "h2")]
-              Request::H2 { req, body } => {
-                send_h2_requests!(stream, req, body);
+              Request::H2 { .. } => {
+                send_h2_requests!(stream);
               }
             },
 
@@ -380,11 +1012,11 @@ pub async fn thread_inner(
               #[allow(unused_mut)]
               let mut stream = timeout!(tls.connector.connect(tls.server_name.clone(), stream), TlsHandshake);
 
-              match config.request {
+              match config.endpoints[0].request {
                 #[cfg(feature = "h1")]
-                Request::H1 { buf } => send_h1_requests!(stream, buf),
+                Request::H1 { .. } => send_h1_requests!(stream),
                 #[cfg(feature = "h2")]
-                Request::H2 { req, body } => send_h2_requests!(stream, req, body),
+                Request::H2 { .. } => send_h2_requests!(stream),
               }
             }
           }
@@ -421,5 +1053,42 @@ pub async fn thread_inner(
 
   unleak!(result);
 
+  // the statuses are recorded for each endpoint, the totals are joined from them
+  let mut result = result;
+  for i in 0..result.endpoints.len() {
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "status-detail")] {
+        let statuses = result.endpoints[i].statuses.clone();
+        result.statuses.join(statuses);
+      } else {
+        result.not_ok_status += result.endpoints[i].not_ok_status;
+      }
+    }
+  }
+
+  // Safety: all the tasks that borrowed the schedule, the position, the vars, the rendered buffers, the sessions and the responses are finished
+  drop(unsafe { Box::from_raw(schedule.get_mut_ptr()) });
+  drop(unsafe { Box::from_raw(position.get_mut_ptr()) });
+  drop(unsafe { Box::from_raw(vars.get_mut_ptr()) });
+  drop(unsafe { Box::from_raw(rendered.get_mut_ptr()) });
+  drop(unsafe { Box::from_raw(sessions.get_mut_ptr()) });
+  drop(unsafe { Box::from_raw(responses.get_mut_ptr()) });
+  #[cfg(feature = "plugin")]
+  {
+    let plugin = unsafe { Box::from_raw(plugin.get_mut_ptr()) };
+    if let Some(plugin) = plugin.as_ref() {
+      result.plugin = plugin.stats;
+    }
+  }
+  #[cfg(feature = "script")]
+  {
+    let script = unsafe { Box::from_raw(script.get_mut_ptr()) };
+    if let Some(script) = script.as_ref() {
+      result.globals = script.globals();
+    }
+  }
+
+  // the measuring flag is not freed as the warm-up task could still be pending if the run was stopped during the warm-up
+
   result
 }
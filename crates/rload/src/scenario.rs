@@ -0,0 +1,132 @@
+use anyhow::Context;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::{
+  endpoint::{Body, EndpointSpec},
+  extract::Extractor,
+};
+
+/// A step of a scenario, a request and the values extracted from its response
+#[derive(Debug, Clone)]
+pub struct Step {
+  pub spec: EndpointSpec,
+  /// the extractors by the name of the variable they bind
+  pub extract: Vec<(String, Extractor)>,
+}
+
+/// Loads the steps of a scenario file, in JSON or (with feature config) YAML, that each connection sends in order, starting over after the last one
+///
+/// The file is a list of steps or an object with them in `steps`, each one with a `method` (GET by default), a `path`,
+/// `headers` as an object or a list of "key: value", a `body` (use @filename to read it from a file relative to the scenario)
+/// and `extract`, the variables to bind from the response for the next steps (eg: `{"token": {"json": "$.token"}}`),
+/// with one of `json` (a JSONPath), `regex` (on the body, its first group), `header` or `cookie` each.
+/// A step is named in the report by its `name`, by default its method and path
+pub fn load(path: &Path) -> Result<Vec<Step>, anyhow::Error> {
+  let content = std::fs::read_to_string(path).with_context(|| format!("error reading scenario file {}", path.display()))?;
+
+  let json: Value = match path.extension().and_then(|ext| ext.to_str()) {
+    #[cfg(any(feature = "config", feature = "openapi"))]
+    Some("yaml" | "yml") => serde_yaml::from_str(&content).with_context(|| format!("error parsing scenario file {}", path.display()))?,
+    #[cfg(not(any(feature = "config", feature = "openapi")))]
+    Some("yaml" | "yml") => anyhow::bail!("feature config must be enabled at compile time to use YAML scenario files, not enabled"),
+    _ => serde_json::from_str(&content).with_context(|| format!("error parsing scenario file {}", path.display()))?,
+  };
+
+  let steps = match json.get("steps").unwrap_or(&json) {
+    Value::Array(steps) if !steps.is_empty() => steps,
+    Value::Array(_) => anyhow::bail!("scenario file {} has no steps", path.display()),
+    _ => anyhow::bail!("invalid scenario file {}, must be a list of steps or an object with a list of steps", path.display()),
+  };
+
+  let dir = path.parent().unwrap_or(Path::new(""));
+
+  steps
+    .iter()
+    .enumerate()
+    .map(|(i, step)| parse_step(step, dir).with_context(|| format!("error parsing scenario file {} step {}", path.display(), i + 1)))
+    .collect()
+}
+
+fn parse_step(step: &Value, dir: &Path) -> Result<Step, anyhow::Error> {
+  let Value::Object(step) = step else {
+    anyhow::bail!("step must be an object with a method, a path and optionally a name, headers, a body and extract");
+  };
+
+  for key in step.keys() {
+    if !["name", "method", "path", "headers", "body", "extract"].contains(&key.as_str()) {
+      anyhow::bail!("unknown key {key}, must be one of name, method, path, headers, body or extract");
+    }
+  }
+
+  let string = |key: &str| -> Result<Option<String>, anyhow::Error> {
+    match step.get(key) {
+      None | Some(Value::Null) => Ok(None),
+      Some(Value::String(value)) => Ok(Some(value.clone())),
+      Some(_) => anyhow::bail!("{key} must be a string"),
+    }
+  };
+
+  let method = string("method")?.unwrap_or_else(|| String::from("GET"));
+  let target = string("path")?.context("missing path")?;
+
+  let headers = match step.get("headers") {
+    None | Some(Value::Null) => Vec::new(),
+    Some(Value::Object(map)) => map
+      .iter()
+      .map(|(key, value)| match value {
+        Value::String(value) => Ok(format!("{key}:{value}")),
+        Value::Number(_) | Value::Bool(_) => Ok(format!("{key}:{value}")),
+        _ => anyhow::bail!("header {key} must be a string"),
+      })
+      .collect::<Result<_, _>>()?,
+    Some(Value::Array(list)) => list
+      .iter()
+      .map(|header| header.as_str().map(String::from).context("headers must be a list of key:value strings"))
+      .collect::<Result<_, _>>()?,
+    Some(_) => anyhow::bail!("headers must be an object or a list of key:value strings"),
+  };
+
+  // the files of the bodies are relative to the scenario file
+  let body = match step.get("body") {
+    None | Some(Value::Null) => None,
+    Some(Value::String(body)) => Some(match body.strip_prefix('@') {
+      Some(file) => Body::File(dir.join(file).display().to_string()),
+      None => Body::Bytes(body.clone().into_bytes()),
+    }),
+    // a structured body is sent as JSON
+    Some(body) => Some(Body::Bytes(body.to_string().into_bytes())),
+  };
+
+  let mut extract = Vec::new();
+  match step.get("extract") {
+    None | Some(Value::Null) => {}
+    Some(Value::Object(map)) => {
+      for (var, value) in map {
+        let extractor = match value.as_object().map(|extractor| extractor.iter().collect::<Vec<_>>()).as_deref() {
+          Some([(kind, Value::String(expr))]) => crate::extract::parse_extractor(kind, expr)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("invalid extractor for {var}"))?,
+          _ => anyhow::bail!("extractor for {var} must be an object with one of json, regex, header or cookie (eg: {{\"json\": \"$.token\"}})"),
+        };
+        extract.push((var.clone(), extractor));
+      }
+    }
+    Some(_) => anyhow::bail!("extract must be an object of variable names to extractors"),
+  }
+
+  let name = string("name")?.unwrap_or_else(|| format!("{} {}", method.trim().to_uppercase(), target));
+
+  Ok(Step {
+    spec: EndpointSpec {
+      weight: 1,
+      name: Some(name),
+      method,
+      target,
+      body,
+      headers,
+      template: true,
+    },
+    extract,
+  })
+}
@@ -0,0 +1,73 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::time::Duration;
+
+use crate::{distribution::Distribution, profile::Profile};
+
+/// Requests sent later than this after their intended send time are counted as late
+///
+/// The runtime timers have a millisecond resolution, so anything below this is just timer jitter
+pub const LATE_TOLERANCE: Duration = Duration::from_millis(1);
+
+/// The timeline of intended send times of one thread in constant-throughput mode
+///
+/// All threads share the same global timeline where the n-th request is due when the integral of the rate profile reaches `n`
+/// (`n / rate` seconds after the start of the run for a constant rate), and each thread takes every `threads`-th slot of it starting at its own index
+///
+/// With a random distribution each thread instead draws the gaps between its slots from it, with a mean gap of `threads` slots,
+/// so all the threads together still follow the rate profile
+#[derive(Debug, Clone)]
+pub struct Schedule {
+  rate: &'static Profile,
+  distribution: Distribution,
+  rng: SmallRng,
+  step: f64,
+  next: f64,
+  cursor: usize,
+}
+
+impl Schedule {
+  /// Each thread seeds its own generator from `seed` and its index, so a run with the same seed repeats the same arrival times
+  pub fn new(rate: &'static Profile, distribution: Distribution, seed: u64, threads: usize, index: usize) -> Self {
+    let mut schedule = Self {
+      rate,
+      distribution,
+      rng: SmallRng::seed_from_u64(seed.wrapping_add(index as u64)),
+      step: threads as f64,
+      next: index as f64,
+      cursor: 0,
+    };
+
+    // a Poisson process has no fixed origin, so the first arrival is also drawn
+    if distribution == Distribution::Poisson {
+      schedule.next = schedule.gap();
+    }
+
+    schedule
+  }
+
+  /// Returns the offset from the start of the run at which the next request of this thread is due
+  ///
+  /// Returns `None` if the rate drops to zero and no more requests are due
+  #[inline(always)]
+  pub fn next_slot(&mut self) -> Option<Duration> {
+    let offset = self.rate.time_of(self.next, &mut self.cursor);
+    self.next += self.gap();
+    offset.map(|offset| self.distribution.warp(offset))
+  }
+
+  /// The number of global slots until the next request of this thread
+  #[inline(always)]
+  fn gap(&mut self) -> f64 {
+    match self.distribution {
+      Distribution::Constant | Distribution::Bursty { .. } => self.step,
+      // random::<f64>() is in [0, 1) so the logarithm is always finite
+      Distribution::Poisson => -(1.0 - self.rng.random::<f64>()).ln() * self.step,
+      Distribution::Uniform(jitter) => self.step * (1.0 + jitter * (2.0 * self.rng.random::<f64>() - 1.0)),
+    }
+  }
+}
+
+/// The number of requests that should have been sent in `elapsed` following the `rate` profile
+pub fn expected(rate: &Profile, distribution: Distribution, elapsed: Duration) -> u64 {
+  rate.integral(distribution.unwarp(elapsed)).ceil() as u64
+}
@@ -0,0 +1,914 @@
+use anyhow::Context;
+use mlua::{Function, Lua, Table, Value};
+use std::{
+  cell::{Cell, RefCell},
+  collections::HashMap,
+  net::{SocketAddr, ToSocketAddrs},
+  rc::Rc,
+  str::FromStr,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use tokio::sync::Notify;
+use url::Url;
+
+use crate::{
+  extract::Response,
+  output::{Stats, WrkErrors},
+  report::Report,
+};
+
+/// The wrk table of the scripts of wrk, adapted from its wrk.lua, the fields of the request are set from the command line
+const WRK: &str = r#"
+local wrk = {
+  scheme  = "http",
+  host    = "localhost",
+  port    = nil,
+  method  = "GET",
+  path    = "/",
+  headers = {},
+  body    = nil,
+  thread  = nil,
+}
+
+function wrk.init(args)
+  if not wrk.headers["Host"] then
+    local host = wrk.host
+    local port = wrk.port
+    host = host:find(":") and ("[" .. host .. "]") or host
+    host = port and (host .. ":" .. port) or host
+    wrk.headers["Host"] = host
+  end
+
+  if type(init) == "function" then
+    init(args)
+  end
+
+  local req = wrk.format()
+  wrk.request = function()
+    return req
+  end
+end
+
+function wrk.format(method, path, headers, body)
+  local method  = method  or wrk.method
+  local path    = path    or wrk.path
+  local headers = headers or wrk.headers
+  local body    = body    or wrk.body
+  local s       = {}
+
+  if not headers["Host"] then
+    headers["Host"] = wrk.headers["Host"]
+  end
+
+  headers["Content-Length"] = body and string.len(body)
+
+  s[1] = string.format("%s %s HTTP/1.1", method, path)
+  for name, value in pairs(headers) do
+    s[#s+1] = string.format("%s: %s", name, value)
+  end
+
+  s[#s+1] = ""
+  s[#s+1] = body or ""
+
+  return table.concat(s, "\r\n")
+end
+
+return wrk
+"#;
+
+/// The wrk.thread of each thread, its get and set access the globals of the thread
+const WRK_THREAD: &str = r#"
+return {
+  get = function(self, name) return _G[name] end,
+  set = function(self, name, value) _G[name] = value end,
+}
+"#;
+
+/// The metatable of the headers of the responses, so they can also be looked up by a name that is not in lowercase
+const HEADERS: &str = r#"
+return {
+  __index = function(headers, name)
+    if type(name) == "string" then
+      return rawget(headers, name:lower())
+    end
+  end,
+}
+"#;
+
+/// The api of a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ScriptApi {
+  /// the hooks of rload, request() returns a table and done(report) is called with the results of rload
+  #[default]
+  Rload,
+  /// the api of wrk, to run the scripts of wrk unmodified
+  Wrk,
+}
+
+impl std::fmt::Display for ScriptApi {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ScriptApi::Rload => write!(f, "rload"),
+      ScriptApi::Wrk => write!(f, "wrk"),
+    }
+  }
+}
+
+/// The request of the command line, the initial values of the wrk table of a script with the api of wrk
+#[derive(Debug, Clone)]
+pub struct WrkRequest {
+  pub scheme: String,
+  pub host: String,
+  /// the port of the url, `None` for the default one of the scheme
+  pub port: Option<u16>,
+  pub method: String,
+  /// the path and the query
+  pub path: String,
+  pub headers: Vec<(String, String)>,
+  pub body: Option<Vec<u8>>,
+  /// the address the threads connect to, unless setup(thread) sets another one
+  pub addr: SocketAddr,
+}
+
+/// A value copied from an interpreter to another, like the ones set with thread:set(name, value) in setup(thread)
+#[derive(Debug, Clone)]
+pub enum Global {
+  Boolean(bool),
+  Integer(i64),
+  Number(f64),
+  String(Vec<u8>),
+  Table(Vec<(Global, Global)>),
+}
+
+/// The threads stopped by their script with wrk.thread:stop(), the run ends when all of them are stopped
+#[derive(Debug)]
+pub struct Stops {
+  threads: usize,
+  stopped: AtomicUsize,
+  /// notified once when the last thread stops
+  pub all: Notify,
+}
+
+impl Stops {
+  fn stop(&self) {
+    if self.stopped.fetch_add(1, Ordering::AcqRel) + 1 == self.threads {
+      self.all.notify_one();
+    }
+  }
+}
+
+/// The interpreter of the main thread, it checks the script before the test starts, runs setup(thread) with the api of wrk
+/// and runs the done hook after the run
+struct MainState {
+  lua: Lua,
+  /// the globals of each thread read with thread:get(name), the ones set by setup until the thread ends
+  threads: Rc<RefCell<Vec<HashMap<String, Global>>>>,
+}
+
+// Safety: the main interpreter is only used by the main thread, before the threads start and after all of them ended
+unsafe impl Send for MainState {}
+unsafe impl Sync for MainState {}
+
+/// The path and the source of a lua script, each thread loads its own interpreter from it
+pub struct ScriptSource {
+  pub path: String,
+  pub source: String,
+  /// the arguments given after --, passed to init(args) with the api of wrk or as the global args
+  pub args: Vec<String>,
+  /// the request of the command line with the api of wrk, `None` with the api of rload
+  pub wrk: Option<WrkRequest>,
+  /// the globals set by setup(thread) for each thread
+  pub setup: Vec<Vec<(String, Global)>>,
+  /// the address each thread connects to, set by setup(thread) with the api of wrk
+  pub addrs: Vec<SocketAddr>,
+  pub stops: Arc<Stops>,
+  main: MainState,
+}
+
+fn hook(lua: &Lua, path: &str, name: &str) -> Result<Option<Function>, anyhow::Error> {
+  lua
+    .globals()
+    .get::<Option<Function>>(name)
+    .map_err(lua_error)
+    .with_context(|| format!("invalid script {path}, {name} must be a function"))
+}
+
+impl ScriptSource {
+  /// Reads a script and runs it once, so its errors are reported before the test starts, with the api of wrk
+  /// it also runs setup(thread) for each of the `threads`
+  pub fn load(path: &str, args: Vec<String>, threads: usize, wrk: Option<WrkRequest>) -> Result<Self, anyhow::Error> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("error reading script {path}"))?;
+
+    let lua = Lua::new();
+    let states = Rc::new(RefCell::new(vec![HashMap::<String, Global>::new(); threads]));
+
+    match &wrk {
+      None => {
+        lua.globals().set("thread_id", 0).map_err(lua_error)?;
+        lua.globals().set("args", lua.create_sequence_from(args.iter().map(String::as_str)).map_err(lua_error)?).map_err(lua_error)?;
+      }
+      Some(request) => {
+        wrk_table(&lua, request).map_err(lua_error)?;
+      }
+    }
+
+    lua
+      .load(&source)
+      .set_name(format!("@{path}"))
+      .exec()
+      .map_err(lua_error)
+      .with_context(|| format!("error running script {path}"))?;
+
+    for name in ["request", "response", "delay", "done"] {
+      hook(&lua, path, name)?;
+    }
+
+    let mut addrs = Vec::new();
+    if let Some(request) = &wrk {
+      let setup = hook(&lua, path, "setup")?;
+      for i in 0..threads {
+        let thread = setup_thread(&lua, &states, i, request.addr).map_err(lua_error)?;
+        if let Some(setup) = &setup {
+          setup
+            .call::<()>(&thread)
+            .map_err(lua_error)
+            .with_context(|| format!("error in the setup hook of script {path}"))?;
+        }
+
+        let addr = thread.get::<String>("addr").map_err(lua_error)?;
+        let addr = addr
+          .parse::<SocketAddr>()
+          .with_context(|| format!("invalid address {addr} set by the setup hook of script {path}"))?;
+        addrs.push(addr);
+      }
+    }
+
+    let setup = states
+      .borrow()
+      .iter()
+      .map(|globals| globals.iter().map(|(name, value)| (name.clone(), value.clone())).collect())
+      .collect();
+
+    Ok(ScriptSource {
+      path: path.to_string(),
+      source,
+      args,
+      wrk,
+      setup,
+      addrs,
+      stops: Arc::new(Stops {
+        threads,
+        stopped: AtomicUsize::new(0),
+        all: Notify::new(),
+      }),
+      main: MainState { lua, threads: states },
+    })
+  }
+
+  /// The address a thread connects to, when it was set by setup(thread)
+  pub fn addr(&self, thread: usize) -> Option<SocketAddr> {
+    self.addrs.get(thread).copied()
+  }
+}
+
+/// Sets the wrk table with the request of the command line, and its functions lookup(host, service) and connect(addr)
+fn wrk_table(lua: &Lua, request: &WrkRequest) -> Result<Table, mlua::Error> {
+  let wrk: Table = lua.load(WRK).set_name("=wrk").eval()?;
+  wrk.set("scheme", request.scheme.as_str())?;
+  wrk.set("host", request.host.as_str())?;
+  wrk.set("port", request.port.map(|port| port.to_string()))?;
+  wrk.set("method", request.method.as_str())?;
+  wrk.set("path", request.path.as_str())?;
+
+  let headers = lua.create_table()?;
+  for (name, value) in &request.headers {
+    headers.set(name.as_str(), value.as_str())?;
+  }
+  wrk.set("headers", headers)?;
+
+  if let Some(body) = &request.body {
+    wrk.set("body", lua.create_string(body)?)?;
+  }
+
+  // the addresses are strings like 127.0.0.1:8080
+  wrk.set("addrs", lua.create_sequence_from([request.addr.to_string()])?)?;
+  wrk.set(
+    "lookup",
+    lua.create_function(|lua, (host, service): (String, String)| {
+      let port = match service.as_str() {
+        "http" => 80,
+        "https" => 443,
+        port => port.parse::<u16>().map_err(|_| mlua::Error::runtime(format!("unable to resolve {host}:{service}, invalid port")))?,
+      };
+
+      let addrs = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| mlua::Error::runtime(format!("unable to resolve {host}:{service} {e}")))?;
+      lua.create_sequence_from(addrs.map(|addr| addr.to_string()))
+    })?,
+  )?;
+  wrk.set(
+    "connect",
+    lua.create_function(|_, addr: String| {
+      Ok(addr
+        .parse::<SocketAddr>()
+        .is_ok_and(|addr| std::net::TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok()))
+    })?,
+  )?;
+
+  lua.globals().set("wrk", &wrk)?;
+  Ok(wrk)
+}
+
+/// The thread passed to setup(thread) in the main interpreter, thread:set(name, value) sets a global of the thread before it starts
+/// and thread:get(name) reads it, after the run it reads the value the global had at the end of the thread
+fn setup_thread(lua: &Lua, states: &Rc<RefCell<Vec<HashMap<String, Global>>>>, i: usize, addr: SocketAddr) -> Result<Table, mlua::Error> {
+  let thread = lua.create_table()?;
+  thread.set("addr", addr.to_string())?;
+
+  let get = states.clone();
+  thread.set(
+    "get",
+    lua.create_function(move |lua, (_, name): (Value, String)| match get.borrow()[i].get(&name) {
+      Some(value) => to_lua(lua, value),
+      None => Ok(Value::Nil),
+    })?,
+  )?;
+
+  let set = states.clone();
+  thread.set(
+    "set",
+    lua.create_function(move |_, (_, name, value): (Value, String, Value)| {
+      let value = match value {
+        Value::Nil => None,
+        value => Some(to_global(&value, 0).ok_or_else(|| {
+          mlua::Error::runtime(format!("thread:set({name}) can only set booleans, numbers, strings and tables of them, not a {}", value.type_name()))
+        })?),
+      };
+
+      match value {
+        Some(value) => set.borrow_mut()[i].insert(name, value),
+        None => set.borrow_mut()[i].remove(&name),
+      };
+
+      Ok(())
+    })?,
+  )?;
+
+  // the threads are not running yet
+  thread.set("stop", lua.create_function(|_, _: Value| Ok(()))?)?;
+
+  Ok(thread)
+}
+
+/// The maximum depth of the tables copied between interpreters, the deeper ones and the cycles are cut
+const MAX_DEPTH: usize = 16;
+
+/// Copies a value out of an interpreter, `None` for the values that can't be copied, like functions
+fn to_global(value: &Value, depth: usize) -> Option<Global> {
+  let global = match value {
+    Value::Boolean(value) => Global::Boolean(*value),
+    Value::Integer(value) => Global::Integer(*value),
+    Value::Number(value) => Global::Number(*value),
+    Value::String(value) => Global::String(value.as_bytes().to_vec()),
+    Value::Table(table) if depth < MAX_DEPTH => Global::Table(
+      table
+        .pairs::<Value, Value>()
+        .filter_map(|pair| {
+          let (key, value) = pair.ok()?;
+          Some((to_global(&key, depth + 1)?, to_global(&value, depth + 1)?))
+        })
+        .collect(),
+    ),
+    _ => return None,
+  };
+
+  Some(global)
+}
+
+fn to_lua(lua: &Lua, global: &Global) -> Result<Value, mlua::Error> {
+  let value = match global {
+    Global::Boolean(value) => Value::Boolean(*value),
+    Global::Integer(value) => Value::Integer(*value),
+    Global::Number(value) => Value::Number(*value),
+    Global::String(value) => Value::String(lua.create_string(value)?),
+    Global::Table(pairs) => {
+      let table = lua.create_table()?;
+      for (key, value) in pairs {
+        table.set(to_lua(lua, key)?, to_lua(lua, value)?)?;
+      }
+      Value::Table(table)
+    }
+  };
+
+  Ok(value)
+}
+
+/// A lua script loaded by a thread, the hooks of each thread run in its own interpreter, without locks
+///
+/// The script can define any of this global functions:
+/// - `request()` is called before each request, it returns a table with the `method` (GET by default), the `path`
+///   with the query (the one of the url by default), the `headers` as a table of names to values and the `body` of the request to send,
+///   or nil to send the request of the endpoint
+/// - `response(status, headers, body)` is called after each response, with the names of the headers in lowercase
+/// - `delay()` is called before each request, it returns the milliseconds to wait before sending it
+/// - `done(report)` is called once after the run with the results, in the interpreter of the main thread
+///
+/// The global `thread_id` is the index of the thread that loaded the script and `args` the arguments given after --.
+/// An error in a hook fails its request, only the first one of each thread is printed
+///
+/// With the api of wrk the script has the wrk table and the hooks `setup(thread)`, `init(args)`, `delay()`, `request()`
+/// that returns a raw request built with `wrk.format()`, `response(status, headers, body)` and `done(summary, latency, requests)`,
+/// as in wrk, only the addresses are strings
+pub struct Script {
+  lua: Lua,
+  request: Option<Function>,
+  /// the request of a script with the api of wrk without a request hook, the same one for all the requests
+  fixed: Option<Arc<[u8]>>,
+  response: Option<Function>,
+  delay: Option<Function>,
+  /// the metatable of the headers passed to the response hook
+  headers: Table,
+  /// the script uses the api of wrk
+  wrk: bool,
+  /// set by wrk.thread:stop()
+  stopped: Rc<Cell<bool>>,
+  /// the globals set before the script ran, they are not copied for thread:get(name)
+  builtin: Vec<String>,
+  failed: Cell<bool>,
+}
+
+// Safety: a script is only used and dropped by the thread that loaded it, the tasks of each thread run in its own
+// single-thread runtime, so the interpreter is never accessed from another thread
+unsafe impl Send for Script {}
+unsafe impl Sync for Script {}
+
+/// A request returned by the request hook, already validated
+#[derive(Debug, Clone)]
+pub enum ScriptRequest {
+  Table {
+    method: http::Method,
+    /// the path and query, `None` for the ones of the url
+    path: Option<http::uri::PathAndQuery>,
+    headers: Vec<(http::HeaderName, http::HeaderValue)>,
+    body: Option<Vec<u8>>,
+  },
+  /// a h1 request built by a script with the api of wrk, sent as it is
+  Raw(Arc<[u8]>),
+}
+
+fn lua_error(e: mlua::Error) -> anyhow::Error {
+  anyhow::Error::msg(e.to_string())
+}
+
+impl Script {
+  pub fn new(source: &ScriptSource, thread: usize) -> Result<Self, anyhow::Error> {
+    let lua = Lua::new();
+    let stopped = Rc::new(Cell::new(false));
+
+    match &source.wrk {
+      None => {
+        lua.globals().set("thread_id", thread).map_err(lua_error)?;
+        lua.globals().set("args", lua.create_sequence_from(source.args.iter().map(String::as_str)).map_err(lua_error)?).map_err(lua_error)?;
+      }
+      Some(request) => {
+        let wrk = wrk_table(&lua, request).map_err(lua_error)?;
+        let wrk_thread: Table = lua.load(WRK_THREAD).set_name("=wrk").eval().map_err(lua_error)?;
+        wrk_thread.set("addr", source.addrs[thread].to_string()).map_err(lua_error)?;
+        let (stops, flag) = (source.stops.clone(), stopped.clone());
+        let stop = lua
+          .create_function(move |_, _: Value| {
+            if !flag.replace(true) {
+              stops.stop();
+            }
+            Ok(())
+          })
+          .map_err(lua_error)?;
+        wrk_thread.set("stop", stop).map_err(lua_error)?;
+        wrk.set("thread", wrk_thread).map_err(lua_error)?;
+      }
+    }
+
+    let builtin = lua
+      .globals()
+      .pairs::<String, Value>()
+      .filter_map(|pair| pair.ok().map(|(name, _)| name))
+      .collect();
+
+    lua
+      .load(&source.source)
+      .set_name(format!("@{}", source.path))
+      .exec()
+      .map_err(lua_error)
+      .with_context(|| format!("error running script {}", source.path))?;
+
+    let mut fixed = None;
+    if source.wrk.is_some() {
+      for (name, value) in &source.setup[thread] {
+        lua.globals().set(name.as_str(), to_lua(&lua, value).map_err(lua_error)?).map_err(lua_error)?;
+      }
+
+      let args = lua.create_sequence_from(source.args.iter().map(String::as_str)).map_err(lua_error)?;
+      let wrk = lua.globals().get::<Table>("wrk").map_err(lua_error)?;
+      wrk
+        .get::<Function>("init")
+        .and_then(|init| init.call::<()>(args))
+        .map_err(lua_error)
+        .with_context(|| format!("error in the init hook of script {}", source.path))?;
+
+      // without a request hook all the requests are the one built by wrk.init()
+      if hook(&lua, &source.path, "request")?.is_none() {
+        let request = wrk
+          .get::<Function>("request")
+          .and_then(|request| request.call::<mlua::String>(()))
+          .map_err(lua_error)
+          .with_context(|| format!("error building the request of script {}", source.path))?;
+        fixed = Some(Arc::from(&request.as_bytes()[..]));
+      }
+    }
+
+    let request = hook(&lua, &source.path, "request")?;
+    let response = hook(&lua, &source.path, "response")?;
+    let delay = hook(&lua, &source.path, "delay")?;
+    let headers = lua.load(HEADERS).set_name("=headers").eval::<Table>().map_err(lua_error)?;
+
+    Ok(Script {
+      lua,
+      request,
+      fixed,
+      response,
+      delay,
+      headers,
+      wrk: source.wrk.is_some(),
+      stopped,
+      builtin,
+      failed: Cell::new(false),
+    })
+  }
+
+  pub fn has_response(&self) -> bool {
+    self.response.is_some()
+  }
+
+  /// The thread was stopped by the script with wrk.thread:stop()
+  pub fn is_stopped(&self) -> bool {
+    self.stopped.get()
+  }
+
+  /// The globals set by the script with the api of wrk, read with thread:get(name) in the done hook
+  pub fn globals(&self) -> Vec<(String, Global)> {
+    if !self.wrk {
+      return Vec::new();
+    }
+
+    self
+      .lua
+      .globals()
+      .pairs::<Value, Value>()
+      .filter_map(|pair| {
+        let (Value::String(name), value) = pair.ok()? else {
+          return None;
+        };
+        let name = name.to_str().ok()?.to_string();
+        if self.builtin.contains(&name) {
+          return None;
+        }
+        Some((name, to_global(&value, 0)?))
+      })
+      .collect()
+  }
+
+  /// Prints the first error of the hooks of this thread, the next ones are only counted
+  fn fail<T>(&self, hook: &str, e: mlua::Error) -> Result<T, mlua::Error> {
+    if !self.failed.replace(true) {
+      eprintln!("error in script hook {hook}: {e}");
+    }
+    Err(e)
+  }
+
+  /// Calls the request hook, `None` when the script has no request hook or it returned nil
+  pub fn request(&self) -> Result<Option<ScriptRequest>, mlua::Error> {
+    if let Some(fixed) = &self.fixed {
+      return Ok(Some(ScriptRequest::Raw(fixed.clone())));
+    }
+
+    let Some(hook) = &self.request else {
+      return Ok(None);
+    };
+
+    let parse = match self.wrk {
+      true => parse_raw,
+      false => parse_request,
+    };
+
+    match hook.call::<Value>(()).and_then(parse) {
+      Ok(req) => Ok(req),
+      Err(e) => self.fail("request", e),
+    }
+  }
+
+  /// Calls the response hook with a response, if the script has one
+  pub fn response(&self, res: &Response) -> Result<(), mlua::Error> {
+    let Some(hook) = &self.response else {
+      return Ok(());
+    };
+
+    let call = || {
+      let headers = self.lua.create_table()?;
+      headers.set_metatable(Some(self.headers.clone()));
+      for (name, value) in &res.headers {
+        // like in a http client, the repeated headers are joined
+        let value = match headers.get::<Option<mlua::String>>(name.as_str())? {
+          Some(prev) => [&prev.as_bytes()[..], b", ", value].concat(),
+          None => value.clone(),
+        };
+        headers.set(name.as_str(), self.lua.create_string(value)?)?;
+      }
+
+      hook.call::<()>((res.status, headers, self.lua.create_string(&res.body)?))
+    };
+
+    match call() {
+      Ok(()) => Ok(()),
+      Err(e) => self.fail("response", e),
+    }
+  }
+
+  /// Calls the delay hook, the time to wait before the next request
+  pub fn delay(&self) -> Option<Duration> {
+    let hook = self.delay.as_ref()?;
+    match hook.call::<f64>(()) {
+      Ok(ms) if ms.is_finite() && ms > 0.0 => Some(Duration::from_secs_f64(ms / 1000.0)),
+      Ok(_) => None,
+      Err(e) => {
+        let _ = self.fail::<()>("delay", e);
+        None
+      }
+    }
+  }
+}
+
+fn parse_raw(value: Value) -> Result<Option<ScriptRequest>, mlua::Error> {
+  match value {
+    Value::String(request) => Ok(Some(ScriptRequest::Raw(Arc::from(&request.as_bytes()[..])))),
+    other => Err(mlua::Error::runtime(format!(
+      "invalid request returned by the request hook, must be a string built with wrk.format(), not a {}",
+      other.type_name()
+    ))),
+  }
+}
+
+fn parse_request(value: Value) -> Result<Option<ScriptRequest>, mlua::Error> {
+  let invalid = |msg: String| mlua::Error::runtime(format!("invalid request returned by the request hook, {msg}"));
+
+  let table = match value {
+    Value::Nil => return Ok(None),
+    Value::Table(table) => table,
+    other => return Err(invalid(format!("must be a table or nil, not a {}", other.type_name()))),
+  };
+
+  let method = match table.get::<Option<String>>("method")? {
+    None => http::Method::GET,
+    Some(method) => {
+      http::Method::from_bytes(method.trim().to_uppercase().as_bytes()).map_err(|_| invalid(format!("invalid method {method}")))?
+    }
+  };
+
+  let path = table
+    .get::<Option<String>>("path")?
+    .map(|path| http::uri::PathAndQuery::from_str(&path).map_err(|_| invalid(format!("invalid path {path}"))))
+    .transpose()?;
+
+  if path.as_ref().is_some_and(|path| !path.as_str().starts_with('/')) {
+    return Err(invalid(String::from("the path must start with /")));
+  }
+
+  let mut headers = Vec::new();
+  if let Some(list) = table.get::<Option<Table>>("headers")? {
+    for pair in list.pairs::<String, mlua::String>() {
+      let (name, value) = pair?;
+      let name = http::HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid(format!("invalid header name {name}")))?;
+      let value = http::HeaderValue::from_bytes(value.as_bytes().trim_ascii())
+        .map_err(|_| invalid(format!("invalid value of header {name}")))?;
+      headers.push((name, value));
+    }
+  }
+
+  let body = table.get::<Option<mlua::String>>("body")?.map(|body| body.as_bytes().to_vec());
+
+  Ok(Some(ScriptRequest::Table {
+    method,
+    path,
+    headers,
+    body,
+  }))
+}
+
+fn path_and_query<'a>(path: &'a Option<http::uri::PathAndQuery>, url: &'a Url) -> &'a str {
+  match path {
+    Some(path) => path.as_str(),
+    None => &url[url::Position::BeforePath..url::Position::AfterQuery],
+  }
+}
+
+impl ScriptRequest {
+  /// Encodes the request as h1 to write it directly to the socket
+  #[cfg(feature = "h1")]
+  pub fn write_h1(&self, url: &Url, keepalive: bool, buf: &mut Vec<u8>) {
+    use std::io::Write;
+
+    buf.clear();
+    let (method, path, headers, body) = match self {
+      ScriptRequest::Table { method, path, headers, body } => (method, path, headers, body),
+      ScriptRequest::Raw(request) => {
+        buf.extend_from_slice(request);
+        return;
+      }
+    };
+
+    let _ = write!(buf, "{} {} HTTP/1.1\r\nhost: {}\r\n", method, path_and_query(path, url), url.host_str().unwrap_or_default());
+
+    for (name, value) in headers {
+      buf.extend_from_slice(name.as_str().as_bytes());
+      buf.extend_from_slice(b": ");
+      buf.extend_from_slice(value.as_bytes());
+      buf.extend_from_slice(b"\r\n");
+    }
+
+    if !keepalive {
+      buf.extend_from_slice(b"connection: close\r\n");
+    }
+
+    let body = body.as_deref().unwrap_or_default();
+    let _ = write!(buf, "content-length: {}\r\n\r\n", body.len());
+    buf.extend_from_slice(body);
+  }
+
+  /// Builds the request as h2
+  #[cfg(feature = "h2")]
+  pub fn to_h2(&self, url: &Url) -> (http::Request<()>, Option<bytes::Bytes>) {
+    let (method, path, headers, body) = match self {
+      ScriptRequest::Table { method, path, headers, body } => (method, path, headers, body),
+      ScriptRequest::Raw(_) => unreachable!("the api of wrk is only allowed with http/1.1"),
+    };
+
+    let mut req = http::Request::new(());
+    *req.method_mut() = method.clone();
+    // the origin of a valid url followed by a valid path and query is always a valid uri
+    *req.uri_mut() = http::Uri::try_from(format!("{}{}", url.origin().ascii_serialization(), path_and_query(path, url))).unwrap();
+
+    let body = body.clone().unwrap_or_default();
+    req.headers_mut().insert(http::header::CONTENT_LENGTH, body.len().into());
+    for (name, value) in headers {
+      req.headers_mut().append(name, value.clone());
+    }
+
+    (req, Some(bytes::Bytes::from(body)))
+  }
+}
+
+/// Runs the done hook of the script with the results of the run, if it has one, in the interpreter that loaded the script,
+/// `globals` are the globals of each thread at the end of the run, for thread:get(name) with the api of wrk
+pub fn done(source: &ScriptSource, report: &Report, globals: Vec<Vec<(String, Global)>>) -> Result<(), anyhow::Error> {
+  let lua = &source.main.lua;
+  let Some(done) = hook(lua, &source.path, "done")? else {
+    return Ok(());
+  };
+
+  let call = || match &source.wrk {
+    None => done.call::<()>(report_table(lua, report)?),
+    Some(_) => {
+      for (state, globals) in source.main.threads.borrow_mut().iter_mut().zip(globals) {
+        *state = globals.into_iter().collect();
+      }
+      done.call::<()>(wrk_done_args(lua, report)?)
+    }
+  };
+
+  call()
+    .map_err(lua_error)
+    .with_context(|| format!("error in the done hook of script {}", source.path))
+}
+
+/// The stats passed to done with the api of wrk, `percentile` is called as stats:percentile(p) like in wrk
+fn stats_table(lua: &Lua, min: u64, max: u64, mean: f64, stdev: f64, percentile: impl Fn(f64) -> u64 + 'static) -> Result<Table, mlua::Error> {
+  let table = lua.create_table()?;
+  table.set("min", min)?;
+  table.set("max", max)?;
+  table.set("mean", mean)?;
+  table.set("stdev", stdev)?;
+  table.set(
+    "percentile",
+    lua.create_function(move |_, (first, second): (Value, Option<f64>)| {
+      let p = match (second, first) {
+        (Some(p), _) => p,
+        (None, Value::Integer(p)) => p as f64,
+        (None, Value::Number(p)) => p,
+        _ => return Err(mlua::Error::runtime("percentile must be called with a number from 0 to 100")),
+      };
+      Ok(percentile(p))
+    })?,
+  )?;
+
+  Ok(table)
+}
+
+/// The summary, the latencies in microseconds and the requests per second of each thread, the arguments of done with the api of wrk
+fn wrk_done_args(lua: &Lua, report: &Report) -> Result<(Table, Table, Table), mlua::Error> {
+  let summary = lua.create_table()?;
+  summary.set("duration", report.elapsed.as_micros() as u64)?;
+  summary.set("requests", report.ok)?;
+  summary.set("bytes", report.read)?;
+
+  let errors = WrkErrors::new(report);
+  let table = lua.create_table()?;
+  table.set("connect", errors.connect)?;
+  table.set("read", errors.read)?;
+  table.set("write", errors.write)?;
+  table.set("status", errors.status)?;
+  table.set("timeout", errors.timeout)?;
+  summary.set("errors", table)?;
+
+  #[cfg(feature = "latency")]
+  let latency = match &report.hdr {
+    Some(hdr) => {
+      let us = |nanos: u64| nanos / 1000;
+      let hdr = hdr.clone();
+      stats_table(lua, us(hdr.min()), us(hdr.max()), hdr.mean() / 1000.0, hdr.stdev() / 1000.0, move |p| {
+        us(hdr.value_at_percentile(p))
+      })?
+    }
+    None => stats_table(lua, 0, 0, 0.0, 0.0, |_| 0)?,
+  };
+  #[cfg(not(feature = "latency"))]
+  let latency = stats_table(lua, 0, 0, 0.0, 0.0, |_| 0)?;
+
+  let rates: Stats = crate::output::thread_rates(report);
+  let requests = stats_table(lua, rates.min as u64, rates.max as u64, rates.mean, rates.stdev, move |p| {
+    rates.percentile(p) as u64
+  })?;
+
+  Ok((summary, latency, requests))
+}
+
+/// The results as a lua table, the durations in seconds and the latencies in milliseconds
+fn report_table(lua: &Lua, report: &Report) -> Result<Table, mlua::Error> {
+  let table = lua.create_table()?;
+  table.set("url", report.url.as_str())?;
+  table.set("elapsed", report.elapsed.as_secs_f64())?;
+  table.set("requests", report.ok)?;
+  table.set("errors", report.errors())?;
+  table.set("read", report.read)?;
+  table.set("write", report.write)?;
+
+  #[cfg(feature = "error-detail")]
+  {
+    let kinds = lua.create_table()?;
+    for (kind, count) in report.err.iter() {
+      kinds.set(kind.to_string(), count)?;
+    }
+    table.set("error_kinds", kinds)?;
+  }
+
+  #[cfg(feature = "status-detail")]
+  {
+    let statuses = lua.create_table()?;
+    for (status, count) in &report.statuses {
+      statuses.set(*status, *count)?;
+    }
+    table.set("statuses", statuses)?;
+  }
+
+  #[cfg(feature = "latency")]
+  if let Some(hdr) = &report.hdr {
+    let ms = |nanos: f64| nanos / 1_000_000.0;
+    let latency = lua.create_table()?;
+    latency.set("min", ms(hdr.min() as f64))?;
+    latency.set("max", ms(hdr.max() as f64))?;
+    latency.set("mean", ms(hdr.mean()))?;
+    latency.set("stdev", ms(hdr.stdev()))?;
+    let hdr = hdr.clone();
+    latency.set(
+      "percentile",
+      lua.create_function(move |_, p: f64| Ok(ms(hdr.value_at_quantile(p / 100.0) as f64)))?,
+    )?;
+    table.set("latency", latency)?;
+  }
+
+  let endpoints = lua.create_table()?;
+  for endpoint in &report.endpoints {
+    let row = lua.create_table()?;
+    row.set("name", endpoint.name.as_str())?;
+    row.set("weight", endpoint.weight)?;
+    row.set("requests", endpoint.ok)?;
+    row.set("errors", endpoint.err)?;
+    endpoints.push(row)?;
+  }
+  table.set("endpoints", endpoints)?;
+
+  Ok(table)
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use crate::profile::Profile;
+
+/// The target a stage ramps to
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Target {
+  /// number of active connections
+  Connections(usize),
+  /// requests per second
+  Rate(f64),
+}
+
+impl Target {
+  pub fn value(self) -> f64 {
+    match self {
+      Target::Connections(n) => n as f64,
+      Target::Rate(rate) => rate,
+    }
+  }
+}
+
+impl std::fmt::Display for Target {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Target::Connections(n) => write!(f, "{n}"),
+      Target::Rate(rate) => write!(f, "{rate}/s"),
+    }
+  }
+}
+
+/// A stage of a load profile, that linearly ramps from the target of the previous stage
+/// (or the initial level for the first one) to its own target over its duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stage {
+  pub duration: Duration,
+  pub target: Target,
+}
+
+impl std::fmt::Display for Stage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", crate::fmt::format_duration(self.duration), self.target)
+  }
+}
+
+/// Parses a stage in the format of duration:connections (eg: 30s:10) or duration:rate/s (eg: 2m:5000/s)
+pub fn parse_stage(s: &str) -> Result<Stage, String> {
+  let (duration, target) = s
+    .trim()
+    .split_once(':')
+    .ok_or_else(|| String::from("invalid stage, stage must be in the format of duration:connections (eg: 30s:10) or duration:rate/s (eg: 2m:5000/s)"))?;
+
+  let duration = crate::args::parse_duration(duration)?;
+
+  let target = match target.trim().strip_suffix("/s") {
+    Some(rate) => {
+      let rate = rate
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid stage rate {rate}, must be a number"))?;
+
+      if !rate.is_finite() || rate < 0.0 {
+        return Err(format!("invalid stage rate {rate}, must be equal or greater than 0"));
+      }
+
+      Target::Rate(rate)
+    }
+
+    None => {
+      let connections = target
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("invalid stage connections {target}, must be an integer"))?;
+      Target::Connections(connections)
+    }
+  };
+
+  Ok(Stage { duration, target })
+}
+
+/// Creates the profile that follows the ramps of the stages starting from `initial`
+pub fn profile(stages: &[Stage], initial: f64) -> Profile {
+  let mut points = vec![(Duration::ZERO, initial)];
+  let mut offset = Duration::ZERO;
+  for stage in stages {
+    offset += stage.duration;
+    points.push((offset, stage.target.value()));
+  }
+
+  Profile::from_points(points)
+}
+
+/// The sum of the durations of the stages
+pub fn total_duration(stages: &[Stage]) -> Duration {
+  stages.iter().map(|stage| stage.duration).sum()
+}
+
+/// The index of the stage that is running at `elapsed` since the start, after the end of the last stage this is the last one
+#[inline(always)]
+pub fn index_at(stages: &[Stage], elapsed: Duration) -> usize {
+  let mut end = Duration::ZERO;
+  for (i, stage) in stages.iter().enumerate() {
+    end += stage.duration;
+    if elapsed < end {
+      return i;
+    }
+  }
+
+  stages.len().saturating_sub(1)
+}
@@ -0,0 +1,593 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::{borrow::Cow, io::Write, time::SystemTime};
+
+use crate::{
+  data::{DataOrder, Row},
+  error::ErrorKind,
+};
+
+/// The names of the built-in variables, the columns of a data file cannot use them
+pub const BUILTIN: &[&str] = &["uuid", "seq", "rand_int", "now_ms", "thread_id"];
+
+/// A variable of a template, rendered again for each request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Var {
+  /// a random version 4 uuid
+  Uuid,
+  /// a sequence number unique across all threads
+  Seq,
+  /// a random integer between both bounds, inclusive
+  RandInt(i64, i64),
+  /// the unix time in milliseconds
+  NowMs,
+  /// the index of the thread that sends the request
+  ThreadId,
+  /// the value of a column of the data file in the current row
+  Column(usize),
+  /// the value extracted from a previous response of the same connection
+  Capture(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+  Static(Box<[u8]>),
+  Var(Var),
+}
+
+/// The part of the request a template is rendered in, that decides how the values of its variables are escaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+  /// the path or the query, the values are percent-encoded so they can't add a space or another part to the url
+  Uri,
+  /// a header value, a value with a control character (eg: \r\n) fails the request instead of adding a header
+  Header,
+  /// the body, the values are copied as they are
+  Body,
+}
+
+/// A precompiled template, the static segments are copied as they are and the variables are rendered for each request
+///
+/// A variable is written as `{{name}}` or `{{name arg arg}}`, the available ones are `{{uuid}}`, `{{seq}}`, `{{rand_int min max}}`,
+/// `{{now_ms}}` and `{{thread_id}}`, with a data file each of its columns by its name (eg: `{{user_id}}`)
+/// and in a scenario each value extracted from the responses by its name (eg: `{{token}}`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+  segments: Vec<Segment>,
+  slot: Slot,
+}
+
+impl Template {
+  /// Parses a template rendered in `slot`, `columns` are the names of the columns of the data file and `captures` the names of the values
+  /// extracted from the responses, if any
+  pub fn parse(src: &[u8], slot: Slot, columns: &[String], captures: &[String]) -> Result<Self, String> {
+    let mut segments = Vec::new();
+    let mut rest = src;
+
+    while let Some(open) = find(rest, b"{{") {
+      if open != 0 {
+        segments.push(Segment::Static(rest[..open].into()));
+      }
+
+      let inner = &rest[open + 2..];
+      let close = find(inner, b"}}").ok_or_else(|| String::from("unclosed {{ in template, variables must be in the format of {{name}}"))?;
+
+      let var = std::str::from_utf8(&inner[..close]).map_err(|_| String::from("invalid template variable, must be utf-8"))?;
+      segments.push(Segment::Var(parse_var(var, columns, captures)?));
+
+      rest = &inner[close + 2..];
+    }
+
+    if !rest.is_empty() {
+      segments.push(Segment::Static(rest.into()));
+    }
+
+    Ok(Self { segments, slot })
+  }
+
+  /// A template without variables, that renders `src` as it is
+  pub fn from_static(src: &[u8]) -> Self {
+    Self {
+      segments: vec![Segment::Static(src.into())],
+      slot: Slot::Body,
+    }
+  }
+
+  /// Whether the template has no variables, so it renders always the same
+  pub fn is_static(&self) -> bool {
+    self.segments.iter().all(|segment| matches!(segment, Segment::Static(_)))
+  }
+
+  /// The columns of the data file rendered in the template
+  pub fn columns(&self) -> impl Iterator<Item = usize> + '_ {
+    self.segments.iter().filter_map(|segment| match segment {
+      Segment::Var(Var::Column(i)) => Some(*i),
+      _ => None,
+    })
+  }
+
+  /// Appends the template rendered with the values of the current request to `buf`,
+  /// fails when a value extracted from a response can't be sent in a header
+  #[inline(always)]
+  pub fn render(&self, vars: &mut Vars, buf: &mut Vec<u8>) -> Result<(), ErrorKind> {
+    for segment in &self.segments {
+      match segment {
+        Segment::Static(bytes) => buf.extend_from_slice(bytes),
+        Segment::Var(var) => {
+          let at = buf.len();
+          vars.write(*var, buf);
+          match self.slot {
+            Slot::Body => {}
+            // the built-in variables are never encoded, so they don't need to be copied
+            Slot::Uri => {
+              if !buf[at..].iter().copied().all(is_unreserved) {
+                let value = buf.split_off(at);
+                percent_encode(&value, buf);
+              }
+            }
+            // the columns of the data file are checked once when it's loaded, see `is_header_safe`
+            Slot::Header => {
+              if matches!(var, Var::Capture(_)) && !is_header_safe(&buf[at..]) {
+                return Err(ErrorKind::Template);
+              }
+            }
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// The bytes that are never percent-encoded in a value of the url, as in encodeURIComponent
+#[inline(always)]
+fn is_unreserved(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+fn percent_encode(value: &[u8], buf: &mut Vec<u8>) {
+  const HEX: &[u8; 16] = b"0123456789ABCDEF";
+  for &byte in value {
+    match is_unreserved(byte) {
+      true => buf.push(byte),
+      false => buf.extend_from_slice(&[b'%', HEX[(byte >> 4) as usize], HEX[(byte & 0x0f) as usize]]),
+    }
+  }
+}
+
+/// Whether a value can be sent in a header, without control characters other than tab
+pub fn is_header_safe(value: &[u8]) -> bool {
+  value.iter().all(|&byte| byte == b'\t' || (byte >= 0x20 && byte != 0x7f))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn parse_var(s: &str, columns: &[String], captures: &[String]) -> Result<Var, String> {
+  let mut parts = s.split_whitespace();
+  let name = parts.next().unwrap_or("");
+  let args = parts.collect::<Vec<_>>();
+
+  let var = match (name, args.as_slice()) {
+    ("uuid", []) => Var::Uuid,
+    ("seq", []) => Var::Seq,
+    ("now_ms", []) => Var::NowMs,
+    ("thread_id", []) => Var::ThreadId,
+    ("rand_int", [min, max]) => {
+      let bound = |s: &str| s.parse::<i64>().map_err(|_| format!("invalid rand_int bound {s}, must be an integer"));
+      let (min, max) = (bound(min)?, bound(max)?);
+      if min > max {
+        return Err(format!("invalid template variable {{{{{s}}}}}, the min of rand_int must be equal or less than the max"));
+      }
+      Var::RandInt(min, max)
+    }
+    ("uuid" | "seq" | "now_ms" | "thread_id" | "rand_int", _) => {
+      return Err(format!(
+        "invalid template variable {{{{{s}}}}}, only rand_int takes arguments and must be in the format of {{{{rand_int min max}}}}"
+      ))
+    }
+    (name, args) => match (columns.iter().position(|column| column == name), captures.iter().position(|capture| capture == name)) {
+      (Some(i), _) if args.is_empty() => Var::Column(i),
+      (None, Some(i)) if args.is_empty() => Var::Capture(i),
+      _ => {
+        return Err(format!(
+          "unknown template variable {{{{{s}}}}}, must be one of {{{{uuid}}}}, {{{{seq}}}}, {{{{rand_int min max}}}}, {{{{now_ms}}}}, {{{{thread_id}}}}, a column of the data file or a value extracted by a step of the scenario"
+        ))
+      }
+    },
+  };
+
+  Ok(var)
+}
+
+/// The url crate percent-encodes the braces and spaces of a path and a query,
+/// this turns the encoded variables back into template variables
+pub fn decode_vars(s: &str) -> Cow<'_, str> {
+  if !s.contains("%7B%7B") && !s.contains("{{") {
+    return Cow::Borrowed(s);
+  }
+
+  // the braces are only encoded in the path, the spaces also in the query
+  let s = s.replace("%7B%7B", "{{").replace("%7D%7D", "}}");
+
+  let mut decoded = String::with_capacity(s.len());
+  let mut rest = s.as_str();
+  while let Some(open) = rest.find("{{") {
+    let Some(close) = rest[open..].find("}}").map(|close| open + close) else {
+      break;
+    };
+
+    decoded.push_str(&rest[..open]);
+    decoded.push_str(&rest[open..close].replace("%20", " "));
+    rest = &rest[close..];
+  }
+
+  decoded.push_str(rest);
+  Cow::Owned(decoded)
+}
+
+/// The values of the variables of a thread
+///
+/// `{{uuid}}`, `{{seq}}`, `{{now_ms}}` and the columns of the data file have the same value everywhere in a request,
+/// each `{{rand_int min max}}` draws its own number
+#[derive(Debug, Clone)]
+pub struct Vars {
+  rng: SmallRng,
+  /// the rows of the data file this thread takes from
+  rows: &'static [Row],
+  order: DataOrder,
+  cursor: usize,
+  row: usize,
+  thread: usize,
+  /// each thread takes every `threads`-th sequence number starting at its own index, so they are unique without any synchronization
+  step: u64,
+  next_seq: u64,
+  seq: u64,
+  uuid: Option<[u8; 36]>,
+  now_ms: Option<u64>,
+  /// the connection of the current request
+  conn: usize,
+  /// the values extracted from the responses, by connection and by variable
+  captures: Vec<Vec<Vec<u8>>>,
+}
+
+impl Vars {
+  /// Each thread seeds its own generator from `seed` and its index, so a run with the same seed renders the same random values
+  pub fn new(seed: u64, threads: usize, index: usize, rows: &'static [Row], order: DataOrder) -> Self {
+    Self {
+      rows,
+      order,
+      cursor: 0,
+      row: 0,
+      // the schedule of arrivals is seeded the same way, this keeps both sequences of numbers apart
+      rng: SmallRng::seed_from_u64(seed.wrapping_add(index as u64).rotate_left(32)),
+      thread: index,
+      step: threads as u64,
+      next_seq: index as u64,
+      seq: 0,
+      uuid: None,
+      now_ms: None,
+      conn: 0,
+      captures: Vec::new(),
+    }
+  }
+
+  /// Binds a value extracted from a response of the connection `conn` to the variable `var`, until the next one
+  pub fn set_capture(&mut self, conn: usize, var: usize, value: Vec<u8>) {
+    if self.captures.len() <= conn {
+      self.captures.resize_with(conn + 1, Vec::new);
+    }
+
+    let captures = &mut self.captures[conn];
+    if captures.len() <= var {
+      captures.resize_with(var + 1, Vec::new);
+    }
+
+    captures[var] = value;
+  }
+
+  /// Starts a new request sent by the connection `conn` of this thread, the values of the previous one are discarded
+  #[inline(always)]
+  pub fn next_request(&mut self, conn: usize) {
+    if !self.rows.is_empty() {
+      self.row = match self.order {
+        DataOrder::Sequential => {
+          let row = self.cursor;
+          self.cursor = (self.cursor + 1) % self.rows.len();
+          row
+        }
+        DataOrder::Random => self.rng.random_range(0..self.rows.len()),
+        DataOrder::Sticky => conn % self.rows.len(),
+      };
+    }
+
+    self.conn = conn;
+    self.seq = self.next_seq;
+    self.next_seq += self.step;
+    self.uuid = None;
+    self.now_ms = None;
+  }
+
+  #[inline(always)]
+  fn write(&mut self, var: Var, buf: &mut Vec<u8>) {
+    // writing to a vec never fails
+    let _ = match var {
+      Var::Seq => write!(buf, "{}", self.seq),
+      Var::ThreadId => write!(buf, "{}", self.thread),
+      Var::Column(i) => {
+        buf.extend_from_slice(&self.rows[self.row][i]);
+        Ok(())
+      }
+      // the values not extracted yet are empty
+      Var::Capture(i) => {
+        if let Some(value) = self.captures.get(self.conn).and_then(|captures| captures.get(i)) {
+          buf.extend_from_slice(value);
+        }
+        Ok(())
+      }
+      Var::RandInt(min, max) => write!(buf, "{}", self.rng.random_range(min..=max)),
+      Var::NowMs => {
+        let now_ms = *self.now_ms.get_or_insert_with(|| {
+          SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+        });
+        write!(buf, "{now_ms}")
+      }
+      Var::Uuid => {
+        let rng = &mut self.rng;
+        let uuid = self.uuid.get_or_insert_with(|| uuid_v4(rng));
+        buf.extend_from_slice(uuid);
+        Ok(())
+      }
+    };
+  }
+}
+
+fn uuid_v4(rng: &mut SmallRng) -> [u8; 36] {
+  const HEX: &[u8; 16] = b"0123456789abcdef";
+
+  let mut bytes = rng.random::<u128>().to_be_bytes();
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+  let mut uuid = [b'-'; 36];
+  let mut i = 0;
+  for (n, byte) in bytes.iter().enumerate() {
+    if matches!(n, 4 | 6 | 8 | 10) {
+      i += 1;
+    }
+    uuid[i] = HEX[(byte >> 4) as usize];
+    uuid[i + 1] = HEX[(byte & 0x0f) as usize];
+    i += 2;
+  }
+
+  uuid
+}
+
+/// A h1 request with variables, the content-length is computed after rendering the body
+#[cfg(feature = "h1")]
+#[derive(Debug, Clone)]
+pub struct H1Template {
+  /// the request line followed by \r\n
+  pub line: Template,
+  /// the headers, each one followed by \r\n, without the content-length
+  pub headers: Template,
+  pub body: Option<Template>,
+}
+
+#[cfg(feature = "h1")]
+impl H1Template {
+  /// The columns of the data file rendered in the headers
+  pub fn header_columns(&self) -> impl Iterator<Item = usize> + '_ {
+    self.headers.columns()
+  }
+
+  /// Renders the next request of the connection `conn` into `buf`, replacing its previous content
+  #[inline(always)]
+  pub fn render(&self, vars: &mut Vars, conn: usize, buf: &mut Vec<u8>) -> Result<(), ErrorKind> {
+    vars.next_request(conn);
+    buf.clear();
+    self.line.render(vars, buf)?;
+    self.headers.render(vars, buf)?;
+    buf.extend_from_slice(b"content-length: ");
+
+    // the body is rendered in place and the length is inserted in front of it
+    let at = buf.len();
+    if let Some(body) = &self.body {
+      body.render(vars, buf)?;
+    }
+
+    let mut length = [0u8; 32];
+    let mut cursor = &mut length[..];
+    let _ = write!(cursor, "{}\r\n\r\n", buf.len() - at);
+    let written = 32 - cursor.len();
+    buf.splice(at..at, length[..written].iter().copied());
+
+    Ok(())
+  }
+}
+
+/// A h2 request with variables in the uri, in the header values or in the body
+#[cfg(feature = "h2")]
+#[derive(Debug, Clone)]
+pub struct H2Template {
+  /// the request with the static headers
+  pub req: http::Request<()>,
+  pub uri: Option<Template>,
+  pub headers: Vec<(http::header::HeaderName, Template)>,
+  pub body: Option<Template>,
+}
+
+#[cfg(feature = "h2")]
+impl H2Template {
+  /// The columns of the data file rendered in the header values
+  pub fn header_columns(&self) -> impl Iterator<Item = usize> + '_ {
+    self.headers.iter().flat_map(|(_, value)| value.columns())
+  }
+
+  /// Renders the next request of the connection `conn`
+  pub fn render(&self, vars: &mut Vars, conn: usize) -> Result<(http::Request<()>, Option<bytes::Bytes>), ErrorKind> {
+    vars.next_request(conn);
+    let mut req = self.req.clone();
+
+    if let Some(uri) = &self.uri {
+      let mut buf = Vec::new();
+      uri.render(vars, &mut buf)?;
+      *req.uri_mut() = http::Uri::from_maybe_shared(bytes::Bytes::from(buf)).map_err(|_| ErrorKind::Template)?;
+    }
+
+    for (name, value) in &self.headers {
+      let mut buf = Vec::new();
+      value.render(vars, &mut buf)?;
+      let value = http::HeaderValue::from_maybe_shared(bytes::Bytes::from(buf)).map_err(|_| ErrorKind::Template)?;
+      req.headers_mut().append(name, value);
+    }
+
+    let body = match &self.body {
+      None => None,
+      Some(body) => {
+        let mut buf = Vec::new();
+        body.render(vars, &mut buf)?;
+        Some(bytes::Bytes::from(buf))
+      }
+    };
+
+    let content_length = body.as_ref().map_or(0, |body| body.len());
+    req.headers_mut().insert(http::header::CONTENT_LENGTH, content_length.into());
+
+    Ok((req, body))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(template: &Template) -> Vec<u8> {
+    let mut vars = Vars::new(0, 1, 0, &[], DataOrder::Sequential);
+    vars.next_request(0);
+    let mut buf = Vec::new();
+    template.render(&mut vars, &mut buf).unwrap();
+    buf
+  }
+
+  /// Renders a template with a single variable named value, as a column of the data file and as a value extracted from a response
+  fn render_value(src: &[u8], slot: Slot, value: &[u8]) -> (Result<Vec<u8>, ErrorKind>, Result<Vec<u8>, ErrorKind>) {
+    let names = [String::from("value")];
+    let row: Row = vec![Box::from(value)].into_boxed_slice();
+    let rows: &'static [Row] = Vec::leak(vec![row]);
+
+    let mut vars = Vars::new(0, 1, 0, rows, DataOrder::Sequential);
+    vars.set_capture(0, 0, value.to_vec());
+    vars.next_request(0);
+
+    let mut render = |template: Template| {
+      let mut buf = Vec::new();
+      template.render(&mut vars, &mut buf).map(|()| buf)
+    };
+
+    (
+      render(Template::parse(src, slot, &names, &[]).unwrap()),
+      render(Template::parse(src, slot, &[], &names).unwrap()),
+    )
+  }
+
+  #[test]
+  fn parses_static_text() {
+    let template = Template::parse(b"/items?page=1", Slot::Body, &[], &[]).unwrap();
+    assert!(template.is_static());
+    assert_eq!(render(&template), b"/items?page=1");
+  }
+
+  #[test]
+  fn parses_variables() {
+    let columns = [String::from("user_id")];
+    let captures = [String::from("token")];
+
+    let template = Template::parse(b"/items/{{seq}}?u={{ user_id }}&t={{token}}&n={{rand_int 5 5}}", Slot::Body, &columns, &captures).unwrap();
+    assert!(!template.is_static());
+    assert_eq!(
+      template.segments,
+      vec![
+        Segment::Static(b"/items/"[..].into()),
+        Segment::Var(Var::Seq),
+        Segment::Static(b"?u="[..].into()),
+        Segment::Var(Var::Column(0)),
+        Segment::Static(b"&t="[..].into()),
+        Segment::Var(Var::Capture(0)),
+        Segment::Static(b"&n="[..].into()),
+        Segment::Var(Var::RandInt(5, 5)),
+      ]
+    );
+  }
+
+  #[test]
+  fn renders_variables() {
+    let template = Template::parse(b"{{seq}}-{{thread_id}}-{{rand_int 7 7}}", Slot::Body, &[], &[]).unwrap();
+    assert_eq!(render(&template), b"0-0-7");
+  }
+
+  #[test]
+  fn rejects_unclosed_braces() {
+    let err = Template::parse(br#"{"a":"{{x"}"#, Slot::Body, &[], &[]).unwrap_err();
+    assert!(err.contains("unclosed {{"), "{err}");
+  }
+
+  #[test]
+  fn rejects_unknown_and_invalid_variables() {
+    assert!(Template::parse(b"{{nope}}", Slot::Body, &[], &[]).unwrap_err().contains("unknown template variable"));
+    assert!(Template::parse(b"{{seq 1}}", Slot::Body, &[], &[]).unwrap_err().contains("only rand_int takes arguments"));
+    assert!(Template::parse(b"{{rand_int 9 1}}", Slot::Body, &[], &[]).is_err());
+    assert!(Template::parse(b"{{rand_int a 1}}", Slot::Body, &[], &[]).is_err());
+    assert!(Template::parse(b"{{\xff}}", Slot::Body, &[], &[]).is_err());
+  }
+
+  #[test]
+  fn percent_encodes_values_in_the_url() {
+    let (column, capture) = render_value(b"/items/{{value}}?q={{value}}", Slot::Uri, b"a b&c=d/\r\n");
+    let expected = b"/items/a%20b%26c%3Dd%2F%0D%0A?q=a%20b%26c%3Dd%2F%0D%0A".to_vec();
+    assert_eq!(column.unwrap(), expected);
+    assert_eq!(capture.unwrap(), expected);
+
+    let (column, _) = render_value(b"/items/{{value}}", Slot::Uri, b"a-1.b_~");
+    assert_eq!(column.unwrap(), b"/items/a-1.b_~");
+  }
+
+  #[test]
+  fn rejects_control_characters_in_headers() {
+    let (_, capture) = render_value(b"bearer {{value}}", Slot::Header, b"x\r\nx-injected: 1");
+    assert!(matches!(capture, Err(ErrorKind::Template)));
+
+    let (_, capture) = render_value(b"bearer {{value}}", Slot::Header, b"a\tb c");
+    assert_eq!(capture.unwrap(), b"bearer a\tb c");
+
+    assert!(is_header_safe(b"a\tb c"));
+    assert!(!is_header_safe(b"a\rb"));
+    assert!(!is_header_safe(b"a\x00b"));
+    assert!(!is_header_safe(b"a\x7fb"));
+  }
+
+  #[test]
+  fn copies_values_in_the_body() {
+    let (column, capture) = render_value(br#"{"a":"{{value}}"}"#, Slot::Body, b"x\r\n y");
+    assert_eq!(column.unwrap(), b"{\"a\":\"x\r\n y\"}");
+    assert_eq!(capture.unwrap(), b"{\"a\":\"x\r\n y\"}");
+  }
+
+  #[test]
+  fn static_templates_keep_braces() {
+    let template = Template::from_static(br#"{"a":"{{x"}"#);
+    assert!(template.is_static());
+    assert_eq!(render(&template), br#"{"a":"{{x"}"#);
+  }
+
+  #[test]
+  fn decodes_encoded_variables() {
+    assert_eq!(decode_vars("/items/%7B%7Bseq%7D%7D"), "/items/{{seq}}");
+    assert_eq!(decode_vars("n={{rand_int%201%2010}}&q=a%20b"), "n={{rand_int 1 10}}&q=a%20b");
+    assert!(matches!(decode_vars("/items/1"), Cow::Borrowed(_)));
+  }
+}
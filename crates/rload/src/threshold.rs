@@ -0,0 +1,386 @@
+use std::time::Duration;
+
+use crate::{fmt::format_duration, report::Report};
+
+/// The exit code of a run that failed one of its thresholds, the same as k6, so a CI job can tell it apart from an error
+pub const EXIT_CODE: i32 = 99;
+
+/// A value of the report that a threshold is evaluated against
+#[derive(Debug, Clone, Copy)]
+pub enum Metric {
+  /// a percentile of the latencies corrected for coordinated omission, from 0 to 100
+  Percentile(f64),
+  /// the mean of the corrected latencies
+  Mean,
+  /// the max of the corrected latencies
+  Max,
+  /// the fulfilled requests per second
+  Rps,
+  /// the number of fulfilled requests
+  Fulfilled,
+  /// the failed requests, as a count or as a ratio of all the requests with a percentage
+  Errors,
+  /// the responses with a status in this range, as a count or as a ratio of all the responses with a percentage
+  Status(u16, u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  Eq,
+  Ne,
+}
+
+impl Op {
+  fn holds(self, actual: f64, expected: f64) -> bool {
+    match self {
+      Op::Lt => actual < expected,
+      Op::Le => actual <= expected,
+      Op::Gt => actual > expected,
+      Op::Ge => actual >= expected,
+      Op::Eq => actual == expected,
+      Op::Ne => actual != expected,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Value {
+  Duration(Duration),
+  Number(f64),
+  /// a percentage, from 0 to 1
+  Ratio(f64),
+}
+
+/// A condition the results of the run must meet (eg: p99<50ms), a run that fails any of them exits with [`EXIT_CODE`]
+#[derive(Debug, Clone)]
+pub struct Threshold {
+  pub metric: Metric,
+  pub op: Op,
+  pub value: Value,
+  /// the threshold as it was given
+  pub spec: String,
+}
+
+/// The result of a threshold at the end of the run
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ThresholdReport {
+  pub spec: String,
+  /// the value of the metric, formatted like the value of the threshold
+  pub actual: String,
+  pub passed: bool,
+}
+
+/// Parses a threshold in the format of metric, operator and value (eg: p99<50ms, rps>10000, errors<0.1%, status_5xx==0)
+///
+/// The metrics are the percentiles of the latencies (p50, p99.9, ...), mean and max, compared with a duration,
+/// rps and fulfilled, compared with a number, and errors and status_NNN or status_Nxx, compared with a count or a percentage of the requests
+pub fn parse_threshold(s: &str) -> Result<Threshold, String> {
+  let spec = s.trim();
+  let invalid = || format!("invalid threshold {spec}, must be a metric, an operator and a value (eg: p99<50ms, rps>10000, errors<0.1% or status_5xx==0)");
+
+  let at = spec.find(['<', '>', '=', '!']).ok_or_else(invalid)?;
+  let (metric, rest) = spec.split_at(at);
+  let metric = metric.trim();
+
+  let (op, value) = [("<=", Op::Le), (">=", Op::Ge), ("==", Op::Eq), ("!=", Op::Ne), ("<", Op::Lt), (">", Op::Gt)]
+    .into_iter()
+    .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (op, value.trim())))
+    .ok_or_else(invalid)?;
+
+  let metric = match metric {
+    "mean" => Metric::Mean,
+    "max" => Metric::Max,
+    "rps" => Metric::Rps,
+    "fulfilled" => Metric::Fulfilled,
+    "errors" => Metric::Errors,
+    _ => {
+      if let Some(p) = metric.strip_prefix('p') {
+        match p.parse::<f64>() {
+          Ok(p) if (0.0..=100.0).contains(&p) => Metric::Percentile(p),
+          _ => return Err(format!("invalid threshold {spec}, the percentile {metric} must be between p0 and p100 (eg: p99.9)")),
+        }
+      } else if let Some(status) = metric.strip_prefix("status_") {
+        let range = match status.strip_suffix("xx") {
+          Some(class @ ("1" | "2" | "3" | "4" | "5")) => {
+            let class = (class.as_bytes()[0] - b'0') as u16;
+            (class * 100, class * 100 + 99)
+          }
+          Some(_) => return Err(format!("invalid threshold {spec}, the status class {status} must be one of 1xx to 5xx")),
+          None => match status.parse::<u16>() {
+            Ok(status @ 100..=599) => (status, status),
+            _ => return Err(format!("invalid threshold {spec}, the status {status} must be between 100 and 599")),
+          },
+        };
+        Metric::Status(range.0, range.1)
+      } else {
+        return Err(format!("invalid threshold {spec}, unknown metric {metric}, must be one of pN (eg: p99), mean, max, rps, fulfilled, errors or status_NNN (eg: status_5xx)"));
+      }
+    }
+  };
+
+  #[cfg(not(feature = "latency"))]
+  if matches!(metric, Metric::Percentile(_) | Metric::Mean | Metric::Max) {
+    return Err(format!("invalid threshold {spec}, feature latency must be enabled at compile time to use thresholds on the latencies"));
+  }
+
+  #[cfg(not(feature = "status-detail"))]
+  if matches!(metric, Metric::Status(..)) {
+    return Err(format!("invalid threshold {spec}, feature status-detail must be enabled at compile time to use thresholds on the statuses"));
+  }
+
+  let value = match metric {
+    Metric::Percentile(_) | Metric::Mean | Metric::Max => Value::Duration(crate::args::parse_duration(value).map_err(|_| {
+      format!("invalid threshold {spec}, the latencies are compared with a duration (eg: 50ms)")
+    })?),
+    Metric::Rps | Metric::Fulfilled => Value::Number(value.parse::<f64>().map_err(|_| format!("invalid threshold {spec}, {value} is not a number"))?),
+    Metric::Errors | Metric::Status(..) => match value.ends_with('%') {
+      true => Value::Ratio(crate::args::parse_ratio(value).map_err(|e| format!("invalid threshold {spec}, {e}"))?),
+      false => Value::Number(value.parse::<u64>().map_err(|_| format!("invalid threshold {spec}, {value} must be a count or a percentage (eg: 0.1%)"))? as f64),
+    },
+  };
+
+  Ok(Threshold {
+    metric,
+    op,
+    value,
+    spec: spec.to_string(),
+  })
+}
+
+fn value_name(value: Value) -> String {
+  match value {
+    Value::Duration(duration) => format_duration(duration).to_string(),
+    Value::Number(n) => n.to_string(),
+    Value::Ratio(ratio) => format!("{}%", (ratio * 100_000.0).round() / 1000.0),
+  }
+}
+
+impl Threshold {
+  /// Whether the threshold is on the latencies, that are then measured even without --latency
+  pub fn is_latency(&self) -> bool {
+    matches!(self.metric, Metric::Percentile(_) | Metric::Mean | Metric::Max)
+  }
+
+  /// The value of the metric in the report, in the unit of the value of the threshold, `None` when the report doesn't have it
+  fn actual(&self, report: &Report) -> Option<Value> {
+    let ratio = |count: u64, total: u64| match total {
+      0 => 0.0,
+      total => count as f64 / total as f64,
+    };
+
+    let in_unit = |count: u64, total: u64| match self.value {
+      Value::Ratio(_) => Value::Ratio(ratio(count, total)),
+      _ => Value::Number(count as f64),
+    };
+
+    match self.metric {
+      #[cfg(feature = "latency")]
+      Metric::Percentile(_) | Metric::Mean | Metric::Max => {
+        // without fulfilled requests there are no latencies, that doesn't meet any threshold on them
+        let hdr = report.hdr_corrected.as_ref().filter(|hdr| !hdr.is_empty())?;
+        let nanos = match self.metric {
+          Metric::Percentile(p) => hdr.value_at_percentile(p),
+          Metric::Mean => hdr.mean().round() as u64,
+          _ => hdr.max(),
+        };
+        Some(Value::Duration(Duration::from_nanos(nanos)))
+      }
+
+      #[cfg(not(feature = "latency"))]
+      Metric::Percentile(_) | Metric::Mean | Metric::Max => None,
+
+      Metric::Rps => Some(Value::Number(match report.elapsed.as_secs_f64() {
+        0.0 => 0.0,
+        secs => report.ok as f64 / secs,
+      })),
+
+      Metric::Fulfilled => Some(Value::Number(report.ok as f64)),
+
+      Metric::Errors => {
+        let errors = report.errors();
+        Some(in_unit(errors, report.ok + errors))
+      }
+
+      #[cfg(feature = "status-detail")]
+      Metric::Status(from, to) => {
+        let total = report.statuses.iter().map(|(_, count)| count).sum();
+        let count = report
+          .statuses
+          .iter()
+          .filter(|(status, _)| (from..=to).contains(status))
+          .map(|(_, count)| count)
+          .sum();
+        Some(in_unit(count, total))
+      }
+
+      #[cfg(not(feature = "status-detail"))]
+      Metric::Status(..) => None,
+    }
+  }
+
+  /// Whether the actual value meets the threshold, a missing value or one in another unit doesn't
+  fn passes(&self, actual: Option<Value>) -> bool {
+    match (actual, self.value) {
+      (Some(Value::Duration(actual)), Value::Duration(expected)) => self.op.holds(actual.as_nanos() as f64, expected.as_nanos() as f64),
+      (Some(Value::Number(actual)), Value::Number(expected)) => self.op.holds(actual, expected),
+      (Some(Value::Ratio(actual)), Value::Ratio(expected)) => self.op.holds(actual, expected),
+      _ => false,
+    }
+  }
+
+  pub fn evaluate(&self, report: &Report) -> ThresholdReport {
+    let actual = self.actual(report);
+    let passed = self.passes(actual);
+
+    let actual = match actual {
+      None => String::from("-"),
+      // the rate is shown with two decimals, the counts are integers
+      Some(Value::Number(n)) => format!("{}", (n * 100.0).round() / 100.0),
+      Some(value) => value_name(value),
+    };
+
+    ThresholdReport {
+      spec: self.spec.clone(),
+      actual,
+      passed,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(s: &str) -> Threshold {
+    parse_threshold(s).unwrap_or_else(|e| panic!("{s}: {e}"))
+  }
+
+  #[test]
+  #[cfg(feature = "latency")]
+  fn parses_latency_thresholds() {
+    for (spec, percentile, op, millis) in [
+      ("p99<50ms", Some(99.0), Op::Lt, 50.0),
+      ("p99.9 <= 1s", Some(99.9), Op::Le, 1000.0),
+      ("p0>1ms", Some(0.0), Op::Gt, 1.0),
+      ("p100==2ms", Some(100.0), Op::Eq, 2.0),
+      ("mean<10ms", None, Op::Lt, 10.0),
+    ] {
+      let threshold = parse(spec);
+      assert!(threshold.is_latency(), "{spec}");
+      assert_eq!(threshold.op, op, "{spec}");
+      match (threshold.metric, percentile) {
+        (Metric::Percentile(p), Some(percentile)) => assert_eq!(p, percentile, "{spec}"),
+        (Metric::Mean, None) => {}
+        (metric, _) => panic!("{spec}: unexpected metric {metric:?}"),
+      }
+      assert!(matches!(threshold.value, Value::Duration(d) if d == Duration::from_secs_f64(millis / 1000.0)), "{spec}");
+    }
+
+    assert!(matches!(parse("max>=1s").metric, Metric::Max));
+  }
+
+  #[test]
+  fn parses_counts_and_ratios() {
+    let threshold = parse("rps>10000");
+    assert!(matches!(threshold.metric, Metric::Rps));
+    assert_eq!(threshold.op, Op::Gt);
+    assert!(matches!(threshold.value, Value::Number(n) if n == 10000.0));
+    assert!(!threshold.is_latency());
+
+    assert!(matches!(parse("fulfilled!=0").value, Value::Number(n) if n == 0.0));
+
+    // the errors are a ratio with a percentage and a count otherwise
+    let threshold = parse("errors<0.1%");
+    assert!(matches!(threshold.metric, Metric::Errors));
+    assert!(matches!(threshold.value, Value::Ratio(r) if (r - 0.001).abs() < 1e-12));
+    assert_eq!(threshold.spec, "errors<0.1%");
+
+    let threshold = parse("errors<5");
+    assert!(matches!(threshold.value, Value::Number(n) if n == 5.0));
+  }
+
+  #[test]
+  #[cfg(feature = "status-detail")]
+  fn parses_status_thresholds() {
+    let threshold = parse("status_5xx==0");
+    assert!(matches!(threshold.metric, Metric::Status(500, 599)));
+    assert_eq!(threshold.op, Op::Eq);
+    assert!(matches!(threshold.value, Value::Number(n) if n == 0.0));
+
+    assert!(matches!(parse("status_404<1%").metric, Metric::Status(404, 404)));
+    assert!(matches!(parse("status_404<1%").value, Value::Ratio(r) if r == 0.01));
+  }
+
+  #[test]
+  fn rejects_invalid_thresholds() {
+    let err = |s: &str| parse_threshold(s).unwrap_err();
+
+    for spec in ["", "p99", "p99 50ms", "rps=>10", "rps~10"] {
+      assert!(err(spec).contains("must be a metric, an operator and a value"), "{spec}");
+    }
+
+    assert!(err("latency<50ms").contains("unknown metric latency"));
+    assert!(err("rps>many").contains("many is not a number"));
+    assert!(err("errors<1.5").contains("must be a count or a percentage"));
+    assert!(err("errors<200%").contains("must be between 0% and 100%"));
+
+    #[cfg(feature = "latency")]
+    {
+      assert!(err("p101<50ms").contains("must be between p0 and p100"));
+      assert!(err("p-1<50ms").contains("must be between p0 and p100"));
+      assert!(err("p99<50").contains("compared with a duration"));
+    }
+
+    #[cfg(feature = "status-detail")]
+    {
+      assert!(err("status_6xx==0").contains("must be one of 1xx to 5xx"));
+      assert!(err("status_99==0").contains("must be between 100 and 599"));
+    }
+  }
+
+  #[test]
+  fn passes_values_in_the_unit_of_the_threshold() {
+    let threshold = parse("errors<0.1%");
+    assert!(threshold.passes(Some(Value::Ratio(0.0005))));
+    assert!(!threshold.passes(Some(Value::Ratio(0.001))));
+    // a count is not compared with a ratio
+    assert!(!threshold.passes(Some(Value::Number(0.0))));
+
+    let threshold = parse("errors<5");
+    assert!(threshold.passes(Some(Value::Number(4.0))));
+    assert!(!threshold.passes(Some(Value::Number(5.0))));
+
+    let threshold = parse("rps>10000");
+    assert!(threshold.passes(Some(Value::Number(10000.5))));
+    assert!(!threshold.passes(Some(Value::Number(10000.0))));
+    // a metric the report doesn't have never meets a threshold
+    assert!(!threshold.passes(None));
+
+    #[cfg(feature = "latency")]
+    {
+      let threshold = parse("p99<50ms");
+      assert!(threshold.passes(Some(Value::Duration(Duration::from_micros(49_999)))));
+      assert!(!threshold.passes(Some(Value::Duration(Duration::from_millis(50)))));
+    }
+  }
+
+  #[test]
+  fn compares_with_the_operator() {
+    // the results of comparing 1, 2 and 3 with 2
+    for (op, holds) in [
+      (Op::Lt, [true, false, false]),
+      (Op::Le, [true, true, false]),
+      (Op::Gt, [false, false, true]),
+      (Op::Ge, [false, true, true]),
+      (Op::Eq, [false, true, false]),
+      (Op::Ne, [true, false, true]),
+    ] {
+      assert_eq!([op.holds(1.0, 2.0), op.holds(2.0, 2.0), op.holds(3.0, 2.0)], holds, "{op:?}");
+    }
+  }
+}